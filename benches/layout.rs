@@ -0,0 +1,36 @@
+//! Rough layout timings. Run with `cargo bench --bench layout`.
+
+use console_mermaid::diagram::Config;
+use console_mermaid::render_diagram;
+use std::time::Instant;
+
+fn fan_out(children: usize) -> String {
+    let mut input = String::from("graph LR\n");
+    for idx in 0..children {
+        input.push_str(&format!("Root --> N{}\n", idx));
+    }
+    input
+}
+
+fn chain(edges: usize) -> String {
+    let mut input = String::from("graph TD\n");
+    for idx in 0..edges {
+        input.push_str(&format!("N{} --> N{}\n", idx, idx + 1));
+    }
+    input
+}
+
+fn time(name: &str, input: &str, iterations: u32) {
+    let config = Config::default_config();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        render_diagram(input, &config).expect("render");
+    }
+    println!("{:<16} {:?}/iter", name, start.elapsed() / iterations);
+}
+
+fn main() {
+    time("fan_out_50", &fan_out(50), 5);
+    time("fan_out_200", &fan_out(200), 1);
+    time("chain_20", &chain(20), 5);
+}
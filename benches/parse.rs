@@ -0,0 +1,37 @@
+//! Rough parse timings. Run with `cargo bench --bench parse`.
+
+use console_mermaid::graph;
+use std::time::Instant;
+
+/// A chain of `edges` links mixing plain, labeled and shaped nodes.
+fn mixed_edges(edges: usize) -> String {
+    let mut input = String::from("graph TD\n");
+    for idx in 0..edges {
+        match idx % 3 {
+            0 => input.push_str(&format!("N{} --> N{}\n", idx, idx + 1)),
+            1 => input.push_str(&format!(
+                "N{} -->|step {}| N{}[Node {}]\n",
+                idx,
+                idx,
+                idx + 1,
+                idx + 1
+            )),
+            _ => input.push_str(&format!("N{} --> N{}(Round {})\n", idx, idx + 1, idx + 1)),
+        }
+    }
+    input
+}
+
+fn main() {
+    let input = mixed_edges(500);
+    let iterations = 20;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        graph::parse(&input).expect("parse");
+    }
+    println!(
+        "{:<16} {:?}/iter",
+        "parse_500_edges",
+        start.elapsed() / iterations
+    );
+}
@@ -0,0 +1,174 @@
+use crate::diagram::{Config, Diagram, remove_comments, split_lines};
+use crate::graph::{EdgeEnd, GraphDiagram, GraphProperties, direction_statement};
+use indexmap::IndexMap;
+use regex::Regex;
+use std::sync::LazyLock;
+
+const CLASS_DIAGRAM_KEYWORD: &str = "classDiagram";
+
+static RELATION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(\w+)\s*(<\|--|\*--|o--|-->|--\|>|--\*|--o|<\|\.\.|\.\.\|>|\.\.>|<\.\.|--|\.\.)\s*(\w+)\s*(?::\s*(.+))?$",
+    )
+    .unwrap()
+});
+static CLASS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^class\s+(\w+)\s*(\{\s*(.*?)\s*(\})?)?$").unwrap());
+static MEMBER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\w+)\s*:\s*(.+)$").unwrap());
+/// A `classDiagram`, laid out and drawn by the graph renderer: classes become
+/// boxes split into name, attribute and method sections, and relationships
+/// edges with UML terminators.
+#[derive(Debug, Clone, Default)]
+pub struct ClassDiagram {
+    graph: GraphDiagram,
+}
+
+#[derive(Default)]
+struct Members {
+    attributes: Vec<String>,
+    methods: Vec<String>,
+}
+
+impl Members {
+    fn add(&mut self, member: &str) {
+        let member = member.trim();
+        if member.is_empty() {
+            return;
+        }
+        if member.contains('(') {
+            self.methods.push(member.to_string());
+        } else {
+            self.attributes.push(member.to_string());
+        }
+    }
+}
+
+pub fn is_class_diagram(input: &str) -> bool {
+    for line in input.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("%%") {
+            continue;
+        }
+        return trimmed == CLASS_DIAGRAM_KEYWORD;
+    }
+    false
+}
+
+fn parse_properties(input: &str, config: &Config) -> Result<GraphProperties, String> {
+    let lines = remove_comments(&split_lines(input.trim()));
+    let Some(header) = lines.first() else {
+        return Err("no content found".to_string());
+    };
+    if !is_class_diagram(header) {
+        return Err(format!("expected \"{}\" keyword", CLASS_DIAGRAM_KEYWORD));
+    }
+
+    let mut properties = GraphProperties::new("cli", config);
+    properties.graph_direction = "TD".to_string();
+    let mut classes: IndexMap<String, Members> = IndexMap::new();
+    // Class whose `{ ... }` body is still open.
+    let mut open_body: Option<String> = None;
+    for (idx, line) in lines.iter().enumerate().skip(1) {
+        let trimmed = line.trim();
+        if let Some(name) = &open_body {
+            if trimmed == "}" {
+                open_body = None;
+            } else {
+                classes[name].add(trimmed);
+            }
+            continue;
+        }
+        if let Some(direction) = direction_statement(trimmed) {
+            properties.graph_direction = direction;
+        } else if let Some(caps) = CLASS_RE.captures(trimmed) {
+            let name = caps[1].to_string();
+            properties.add_node(&name, &name);
+            let members = classes.entry(name.clone()).or_default();
+            if let Some(body) = caps.get(3) {
+                for member in body.as_str().split(';') {
+                    members.add(member);
+                }
+            }
+            if caps.get(2).is_some() && caps.get(4).is_none() {
+                open_body = Some(name);
+            }
+        } else if let Some(caps) = RELATION_RE.captures(trimmed) {
+            for name in [&caps[1], &caps[3]] {
+                classes.entry(name.to_string()).or_default();
+            }
+            let label = caps.get(4).map_or("", |m| m.as_str().trim());
+            add_relation(&mut properties, &caps[1], &caps[2], &caps[3], label);
+        } else if let Some(caps) = MEMBER_RE.captures(trimmed) {
+            properties.add_node(&caps[1], &caps[1]);
+            classes
+                .entry(caps[1].to_string())
+                .or_default()
+                .add(&caps[2]);
+        } else {
+            return Err(format!("line {}: invalid syntax: \"{}\"", idx + 1, trimmed));
+        }
+    }
+    if let Some(name) = open_body {
+        return Err(format!("class {} is missing its closing }}", name));
+    }
+
+    for (name, members) in classes {
+        let mut compartments = vec![vec![name.clone()]];
+        if !members.attributes.is_empty() || !members.methods.is_empty() {
+            compartments.push(members.attributes);
+            compartments.push(members.methods);
+        }
+        properties.node_compartments.insert(name, compartments);
+    }
+    Ok(properties)
+}
+
+/// Adds the edge for `lhs <op> rhs`. Edges always run left to right in the
+/// source, with the terminator on whichever end the operator marks.
+fn add_relation(properties: &mut GraphProperties, lhs: &str, op: &str, rhs: &str, label: &str) {
+    let marker = if op.contains('|') {
+        EdgeEnd::Triangle
+    } else if op.contains('*') {
+        EdgeEnd::FilledDiamond
+    } else if op.contains('o') {
+        EdgeEnd::Diamond
+    } else if op.contains('<') || op.contains('>') {
+        EdgeEnd::Arrow
+    } else {
+        EdgeEnd::Open
+    };
+    let points_left = op.starts_with(['<', '*', 'o']);
+    let edge = properties.add_edge(lhs, rhs, label);
+    if points_left {
+        edge.tail = Some(marker);
+        edge.end = EdgeEnd::Open;
+    } else {
+        edge.end = marker;
+    }
+    let index = edge.index;
+    if op.contains("..") {
+        properties.link_styles.insert(
+            index,
+            [("stroke-dasharray".to_string(), "3".to_string())].into(),
+        );
+    }
+}
+
+impl Diagram for ClassDiagram {
+    fn parse(&mut self, input: &str, config: &Config) -> Result<(), String> {
+        self.graph = GraphDiagram::from_properties(parse_properties(input, config)?);
+        Ok(())
+    }
+
+    fn render(&self, config: &Config) -> Result<String, String> {
+        self.graph.render(config)
+    }
+
+    fn diagram_type(&self) -> &'static str {
+        "class"
+    }
+
+    fn render_to(&self, config: &Config, writer: &mut dyn std::io::Write) -> Result<(), String> {
+        self.graph.render_to(config, writer)
+    }
+}
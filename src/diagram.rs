@@ -1,10 +1,145 @@
+use crate::class::ClassDiagram;
+use crate::er::ErDiagram;
 use crate::graph::GraphDiagram;
+use crate::journey::JourneyDiagram;
+use crate::mindmap::MindmapDiagram;
 use crate::sequence::SequenceDiagram;
+use crate::state::StateDiagram;
+use std::borrow::Cow;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub trait Diagram {
     fn parse(&mut self, input: &str, config: &Config) -> Result<(), String>;
     fn render(&self, config: &Config) -> Result<String, String>;
     fn diagram_type(&self) -> &'static str;
+
+    /// Writes the same bytes `render` returns. Implementations override this
+    /// to emit output incrementally instead of building one `String`.
+    fn render_to(&self, config: &Config, writer: &mut dyn std::io::Write) -> Result<(), String> {
+        let output = self.render(config)?;
+        writer
+            .write_all(output.as_bytes())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Which side of a node box receives the extra column when a label cannot be
+/// centered exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelCenterBias {
+    Left,
+    Right,
+}
+
+/// Horizontal placement of a label inside its node box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NodeLabelAlign {
+    /// Inset from the left border by `box_border_padding`.
+    Left,
+    #[default]
+    Center,
+    /// Inset from the right border by `box_border_padding`.
+    Right,
+}
+
+/// Where along its edge a graph edge label is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeLabelPosition {
+    /// Just past the source node.
+    Start,
+    #[default]
+    Middle,
+    /// Just before the arrowhead.
+    End,
+}
+
+/// Corner glyphs used when rendering with `use_ascii`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AsciiCornerStyle {
+    /// Every corner and junction is `+`.
+    #[default]
+    Plus,
+    /// Top corners are `.` and bottom corners are `'`, junctions stay `+`.
+    Rounded,
+}
+
+/// Line glyphs for text output. `Ascii` is equivalent to `use_ascii`; the
+/// others restyle the light Unicode set both renderers draw with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CharSet {
+    #[default]
+    Light,
+    Heavy,
+    /// Light lines with arc corners.
+    Rounded,
+    Double,
+    Ascii,
+}
+
+impl CharSet {
+    /// Maps a light Unicode line glyph to its equivalent in this set; other
+    /// characters, and every glyph under `Light` or `Ascii`, pass through.
+    pub(crate) fn glyph(self, light: char) -> char {
+        const LIGHT: &str = "─│┌┐└┘├┤┬┴┼┈";
+        let target = match self {
+            CharSet::Light | CharSet::Ascii => return light,
+            CharSet::Heavy => "━┃┏┓┗┛┣┫┳┻╋┉",
+            CharSet::Rounded => "─│╭╮╰╯├┤┬┴┼┈",
+            CharSet::Double => "═║╔╗╚╝╠╣╦╩╬┈",
+        };
+        LIGHT
+            .chars()
+            .position(|c| c == light)
+            .and_then(|idx| target.chars().nth(idx))
+            .unwrap_or(light)
+    }
+}
+
+/// Arrowheads drawn at the end of graph edges, by the direction they point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArrowGlyphs {
+    pub up: char,
+    pub down: char,
+    pub left: char,
+    pub right: char,
+}
+
+impl ArrowGlyphs {
+    pub const UNICODE: ArrowGlyphs = ArrowGlyphs {
+        up: '▲',
+        down: '▼',
+        left: '◄',
+        right: '►',
+    };
+    pub const ASCII: ArrowGlyphs = ArrowGlyphs {
+        up: '^',
+        down: 'v',
+        left: '<',
+        right: '>',
+    };
+}
+
+/// Side of a node box that a self-loop (`A --> A`) leaves from. The loop
+/// returns through the neighbouring side: right and bottom pair with each
+/// other, as do left and top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfLoopSide {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+/// Render target. `Ascii` is equivalent to setting `use_ascii`; `Svg` and
+/// `Json` (a machine-readable layout description) are only available for
+/// graph diagrams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    Ascii,
+    #[default]
+    Unicode,
+    Svg,
+    Json,
 }
 
 #[derive(Debug, Clone)]
@@ -20,6 +155,73 @@ pub struct Config {
     pub sequence_participant_spacing: i32,
     pub sequence_message_spacing: i32,
     pub sequence_self_message_width: i32,
+    /// Always put a centered label's spare column on this side; by default it
+    /// goes right in even-width boxes and left in odd-width ones.
+    pub label_center_bias: Option<LabelCenterBias>,
+    /// Columns to fit the output into. Sequence diagrams tighten their
+    /// spacing first, then truncate participant and message labels.
+    pub max_width: Option<i32>,
+    pub ascii_corner_style: AsciiCornerStyle,
+    pub output_format: OutputFormat,
+    pub sequence_lenient: bool,
+    pub caption: Option<String>,
+    pub merge_bidirectional: bool,
+    pub edge_lane_padding: Option<i32>,
+    pub grid_overlay: bool,
+    pub self_loop_size: i32,
+    pub self_loop_side: Option<SelfLoopSide>,
+    pub node_label_align: NodeLabelAlign,
+    /// Horizontal inset inside node boxes; falls back to `box_border_padding`.
+    pub box_border_padding_x: Option<i32>,
+    /// Vertical inset inside node boxes; falls back to `box_border_padding`.
+    pub box_border_padding_y: Option<i32>,
+    /// Shading drawn inside nodes whose style sets `fill` (cli style only)
+    /// and behind sequence `rect` blocks. Nodes default to `░`, or `.` with
+    /// `use_ascii`; `rect` blocks default to a shade that darkens with nesting.
+    pub fill_glyph: Option<char>,
+    /// Blank lines emitted above text output.
+    pub margin_top: i32,
+    /// Spaces prepended to every line of text output.
+    pub margin_left: i32,
+    /// Minimum interior width of graph node boxes; shorter labels are centered.
+    pub node_min_width: i32,
+    /// Line glyphs for text output.
+    pub charset: CharSet,
+    /// With `max_width`, pick LR or TD before layout from the graph's
+    /// estimated depth and breadth, flipping the declared direction when only
+    /// the other one fits. Without it, only an LR graph that overflows once
+    /// laid out is retried top-down; a wide TD graph is left as is.
+    pub auto_direction: bool,
+    /// Draw numbered markers on graph edges and list their labels below the
+    /// diagram (text output only).
+    pub labels_as_legend: bool,
+    /// Graph arrowheads; defaults to `ArrowGlyphs::UNICODE`, or
+    /// `ArrowGlyphs::ASCII` with `use_ascii`.
+    pub arrow_glyphs: Option<ArrowGlyphs>,
+    /// Collapses each run of blank graph rows down to a single row.
+    pub compact: bool,
+    /// Blank rows (LR) or columns (TD) between disconnected parts of a
+    /// graph; defaults to the usual node padding.
+    pub component_spacing: Option<i32>,
+    /// Truncates node and participant labels wider than this many columns,
+    /// ending them with `…` (`...` in ASCII).
+    pub max_label_width: Option<usize>,
+    /// Draws sequence lifelines dotted (`┆`, or `:` in ASCII).
+    pub sequence_dotted_lifelines: bool,
+    /// Where graph edge labels sit along their edge.
+    pub edge_label_position: EdgeLabelPosition,
+    /// Cells the edge router may expand in one search before giving up on
+    /// that route; defaults to a bound derived from the grid size.
+    pub path_search_limit: Option<usize>,
+    /// Glyphs for sequence participant boxes, such as `CharSet::Rounded`;
+    /// defaults to `charset`. ASCII output keeps its plain boxes.
+    pub participant_box_style: Option<CharSet>,
+    /// Columns between tab stops when expanding tabs in the input, so tab-
+    /// and space-indented lines nest the same way.
+    pub tab_width: usize,
+    /// Lays out and routes graph edges sorted by source then target node
+    /// instead of in declaration order.
+    pub deterministic_edges: bool,
 }
 
 #[derive(Debug)]
@@ -42,6 +244,12 @@ impl std::fmt::Display for ConfigError {
 impl std::error::Error for ConfigError {}
 
 impl Config {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            config: Self::default_config(),
+        }
+    }
+
     pub fn default_config() -> Self {
         Self {
             use_ascii: false,
@@ -55,6 +263,37 @@ impl Config {
             sequence_participant_spacing: 5,
             sequence_message_spacing: 1,
             sequence_self_message_width: 4,
+            label_center_bias: None,
+            max_width: None,
+            ascii_corner_style: AsciiCornerStyle::Plus,
+            output_format: OutputFormat::Unicode,
+            sequence_lenient: false,
+            caption: None,
+            merge_bidirectional: false,
+            edge_lane_padding: None,
+            grid_overlay: false,
+            self_loop_size: 1,
+            self_loop_side: None,
+            node_label_align: NodeLabelAlign::Center,
+            box_border_padding_x: None,
+            box_border_padding_y: None,
+            fill_glyph: None,
+            margin_top: 0,
+            margin_left: 0,
+            node_min_width: 0,
+            charset: CharSet::Light,
+            auto_direction: false,
+            labels_as_legend: false,
+            arrow_glyphs: None,
+            compact: false,
+            component_spacing: None,
+            max_label_width: None,
+            sequence_dotted_lifelines: false,
+            edge_label_position: EdgeLabelPosition::Middle,
+            path_search_limit: None,
+            participant_box_style: None,
+            tab_width: 4,
+            deterministic_edges: false,
         }
     }
 
@@ -80,6 +319,37 @@ impl Config {
             sequence_participant_spacing: defaults.sequence_participant_spacing,
             sequence_message_spacing: defaults.sequence_message_spacing,
             sequence_self_message_width: defaults.sequence_self_message_width,
+            label_center_bias: defaults.label_center_bias,
+            max_width: defaults.max_width,
+            ascii_corner_style: defaults.ascii_corner_style,
+            output_format: defaults.output_format,
+            sequence_lenient: defaults.sequence_lenient,
+            caption: defaults.caption,
+            merge_bidirectional: defaults.merge_bidirectional,
+            edge_lane_padding: defaults.edge_lane_padding,
+            grid_overlay: defaults.grid_overlay,
+            self_loop_size: defaults.self_loop_size,
+            self_loop_side: defaults.self_loop_side,
+            node_label_align: defaults.node_label_align,
+            box_border_padding_x: defaults.box_border_padding_x,
+            box_border_padding_y: defaults.box_border_padding_y,
+            fill_glyph: defaults.fill_glyph,
+            margin_top: defaults.margin_top,
+            margin_left: defaults.margin_left,
+            node_min_width: defaults.node_min_width,
+            charset: defaults.charset,
+            auto_direction: defaults.auto_direction,
+            labels_as_legend: defaults.labels_as_legend,
+            arrow_glyphs: defaults.arrow_glyphs,
+            compact: defaults.compact,
+            component_spacing: defaults.component_spacing,
+            max_label_width: defaults.max_label_width,
+            sequence_dotted_lifelines: defaults.sequence_dotted_lifelines,
+            edge_label_position: defaults.edge_label_position,
+            path_search_limit: defaults.path_search_limit,
+            participant_box_style: defaults.participant_box_style,
+            tab_width: defaults.tab_width,
+            deterministic_edges: defaults.deterministic_edges,
         };
 
         config.validate()?;
@@ -93,6 +363,14 @@ impl Config {
         config
     }
 
+    /// Whether text output is limited to ASCII: `use_ascii`, the `Ascii`
+    /// output format or the `Ascii` charset.
+    pub(crate) fn ascii_output(&self) -> bool {
+        self.use_ascii
+            || self.output_format == OutputFormat::Ascii
+            || self.charset == CharSet::Ascii
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         if self.box_border_padding < 0 {
             return Err(ConfigError {
@@ -102,6 +380,35 @@ impl Config {
             }
             .to_string());
         }
+        for (field, value) in [
+            ("box_border_padding_x", self.box_border_padding_x),
+            ("box_border_padding_y", self.box_border_padding_y),
+        ] {
+            if let Some(value) = value
+                && value < 0
+            {
+                return Err(ConfigError {
+                    field,
+                    value: value.to_string(),
+                    message: "must be non-negative",
+                }
+                .to_string());
+            }
+        }
+        for (field, value) in [
+            ("margin_top", self.margin_top),
+            ("margin_left", self.margin_left),
+            ("node_min_width", self.node_min_width),
+        ] {
+            if value < 0 {
+                return Err(ConfigError {
+                    field,
+                    value: value.to_string(),
+                    message: "must be non-negative",
+                }
+                .to_string());
+            }
+        }
         if self.padding_between_x < 0 {
             return Err(ConfigError {
                 field: "padding_between_x",
@@ -158,16 +465,432 @@ impl Config {
             }
             .to_string());
         }
+        if let Some(max_width) = self.max_width
+            && max_width <= 0
+        {
+            return Err(ConfigError {
+                field: "max_width",
+                value: max_width.to_string(),
+                message: "must be positive",
+            }
+            .to_string());
+        }
+        if let Some(spacing) = self.component_spacing
+            && spacing < 0
+        {
+            return Err(ConfigError {
+                field: "component_spacing",
+                value: spacing.to_string(),
+                message: "must be non-negative",
+            }
+            .to_string());
+        }
+        if self.tab_width == 0 {
+            return Err(ConfigError {
+                field: "tab_width",
+                value: "0".to_string(),
+                message: "must be positive",
+            }
+            .to_string());
+        }
+        if self.max_label_width == Some(0) {
+            return Err(ConfigError {
+                field: "max_label_width",
+                value: "0".to_string(),
+                message: "must be positive",
+            }
+            .to_string());
+        }
+        if self.self_loop_size < 1 {
+            return Err(ConfigError {
+                field: "self_loop_size",
+                value: self.self_loop_size.to_string(),
+                message: "must be at least 1",
+            }
+            .to_string());
+        }
+        if let Some(edge_lane_padding) = self.edge_lane_padding
+            && edge_lane_padding < 0
+        {
+            return Err(ConfigError {
+                field: "edge_lane_padding",
+                value: edge_lane_padding.to_string(),
+                message: "must be non-negative",
+            }
+            .to_string());
+        }
 
         Ok(())
     }
 }
 
+/// Fluent alternative to mutating [`Config::default_config`]; `build` runs
+/// [`Config::validate`].
+#[derive(Debug, Clone)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn ascii(mut self, value: bool) -> Self {
+        self.config.use_ascii = value;
+        self
+    }
+
+    pub fn show_coords(mut self, value: bool) -> Self {
+        self.config.show_coords = value;
+        self
+    }
+
+    pub fn verbose(mut self, value: bool) -> Self {
+        self.config.verbose = value;
+        self
+    }
+
+    pub fn box_padding(mut self, value: i32) -> Self {
+        self.config.box_border_padding = value;
+        self
+    }
+
+    pub fn padding_x(mut self, value: i32) -> Self {
+        self.config.padding_between_x = value;
+        self
+    }
+
+    pub fn padding_y(mut self, value: i32) -> Self {
+        self.config.padding_between_y = value;
+        self
+    }
+
+    pub fn direction(mut self, value: impl Into<String>) -> Self {
+        self.config.graph_direction = value.into();
+        self
+    }
+
+    pub fn style_type(mut self, value: impl Into<String>) -> Self {
+        self.config.style_type = value.into();
+        self
+    }
+
+    pub fn sequence_participant_spacing(mut self, value: i32) -> Self {
+        self.config.sequence_participant_spacing = value;
+        self
+    }
+
+    pub fn sequence_message_spacing(mut self, value: i32) -> Self {
+        self.config.sequence_message_spacing = value;
+        self
+    }
+
+    pub fn sequence_self_message_width(mut self, value: i32) -> Self {
+        self.config.sequence_self_message_width = value;
+        self
+    }
+
+    pub fn label_center_bias(mut self, value: LabelCenterBias) -> Self {
+        self.config.label_center_bias = Some(value);
+        self
+    }
+
+    pub fn max_width(mut self, value: i32) -> Self {
+        self.config.max_width = Some(value);
+        self
+    }
+
+    pub fn ascii_corner_style(mut self, value: AsciiCornerStyle) -> Self {
+        self.config.ascii_corner_style = value;
+        self
+    }
+
+    pub fn output_format(mut self, value: OutputFormat) -> Self {
+        self.config.output_format = value;
+        self
+    }
+
+    pub fn sequence_lenient(mut self, value: bool) -> Self {
+        self.config.sequence_lenient = value;
+        self
+    }
+
+    pub fn caption(mut self, value: impl Into<String>) -> Self {
+        self.config.caption = Some(value.into());
+        self
+    }
+
+    pub fn merge_bidirectional(mut self, value: bool) -> Self {
+        self.config.merge_bidirectional = value;
+        self
+    }
+
+    pub fn edge_lane_padding(mut self, value: i32) -> Self {
+        self.config.edge_lane_padding = Some(value);
+        self
+    }
+
+    pub fn grid_overlay(mut self, value: bool) -> Self {
+        self.config.grid_overlay = value;
+        self
+    }
+
+    pub fn self_loop_size(mut self, value: i32) -> Self {
+        self.config.self_loop_size = value;
+        self
+    }
+
+    pub fn self_loop_side(mut self, value: SelfLoopSide) -> Self {
+        self.config.self_loop_side = Some(value);
+        self
+    }
+
+    pub fn node_label_align(mut self, value: NodeLabelAlign) -> Self {
+        self.config.node_label_align = value;
+        self
+    }
+
+    pub fn box_border_padding_x(mut self, value: i32) -> Self {
+        self.config.box_border_padding_x = Some(value);
+        self
+    }
+
+    pub fn box_border_padding_y(mut self, value: i32) -> Self {
+        self.config.box_border_padding_y = Some(value);
+        self
+    }
+
+    pub fn fill_glyph(mut self, value: char) -> Self {
+        self.config.fill_glyph = Some(value);
+        self
+    }
+
+    pub fn margin_top(mut self, value: i32) -> Self {
+        self.config.margin_top = value;
+        self
+    }
+
+    pub fn margin_left(mut self, value: i32) -> Self {
+        self.config.margin_left = value;
+        self
+    }
+
+    pub fn node_min_width(mut self, value: i32) -> Self {
+        self.config.node_min_width = value;
+        self
+    }
+
+    pub fn charset(mut self, value: CharSet) -> Self {
+        self.config.charset = value;
+        self
+    }
+
+    pub fn auto_direction(mut self, value: bool) -> Self {
+        self.config.auto_direction = value;
+        self
+    }
+
+    pub fn labels_as_legend(mut self, value: bool) -> Self {
+        self.config.labels_as_legend = value;
+        self
+    }
+
+    pub fn arrow_glyphs(mut self, value: ArrowGlyphs) -> Self {
+        self.config.arrow_glyphs = Some(value);
+        self
+    }
+
+    pub fn compact(mut self, value: bool) -> Self {
+        self.config.compact = value;
+        self
+    }
+
+    pub fn component_spacing(mut self, value: i32) -> Self {
+        self.config.component_spacing = Some(value);
+        self
+    }
+
+    pub fn max_label_width(mut self, value: usize) -> Self {
+        self.config.max_label_width = Some(value);
+        self
+    }
+
+    pub fn sequence_dotted_lifelines(mut self, value: bool) -> Self {
+        self.config.sequence_dotted_lifelines = value;
+        self
+    }
+
+    pub fn edge_label_position(mut self, value: EdgeLabelPosition) -> Self {
+        self.config.edge_label_position = value;
+        self
+    }
+
+    pub fn path_search_limit(mut self, value: usize) -> Self {
+        self.config.path_search_limit = Some(value);
+        self
+    }
+
+    pub fn participant_box_style(mut self, value: CharSet) -> Self {
+        self.config.participant_box_style = Some(value);
+        self
+    }
+
+    pub fn tab_width(mut self, value: usize) -> Self {
+        self.config.tab_width = value;
+        self
+    }
+
+    pub fn deterministic_edges(mut self, value: bool) -> Self {
+        self.config.deterministic_edges = value;
+        self
+    }
+
+    pub fn build(self) -> Result<Config, String> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Reports which renderer would handle `input` without parsing it:
+/// `"sequence"`, `"state"`, `"er"`, `"class"`, `"mindmap"`, `"journey"`,
+/// `"graph"`, or `None` when the header is not recognized.
+pub fn detect_diagram_type(input: &str) -> Option<&'static str> {
+    if crate::sequence::is_sequence_diagram(input) {
+        return Some("sequence");
+    }
+    if crate::state::is_state_diagram(input) {
+        return Some("state");
+    }
+    if crate::er::is_er_diagram(input) {
+        return Some("er");
+    }
+    if crate::class::is_class_diagram(input) {
+        return Some("class");
+    }
+    if crate::mindmap::is_mindmap(input) {
+        return Some("mindmap");
+    }
+    if crate::journey::is_journey(input) {
+        return Some("journey");
+    }
+    let header = input
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("%%"))?;
+    if header.starts_with("graph ") || header.starts_with("flowchart ") {
+        Some("graph")
+    } else {
+        None
+    }
+}
+
+/// Splits a document holding several diagrams into one source per diagram.
+/// A new block starts at a diagram header (`graph`, `flowchart`,
+/// `sequenceDiagram`, `stateDiagram`, `erDiagram`, `classDiagram`, `mindmap`
+/// or `journey`) once the current block already has one, or after a `---`
+/// line that follows a header. A `---` before any header is kept as
+/// front-matter.
+pub fn split_diagrams(input: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut has_header = false;
+    for line in input.lines() {
+        let trimmed = line.trim();
+        let is_header = trimmed.starts_with("graph ")
+            || trimmed.starts_with("flowchart ")
+            || trimmed.starts_with("sequenceDiagram")
+            || crate::state::is_state_diagram(trimmed)
+            || crate::er::is_er_diagram(trimmed)
+            || crate::class::is_class_diagram(trimmed)
+            || crate::mindmap::is_mindmap(trimmed)
+            || crate::journey::is_journey(trimmed);
+        if has_header && (is_header || trimmed == "---") {
+            blocks.push(current.join("\n").trim_end().to_string());
+            current.clear();
+            has_header = false;
+            if trimmed == "---" {
+                continue;
+            }
+        }
+        has_header |= is_header;
+        current.push(line);
+    }
+    blocks.push(current.join("\n").trim_end().to_string());
+    blocks.retain(|block| !block.trim().is_empty());
+    blocks
+}
+
+/// The contents of every fenced ```` ```mermaid ```` (or `~~~mermaid`) block
+/// in a Markdown document, in order. An unclosed fence runs to the end.
+pub fn extract_mermaid_blocks(md: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    // The open fence's marker, its length and indent, and the lines so far.
+    let mut open: Option<(char, usize, usize, Vec<&str>)> = None;
+    for line in md.lines() {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let trimmed = line.trim();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let fence_len = marker.map_or(0, |c| trimmed.chars().take_while(|ch| *ch == c).count());
+        match &mut open {
+            Some((fence, len, _, _))
+                if indent < 4
+                    && marker == Some(*fence)
+                    && fence_len >= *len
+                    && trimmed.len() == fence_len =>
+            {
+                let (_, _, _, lines) = open.take().unwrap();
+                blocks.push(lines.join("\n"));
+            }
+            Some((_, _, fence_indent, lines)) => {
+                let strip = (line.len() - line.trim_start_matches(' ').len()).min(*fence_indent);
+                lines.push(&line[strip..]);
+            }
+            None if indent < 4 && fence_len >= 3 => {
+                let info = trimmed[fence_len..].split_whitespace().next();
+                if info == Some("mermaid") {
+                    open = Some((marker.unwrap(), fence_len, indent, Vec::new()));
+                }
+            }
+            None => {}
+        }
+    }
+    if let Some((_, _, _, lines)) = open {
+        blocks.push(lines.join("\n"));
+    }
+    blocks
+}
+
+/// Mermaid diagram headers this crate recognizes but cannot render yet.
+const UNSUPPORTED_DIAGRAM_KEYWORDS: &[&str] = &[
+    "gitGraph",
+    "pie",
+    "gantt",
+    "timeline",
+    "quadrantChart",
+    "requirementDiagram",
+    "C4Context",
+    "sankey-beta",
+    "xychart-beta",
+    "block-beta",
+];
+
 pub fn diagram_factory(input: &str) -> Result<Box<dyn Diagram>, String> {
     let input = input.trim();
     if crate::sequence::is_sequence_diagram(input) {
         return Ok(Box::new(SequenceDiagram::default()));
     }
+    if crate::state::is_state_diagram(input) {
+        return Ok(Box::new(StateDiagram::default()));
+    }
+    if crate::er::is_er_diagram(input) {
+        return Ok(Box::new(ErDiagram::default()));
+    }
+    if crate::class::is_class_diagram(input) {
+        return Ok(Box::new(ClassDiagram::default()));
+    }
+    if crate::mindmap::is_mindmap(input) {
+        return Ok(Box::new(MindmapDiagram::default()));
+    }
+    if crate::journey::is_journey(input) {
+        return Ok(Box::new(JourneyDiagram::default()));
+    }
 
     for line in input.lines() {
         let trimmed = line.trim();
@@ -177,6 +900,10 @@ pub fn diagram_factory(input: &str) -> Result<Box<dyn Diagram>, String> {
         if trimmed.starts_with("graph ") || trimmed.starts_with("flowchart ") {
             return Ok(Box::new(GraphDiagram::default()));
         }
+        let keyword = trimmed.split_whitespace().next().unwrap_or_default();
+        if UNSUPPORTED_DIAGRAM_KEYWORDS.contains(&keyword) {
+            return Err(format!("diagram type '{}' is not yet supported", keyword));
+        }
         if !trimmed.starts_with("%%") {
             return Ok(Box::new(GraphDiagram::default()));
         }
@@ -185,11 +912,123 @@ pub fn diagram_factory(input: &str) -> Result<Box<dyn Diagram>, String> {
     Ok(Box::new(GraphDiagram::default()))
 }
 
+/// Appends `caption` as a final line, centered over the widest line of `output`.
+pub fn append_caption(output: &str, caption: &str) -> String {
+    let body = output.trim_end_matches('\n');
+    let width = body
+        .lines()
+        .map(|line| UnicodeWidthStr::width(line.trim_end()))
+        .max()
+        .unwrap_or(0);
+    let left = width.saturating_sub(UnicodeWidthStr::width(caption)) / 2;
+    let mut result = format!("{}\n{}{}", body, " ".repeat(left), caption);
+    if output.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// Shifts rendered `lines` by the configured top and left margins.
+pub(crate) fn apply_margins(lines: &mut Vec<String>, config: &Config) {
+    if config.margin_left > 0 {
+        let indent = " ".repeat(config.margin_left as usize);
+        for line in lines.iter_mut() {
+            line.insert_str(0, &indent);
+        }
+    }
+    if config.margin_top > 0 {
+        lines.splice(0..0, vec![String::new(); config.margin_top as usize]);
+    }
+}
+
+/// Pads `text` on the left so it sits centered over `width` columns.
+pub(crate) fn center_line(text: &str, width: usize) -> String {
+    let left = width.saturating_sub(UnicodeWidthStr::width(text)) / 2;
+    format!("{}{}", " ".repeat(left), text)
+}
+
+/// Shortens each line of `text` to at most `max_width` columns, marking a cut
+/// with a trailing ellipsis.
+pub(crate) fn truncate_label(text: &str, max_width: usize, ascii: bool) -> String {
+    let ellipsis = if ascii { "..." } else { "…" };
+    text.split('\n')
+        .map(|line| {
+            if UnicodeWidthStr::width(line) <= max_width {
+                return line.to_string();
+            }
+            if max_width < UnicodeWidthStr::width(ellipsis) {
+                return ellipsis[..max_width].to_string();
+            }
+            let budget = max_width - UnicodeWidthStr::width(ellipsis);
+            let mut kept = String::new();
+            let mut width = 0;
+            for c in line.chars() {
+                width += UnicodeWidthChar::width(c).unwrap_or(0);
+                if width > budget {
+                    break;
+                }
+                kept.push(c);
+            }
+            kept.push_str(ellipsis);
+            kept
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Replaces each tab with spaces up to the next multiple of `tab_width`
+/// columns on its line.
+pub fn expand_tabs(input: &str, tab_width: usize) -> Cow<'_, str> {
+    if !input.contains('\t') || tab_width == 0 {
+        return Cow::Borrowed(input);
+    }
+    let mut expanded = String::with_capacity(input.len());
+    let mut column = 0;
+    for c in input.chars() {
+        match c {
+            '\t' => {
+                let spaces = tab_width - column % tab_width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                expanded.push(c);
+                column = 0;
+            }
+            _ => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    Cow::Owned(expanded)
+}
+
 pub fn split_lines(input: &str) -> Vec<String> {
     let re = regex::Regex::new(r"\n|\\n").unwrap();
     re.split(input).map(|s| s.to_string()).collect()
 }
 
+/// Byte offset of a trailing `%%` comment in `line`. A `%%` between double
+/// quotes or between a pair of `|` (an edge label) is part of the content.
+pub(crate) fn comment_start(line: &str) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            delimiter @ (b'"' | b'|') => {
+                idx += match bytes[idx + 1..].iter().position(|&b| b == delimiter) {
+                    Some(offset) => offset + 2,
+                    None => 1,
+                };
+            }
+            b'%' if bytes.get(idx + 1) == Some(&b'%') => return Some(idx),
+            _ => idx += 1,
+        }
+    }
+    None
+}
+
 pub fn remove_comments(lines: &[String]) -> Vec<String> {
     let mut cleaned = Vec::new();
     for line in lines {
@@ -198,7 +1037,7 @@ pub fn remove_comments(lines: &[String]) -> Vec<String> {
             continue;
         }
         let mut current = line.clone();
-        if let Some(idx) = current.find("%%") {
+        if let Some(idx) = comment_start(&current) {
             current = current[..idx].trim().to_string();
         }
         if !current.trim().is_empty() {
@@ -0,0 +1,89 @@
+use crate::diagram::{Config, Diagram, remove_comments, split_lines};
+use crate::graph::{Cardinality, GraphDiagram, GraphProperties, direction_statement};
+use regex::Regex;
+use std::sync::LazyLock;
+
+const ER_DIAGRAM_KEYWORD: &str = "erDiagram";
+
+static RELATIONSHIP_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^([\w-]+)\s*(\|\||\|o|\}\||\}o)(--|\.\.)(\|\||o\||\|\{|o\{)\s*([\w-]+)\s*:\s*(?:"([^"]*)"|(.+))$"#,
+    )
+    .unwrap()
+});
+static ENTITY_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[\w-]+$").unwrap());
+/// An `erDiagram`, laid out and drawn by the graph renderer: entities become
+/// boxes and relationships labelled edges with crow's-foot ends.
+#[derive(Debug, Clone, Default)]
+pub struct ErDiagram {
+    graph: GraphDiagram,
+}
+
+pub fn is_er_diagram(input: &str) -> bool {
+    for line in input.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("%%") {
+            continue;
+        }
+        return trimmed == ER_DIAGRAM_KEYWORD;
+    }
+    false
+}
+
+fn parse_properties(input: &str, config: &Config) -> Result<GraphProperties, String> {
+    let lines = remove_comments(&split_lines(input.trim()));
+    let Some(header) = lines.first() else {
+        return Err("no content found".to_string());
+    };
+    if !is_er_diagram(header) {
+        return Err(format!("expected \"{}\" keyword", ER_DIAGRAM_KEYWORD));
+    }
+
+    let mut properties = GraphProperties::new("cli", config);
+    properties.graph_direction = "TD".to_string();
+    for (idx, line) in lines.iter().enumerate().skip(1) {
+        let trimmed = line.trim();
+        if let Some(direction) = direction_statement(trimmed) {
+            properties.graph_direction = direction;
+        } else if let Some(caps) = RELATIONSHIP_RE.captures(trimmed) {
+            let label = caps
+                .get(6)
+                .or_else(|| caps.get(7))
+                .map_or("", |m| m.as_str().trim());
+            let ends = [parse_cardinality(&caps[2]), parse_cardinality(&caps[4])];
+            properties.add_edge(&caps[1], &caps[5], label).cardinality = Some(ends);
+        } else if ENTITY_RE.is_match(trimmed) {
+            properties.add_node(trimmed, trimmed);
+        } else {
+            return Err(format!("line {}: invalid syntax: \"{}\"", idx + 1, trimmed));
+        }
+    }
+    Ok(properties)
+}
+
+/// Reads one side of a relationship such as `||`, `o{` or `}|`.
+fn parse_cardinality(token: &str) -> Cardinality {
+    Cardinality {
+        optional: token.contains('o'),
+        many: token.contains('{') || token.contains('}'),
+    }
+}
+
+impl Diagram for ErDiagram {
+    fn parse(&mut self, input: &str, config: &Config) -> Result<(), String> {
+        self.graph = GraphDiagram::from_properties(parse_properties(input, config)?);
+        Ok(())
+    }
+
+    fn render(&self, config: &Config) -> Result<String, String> {
+        self.graph.render(config)
+    }
+
+    fn diagram_type(&self) -> &'static str {
+        "er"
+    }
+
+    fn render_to(&self, config: &Config, writer: &mut dyn std::io::Write) -> Result<(), String> {
+        self.graph.render_to(config, writer)
+    }
+}
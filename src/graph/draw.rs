@@ -1,27 +1,33 @@
-use crate::graph::layout::mk_graph;
+use crate::diagram::{
+    ArrowGlyphs, AsciiCornerStyle, CharSet, EdgeLabelPosition, LabelCenterBias, NodeLabelAlign,
+};
+use crate::graph::junctions::{is_junction, junction_dirs, junction_from_dirs, merge};
+use crate::graph::layout::{layout_graph, legend_marker};
 use crate::graph::types::{
-    DOWN, Direction, Drawing, DrawingCoord, Edge, GenericCoord, Graph, GraphProperties, GridCoord,
-    LEFT, LOWER_LEFT, LOWER_RIGHT, Node, RIGHT, Subgraph, UP, UPPER_LEFT, UPPER_RIGHT, ceil_div,
-    determine_direction, max,
+    Cardinality, DOWN, Direction, Drawing, DrawingCoord, Edge, EdgeEnd, GenericCoord, Graph,
+    GraphProperties, GridCoord, LEFT, LOWER_LEFT, LOWER_RIGHT, Node, NodeShape, RIGHT, Subgraph,
+    UP, UPPER_LEFT, UPPER_RIGHT, determine_direction, label_height, label_width, max, min,
 };
-use std::collections::HashMap;
-
-pub(crate) fn draw_map(properties: &GraphProperties, show_coords: bool) -> Result<String, String> {
-    let mut graph = mk_graph(properties);
-    graph.set_style_classes(properties);
-    graph.padding_x = properties.padding_x;
-    graph.padding_y = properties.padding_y;
-    graph.box_border_padding = properties.box_border_padding;
-    graph.use_ascii = properties.use_ascii;
-    graph.graph_direction = properties.graph_direction.clone();
-    graph.set_subgraphs(&properties.subgraphs);
-    graph.create_mapping();
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Renders the graph to a drawing, also handing back the laid-out graph so
+/// callers can inspect final node positions.
+pub(crate) fn draw_map(properties: &GraphProperties, show_coords: bool) -> (Drawing, Graph) {
+    let mut graph = layout_graph(properties);
     let mut drawing = graph.draw();
+    if !graph.legend.is_empty() {
+        draw_legend(&mut drawing, &graph);
+    }
+    if properties.grid_overlay {
+        draw_grid_overlay(&mut drawing, &graph);
+    }
     if show_coords {
         drawing = debug_drawing_wrapper(&drawing);
         drawing = debug_coord_wrapper(&drawing, &graph);
     }
-    Ok(drawing_to_string(&drawing))
+    (drawing, graph)
 }
 
 impl Graph {
@@ -40,7 +46,14 @@ impl Graph {
         let mut label_drawings = Vec::new();
 
         for edge_idx in 0..self.edges.len() {
-            let (line, box_start, arrow_head, corners, label) = self.draw_edge(edge_idx);
+            let (mut line, mut box_start, mut arrow_head, mut corners, label) =
+                self.draw_edge(edge_idx);
+            let style = &self.edges[edge_idx].style;
+            if !style.is_empty() {
+                for drawing in [&mut line, &mut box_start, &mut arrow_head, &mut corners] {
+                    apply_link_style(drawing, style, self.use_ascii, &self.style_type);
+                }
+            }
             line_drawings.push(line);
             corner_drawings.push(corners);
             arrow_head_drawings.push(arrow_head);
@@ -48,6 +61,11 @@ impl Graph {
             label_drawings.push(label);
         }
 
+        let mut blocked = self.label_blocked_cells(&[&arrow_head_drawings, &box_start_drawings]);
+        for (edge_idx, label) in label_drawings.iter_mut().enumerate() {
+            *label = self.place_label(edge_idx, label, &mut blocked);
+        }
+
         self.drawing =
             self.merge_drawings(&self.drawing, DrawingCoord { x: 0, y: 0 }, &line_drawings);
         self.drawing =
@@ -62,23 +80,64 @@ impl Graph {
             DrawingCoord { x: 0, y: 0 },
             &box_start_drawings,
         );
+        // Junctions are merged in the light set, so restyle only once every
+        // line is in place, before any edge label text goes on top.
+        restyle_lines(&mut self.drawing, self.charset);
         self.drawing =
             self.merge_drawings(&self.drawing, DrawingCoord { x: 0, y: 0 }, &label_drawings);
 
         self.draw_subgraph_labels();
+        if self.compact {
+            self.compact_blank_rows();
+        }
 
         self.drawing.clone()
     }
 
-    pub(crate) fn draw_node(&mut self, idx: usize) {
-        if let Some(coord) = self.nodes[idx].drawing_coord {
-            if let Some(drawing) = &self.nodes[idx].drawing {
-                self.drawing = self.merge_drawings(&self.drawing, coord, &[drawing.clone()]);
-                self.nodes[idx].drawn = true;
+    /// Drops every blank row that directly follows another blank row, moving
+    /// the nodes below up with it. Rows crossed by an edge or a box border
+    /// are never blank, so nothing drawn is lost.
+    pub(crate) fn compact_blank_rows(&mut self) {
+        let (max_x, max_y) = get_drawing_size(&self.drawing);
+        let blank =
+            |y: i32| (0..=max_x).all(|x| self.drawing[x as usize][y as usize].trim().is_empty());
+        let mut removed = vec![0; (max_y + 1) as usize];
+        let mut keep = Vec::new();
+        let mut dropped = 0;
+        for y in 0..=max_y {
+            if y > 0 && blank(y) && blank(y - 1) {
+                dropped += 1;
+            } else {
+                keep.push(y as usize);
+            }
+            removed[y as usize] = dropped;
+        }
+        if dropped == 0 {
+            return;
+        }
+
+        for column in &mut self.drawing {
+            *column = keep.iter().map(|&y| column[y].clone()).collect();
+        }
+        for node in &mut self.nodes {
+            if let Some(coord) = &mut node.drawing_coord
+                && coord.y >= 0
+                && (coord.y as usize) < removed.len()
+            {
+                coord.y -= removed[coord.y as usize];
             }
         }
     }
 
+    pub(crate) fn draw_node(&mut self, idx: usize) {
+        if let Some(coord) = self.nodes[idx].drawing_coord
+            && let Some(drawing) = &self.nodes[idx].drawing
+        {
+            self.drawing = self.merge_drawings(&self.drawing, coord, std::slice::from_ref(drawing));
+            self.nodes[idx].drawn = true;
+        }
+    }
+
     pub(crate) fn draw_edge(
         &self,
         edge_idx: usize,
@@ -166,8 +225,61 @@ impl Graph {
         }
         let label = self.draw_arrow_label(edge);
         let (path, lines_drawn, _line_dirs) = self.draw_path(&edge.path);
-        let box_start = self.draw_box_start(&edge.path, &lines_drawn[0]);
-        let arrow_head = self.draw_arrow_head(lines_drawn.last().unwrap(), edge.end_dir.opposite());
+        if let Some([parent_end, child_end]) = edge.cardinality {
+            let box_start = self.draw_box_start(&edge.path, &lines_drawn[0], edge.from);
+            let first_dir = segment_direction(&edge.path[..2]).unwrap_or(edge.start_dir);
+            let first_line = &lines_drawn[0];
+            let from = if first_dir == UP || first_dir == LEFT {
+                first_line[first_line.len() - 1]
+            } else {
+                first_line[0]
+            };
+            let last_dir = segment_direction(&edge.path[edge.path.len() - 2..])
+                .unwrap_or(edge.end_dir.opposite());
+            let last_line = lines_drawn.last().unwrap();
+            let to = if last_dir == UP || last_dir == LEFT {
+                last_line[0]
+            } else {
+                last_line[last_line.len() - 1]
+            };
+            let mut ends = box_start;
+            self.draw_cardinality(&mut ends, from, first_dir.opposite(), parent_end);
+            self.draw_cardinality(&mut ends, to, last_dir, child_end);
+            let corners = self.draw_corners(&edge.path);
+            return (path, ends, mk_drawing(0, 0), corners, label);
+        }
+        let tail = if edge.bidirectional {
+            Some(edge.end)
+        } else {
+            edge.tail
+        };
+        let box_start = if let Some(tail) = tail {
+            let first: Vec<GridCoord> = edge.path.iter().take(2).rev().copied().collect();
+            let dir = segment_direction(&first).unwrap_or(edge.start_dir.opposite());
+            self.draw_arrow_head(&lines_drawn[0], dir, tail)
+        } else {
+            self.draw_box_start(&edge.path, &lines_drawn[0], edge.from)
+        };
+        // The head points along the final segment actually drawn; the
+        // declared end direction is only a fallback for single-cell paths.
+        let dir = segment_direction(&edge.path[edge.path.len().saturating_sub(2)..])
+            .unwrap_or(edge.end_dir.opposite());
+        let last_line = lines_drawn.last().unwrap();
+        let arrow_head = if self.nodes[edge.to].shape == NodeShape::Box {
+            self.draw_arrow_head(last_line, dir, edge.end)
+        } else {
+            // Markers have no border, so the line runs on up to the glyph
+            // and the head sits right against it.
+            let head = if dir == UP || dir == LEFT {
+                last_line[0]
+            } else {
+                last_line[last_line.len() - 1]
+            };
+            let mut drawing = copy_canvas(&self.drawing);
+            let head = self.bridge_to_marker(&mut drawing, edge.to, head, dir);
+            let head_drawing = self.draw_arrow_head(&[head], dir, edge.end);
+            merge_drawings(&drawing, DrawingCoord { x: 0, y: 0 }, &[head_drawing], true)
+        };
         let corners = self.draw_corners(&edge.path);
         (path, box_start, arrow_head, corners, label)
     }
@@ -368,9 +480,12 @@ impl Graph {
         &self,
         path: &[GridCoord],
         first_line: &[DrawingCoord],
+        from_node: usize,
     ) -> Drawing {
         let mut drawing = copy_canvas(&self.drawing);
-        if self.use_ascii || first_line.is_empty() {
+        if (self.use_ascii && self.nodes[from_node].shape == NodeShape::Box)
+            || first_line.is_empty()
+        {
             return drawing;
         }
         let dir = determine_direction(
@@ -388,6 +503,11 @@ impl Graph {
         } else {
             first_line[0]
         };
+        if self.nodes[from_node].shape != NodeShape::Box {
+            // A marker has no border to tee off; run the line back to it.
+            self.bridge_to_marker(&mut drawing, from_node, from, dir.opposite());
+            return drawing;
+        }
         match dir {
             d if d == UP => set_cell(&mut drawing, from.x, from.y + 1, "┴"),
             d if d == DOWN => set_cell(&mut drawing, from.x, from.y - 1, "┬"),
@@ -398,9 +518,101 @@ impl Graph {
         drawing
     }
 
-    pub(crate) fn draw_arrow_head(&self, line: &[DrawingCoord], arrow_dir: Direction) -> Drawing {
+    /// Draws an ER crow's-foot terminator ending at `cell`, next to the entity
+    /// that lies in direction `toward`: the maximum (a bar for one, a fork for
+    /// many) against the box, then the minimum (a bar for one, `○` for zero).
+    fn draw_cardinality(
+        &self,
+        drawing: &mut Drawing,
+        cell: DrawingCoord,
+        toward: Direction,
+        cardinality: Cardinality,
+    ) {
+        let Some((dx, dy)) = step(toward) else {
+            return;
+        };
+        // Bars that cross the line; box-drawing junctions would merge away.
+        let one = match (self.use_ascii, dy) {
+            (true, _) => "+",
+            (false, 0) => "╫",
+            (false, _) => "╪",
+        };
+        let max = if cardinality.many {
+            match (toward, self.use_ascii) {
+                (d, _) if d == RIGHT => "<",
+                (d, _) if d == LEFT => ">",
+                (d, false) if d == DOWN => "∧",
+                (d, true) if d == DOWN => "^",
+                (_, false) => "∨",
+                (_, true) => "v",
+            }
+        } else {
+            one
+        };
+        let min = match (cardinality.optional, self.use_ascii) {
+            (true, false) => "○",
+            (true, true) => "o",
+            (false, _) => one,
+        };
+        set_cell(drawing, cell.x, cell.y, max);
+        set_cell(drawing, cell.x - dx, cell.y - dy, min);
+    }
+
+    /// Drawing position of a marker node's glyph: the middle of its middle
+    /// grid cell.
+    pub(crate) fn marker_center(&self, node_idx: usize) -> DrawingCoord {
+        let grid = self.nodes[node_idx].grid_coord.unwrap();
+        self.grid_to_drawing_coord(
+            GridCoord {
+                x: grid.x + 1,
+                y: grid.y + 1,
+            },
+            None,
+        )
+    }
+
+    /// Extends a line from `from` towards a marker glyph travelling in `dir`,
+    /// returning the last cell before the glyph.
+    fn bridge_to_marker(
+        &self,
+        drawing: &mut Drawing,
+        node_idx: usize,
+        from: DrawingCoord,
+        dir: Direction,
+    ) -> DrawingCoord {
+        let glyph = self.marker_center(node_idx);
+        let Some((dx, dy)) = step(dir) else {
+            return from;
+        };
+        let line = match (dx, self.use_ascii) {
+            (0, false) => "│",
+            (0, true) => "|",
+            (_, false) => "─",
+            (_, true) => "-",
+        };
+        let mut cell = from;
+        loop {
+            set_cell(drawing, cell.x, cell.y, line);
+            let next = DrawingCoord {
+                x: cell.x + dx,
+                y: cell.y + dy,
+            };
+            // Stop one short of the glyph along the direction of travel.
+            if (next.x - glyph.x) * dx + (next.y - glyph.y) * dy >= 0 {
+                return cell;
+            }
+            cell = next;
+        }
+    }
+
+    pub(crate) fn draw_arrow_head(
+        &self,
+        line: &[DrawingCoord],
+        arrow_dir: Direction,
+        end: EdgeEnd,
+    ) -> Drawing {
         let mut drawing = copy_canvas(&self.drawing);
-        if line.is_empty() {
+        if line.is_empty() || end == EdgeEnd::Open {
             return drawing;
         }
         let dir = arrow_dir;
@@ -410,42 +622,44 @@ impl Graph {
             line[line.len() - 1]
         };
 
-        let ch = if !self.use_ascii {
-            match dir {
-                d if d == UP => "▲",
-                d if d == DOWN => "▼",
-                d if d == LEFT => "◄",
-                d if d == RIGHT => "►",
+        let glyphs = self.arrow_glyphs.unwrap_or(if self.use_ascii {
+            ArrowGlyphs::ASCII
+        } else {
+            ArrowGlyphs::UNICODE
+        });
+        let arrow = match dir {
+            d if d == UP => glyphs.up,
+            d if d == DOWN => glyphs.down,
+            d if d == LEFT => glyphs.left,
+            _ => glyphs.right,
+        }
+        .to_string();
+        let ch = match (end, self.use_ascii) {
+            (EdgeEnd::Cross, true) => "x",
+            (EdgeEnd::Cross, false) => "✗",
+            (EdgeEnd::Circle, true) | (EdgeEnd::Diamond, true) => "o",
+            (EdgeEnd::Circle, false) => "○",
+            (EdgeEnd::Diamond, false) => "◇",
+            (EdgeEnd::FilledDiamond, true) => "*",
+            (EdgeEnd::FilledDiamond, false) => "◆",
+            (EdgeEnd::Triangle, false) => match dir {
+                d if d == UP => "△",
+                d if d == DOWN => "▽",
+                d if d == LEFT => "◁",
+                _ => "▷",
+            },
+            (_, false) => match dir {
+                d if d == UP || d == DOWN || d == LEFT || d == RIGHT => &arrow,
                 d if d == UPPER_RIGHT => "◥",
                 d if d == UPPER_LEFT => "◤",
                 d if d == LOWER_RIGHT => "◢",
                 d if d == LOWER_LEFT => "◣",
-                _ => match arrow_dir {
-                    d if d == UP => "▲",
-                    d if d == DOWN => "▼",
-                    d if d == LEFT => "◄",
-                    d if d == RIGHT => "►",
-                    d if d == UPPER_RIGHT => "◥",
-                    d if d == UPPER_LEFT => "◤",
-                    d if d == LOWER_RIGHT => "◢",
-                    d if d == LOWER_LEFT => "◣",
-                    _ => "●",
-                },
-            }
-        } else {
-            match dir {
-                d if d == UP => "^",
-                d if d == DOWN => "v",
-                d if d == LEFT => "<",
-                d if d == RIGHT => ">",
-                _ => match arrow_dir {
-                    d if d == UP => "^",
-                    d if d == DOWN => "v",
-                    d if d == LEFT => "<",
-                    d if d == RIGHT => ">",
-                    _ => "*",
-                },
-            }
+                _ => "●",
+            },
+            (_, true) => match dir {
+                d if d == UP || d == DOWN || d == LEFT || d == RIGHT => &arrow,
+                _ => "*",
+            },
         };
 
         set_cell(&mut drawing, head.x, head.y, ch);
@@ -477,27 +691,30 @@ impl Graph {
                     y: path[idx + 1].y,
                 },
             );
-            let corner = if !self.use_ascii {
-                if (prev_dir == RIGHT && next_dir == DOWN) || (prev_dir == UP && next_dir == LEFT) {
-                    "┐"
-                } else if (prev_dir == RIGHT && next_dir == UP)
-                    || (prev_dir == DOWN && next_dir == LEFT)
-                {
-                    "┘"
-                } else if (prev_dir == LEFT && next_dir == DOWN)
-                    || (prev_dir == UP && next_dir == RIGHT)
-                {
-                    "┌"
-                } else if (prev_dir == LEFT && next_dir == UP)
-                    || (prev_dir == DOWN && next_dir == RIGHT)
-                {
-                    "└"
-                } else {
-                    "+"
-                }
+            let corner = if (prev_dir == RIGHT && next_dir == DOWN)
+                || (prev_dir == UP && next_dir == LEFT)
+            {
+                "┐"
+            } else if (prev_dir == RIGHT && next_dir == UP)
+                || (prev_dir == DOWN && next_dir == LEFT)
+            {
+                "┘"
+            } else if (prev_dir == LEFT && next_dir == DOWN)
+                || (prev_dir == UP && next_dir == RIGHT)
+            {
+                "┌"
+            } else if (prev_dir == LEFT && next_dir == UP)
+                || (prev_dir == DOWN && next_dir == RIGHT)
+            {
+                "└"
             } else {
                 "+"
             };
+            let corner = if self.use_ascii {
+                ascii_corner(corner, self.ascii_corner_style)
+            } else {
+                corner
+            };
             set_cell(&mut drawing, drawing_coord.x, drawing_coord.y, corner);
         }
         drawing
@@ -509,7 +726,109 @@ impl Graph {
             return drawing;
         }
         let line = self.line_to_drawing(&edge.label_line);
-        draw_text_on_line(&mut drawing, &line, &edge.text);
+        draw_text_on_line(&mut drawing, &line, &edge.text, self.edge_label_position);
+        drawing
+    }
+
+    /// Cells edge labels must not cover: every node box, plus the arrowheads
+    /// and box-start glyphs already drawn.
+    fn label_blocked_cells(&self, glyph_layers: &[&[Drawing]]) -> HashSet<(i32, i32)> {
+        let mut blocked = HashSet::new();
+        for node in &self.nodes {
+            let (Some(coord), Some(drawing)) = (node.drawing_coord, node.drawing.as_ref()) else {
+                continue;
+            };
+            let (w, h) = get_drawing_size(drawing);
+            for x in coord.x..=coord.x + w {
+                for y in coord.y..=coord.y + h {
+                    blocked.insert((x, y));
+                }
+            }
+        }
+        for drawing in glyph_layers.iter().flat_map(|layer| layer.iter()) {
+            blocked.extend(non_blank_cells(drawing));
+        }
+        blocked
+    }
+
+    /// Keeps `label` where it is unless it covers a blocked cell, in which
+    /// case it slides along its line and then tries the edge's other
+    /// segments. When nothing fits, the colliding cells are dropped so at
+    /// least the boxes stay intact. The chosen cells become blocked for
+    /// later labels.
+    fn place_label(
+        &self,
+        edge_idx: usize,
+        label: &Drawing,
+        blocked: &mut HashSet<(i32, i32)>,
+    ) -> Drawing {
+        let edge = &self.edges[edge_idx];
+        // Text pushed past the top or left edge is silently cut, so a
+        // candidate must also keep every glyph.
+        let glyphs: usize = edge
+            .text
+            .split_whitespace()
+            .map(UnicodeWidthStr::width)
+            .sum();
+        let fits = |drawing: &Drawing| {
+            non_blank_cells(drawing).len() >= glyphs
+                && label_cells(drawing)
+                    .iter()
+                    .all(|cell| !blocked.contains(cell))
+        };
+        let mut placed = None;
+        if fits(label) {
+            placed = Some(label.clone());
+        } else if edge.label_line.len() >= 2 {
+            let line = self.line_to_drawing(&edge.label_line);
+            let horizontal = line[0].y == line[1].y;
+            let span = (line[0].x - line[1].x)
+                .abs()
+                .max((line[0].y - line[1].y).abs());
+            // Slide along the line first, then step just beside it.
+            let beside = if horizontal {
+                1
+            } else {
+                label_width(&edge.text) / 2 + 1
+            };
+            let offsets = (1..=span)
+                .flat_map(|offset| [offset, -offset])
+                .map(|offset| (horizontal, offset))
+                .chain([(!horizontal, beside), (!horizontal, -beside)]);
+            let shifted = offsets.map(|(along_x, offset)| {
+                line.iter()
+                    .map(|point| DrawingCoord {
+                        x: point.x + if along_x { offset } else { 0 },
+                        y: point.y + if along_x { 0 } else { offset },
+                    })
+                    .collect::<Vec<_>>()
+            });
+            let segments = edge
+                .path
+                .windows(2)
+                .filter(|segment| *segment != edge.label_line.as_slice())
+                .map(|segment| self.line_to_drawing(segment));
+            placed = shifted.chain(segments).find_map(|candidate| {
+                let mut drawing = copy_canvas(&self.drawing);
+                draw_text_on_line(
+                    &mut drawing,
+                    &candidate,
+                    &edge.text,
+                    self.edge_label_position,
+                );
+                fits(&drawing).then_some(drawing)
+            });
+        }
+        let drawing = placed.unwrap_or_else(|| {
+            let mut trimmed = label.clone();
+            for (x, y) in non_blank_cells(label) {
+                if blocked.contains(&(x, y)) {
+                    trimmed[x as usize][y as usize] = " ".to_string();
+                }
+            }
+            trimmed
+        });
+        blocked.extend(label_cells(&drawing));
         drawing
     }
 
@@ -529,6 +848,23 @@ pub(crate) fn draw_box(node: &Node, graph: &Graph) -> Drawing {
         h += graph.row_height.get(&(grid.y + i)).unwrap_or(&0);
     }
     let mut drawing = mk_drawing(w, h);
+    if node.shape != NodeShape::Box {
+        let glyph = match (node.shape, graph.use_ascii) {
+            (NodeShape::Start, false) => "●",
+            (NodeShape::Start, true) => "*",
+            (_, false) => "◉",
+            (_, true) => "@",
+        };
+        let origin = graph.grid_to_drawing_coord(grid, None);
+        let center = graph.marker_center(node.index);
+        set_cell(
+            &mut drawing,
+            center.x - origin.x,
+            center.y - origin.y,
+            glyph,
+        );
+        return drawing;
+    }
     if !graph.use_ascii {
         for x in 1..w {
             set_cell(&mut drawing, x, 0, "─");
@@ -551,22 +887,69 @@ pub(crate) fn draw_box(node: &Node, graph: &Graph) -> Drawing {
             set_cell(&mut drawing, 0, y, "|");
             set_cell(&mut drawing, w, y, "|");
         }
-        set_cell(&mut drawing, 0, 0, "+");
-        set_cell(&mut drawing, w, 0, "+");
-        set_cell(&mut drawing, 0, h, "+");
-        set_cell(&mut drawing, w, h, "+");
-    }
-
-    let text_y = h / 2;
-    let name_len = node.label.chars().count() as i32;
-    let text_x = w / 2 - ceil_div(name_len, 2) + 1;
-    for (i, ch) in node.label.chars().enumerate() {
-        let wrapped = wrap_text_in_color(
-            ch.to_string(),
-            node.style_class.styles.get("color"),
-            &graph.style_type,
+        set_cell(
+            &mut drawing,
+            0,
+            0,
+            ascii_corner("┌", graph.ascii_corner_style),
+        );
+        set_cell(
+            &mut drawing,
+            w,
+            0,
+            ascii_corner("┐", graph.ascii_corner_style),
         );
-        set_cell(&mut drawing, text_x + i as i32, text_y, &wrapped);
+        set_cell(
+            &mut drawing,
+            0,
+            h,
+            ascii_corner("└", graph.ascii_corner_style),
+        );
+        set_cell(
+            &mut drawing,
+            w,
+            h,
+            ascii_corner("┘", graph.ascii_corner_style),
+        );
+    }
+
+    if graph.style_type == "cli" && node.style_class.styles.contains_key("fill") {
+        let glyph = graph
+            .fill_glyph
+            .unwrap_or(if graph.use_ascii { '.' } else { '░' })
+            .to_string();
+        for x in 1..w {
+            for y in 1..h {
+                set_cell(&mut drawing, x, y, &glyph);
+            }
+        }
+    }
+
+    if !node.compartments.is_empty() {
+        draw_compartments(&mut drawing, node, graph, w);
+        return drawing;
+    }
+
+    let label = graph.display_label(&node.label);
+    let text_top = 1 + max(h - 1 - label_height(&label), 0) / 2;
+    for (row, line) in label.split('\n').enumerate() {
+        let name_len = label_width(line);
+        let slack = max(w - 1 - name_len, 0);
+        let left_space = match (graph.node_label_align, graph.label_center_bias) {
+            (NodeLabelAlign::Left, _) => min(graph.box_border_padding_x, slack),
+            (NodeLabelAlign::Right, _) => max(slack - graph.box_border_padding_x, 0),
+            (NodeLabelAlign::Center, Some(LabelCenterBias::Left)) => slack / 2,
+            (NodeLabelAlign::Center, Some(LabelCenterBias::Right)) => slack - slack / 2,
+            (NodeLabelAlign::Center, None) => max(w / 2 - (name_len + 1) / 2, 0),
+        };
+        let text_x = 1 + left_space;
+        set_text(&mut drawing, text_x, text_top + row as i32, line, |ch| {
+            wrap_text_in_color(
+                ch.to_string(),
+                node.style_class.styles.get("color"),
+                &graph.style_type,
+            )
+        });
     }
     drawing
 }
@@ -600,14 +983,70 @@ fn draw_subgraph(sg: &Subgraph, graph: &Graph) -> Drawing {
             set_cell(&mut drawing, 0, y, "|");
             set_cell(&mut drawing, width, y, "|");
         }
-        set_cell(&mut drawing, 0, 0, "+");
-        set_cell(&mut drawing, width, 0, "+");
-        set_cell(&mut drawing, 0, height, "+");
-        set_cell(&mut drawing, width, height, "+");
+        set_cell(
+            &mut drawing,
+            0,
+            0,
+            ascii_corner("┌", graph.ascii_corner_style),
+        );
+        set_cell(
+            &mut drawing,
+            width,
+            0,
+            ascii_corner("┐", graph.ascii_corner_style),
+        );
+        set_cell(
+            &mut drawing,
+            0,
+            height,
+            ascii_corner("└", graph.ascii_corner_style),
+        );
+        set_cell(
+            &mut drawing,
+            width,
+            height,
+            ascii_corner("┘", graph.ascii_corner_style),
+        );
     }
     drawing
 }
 
+/// Fills a class box: the centered name, then each further section
+/// left-aligned below a divider.
+fn draw_compartments(drawing: &mut Drawing, node: &Node, graph: &Graph, w: i32) {
+    let (left, divider, right) = if graph.use_ascii {
+        ("+", "-", "+")
+    } else {
+        ("├", "─", "┤")
+    };
+    let mut y = 1;
+    for (idx, section) in node.compartments.iter().enumerate() {
+        if idx > 0 {
+            set_cell(drawing, 0, y, left);
+            for x in 1..w {
+                set_cell(drawing, x, y, divider);
+            }
+            set_cell(drawing, w, y, right);
+            y += 1;
+        }
+        for (row, line) in section.iter().enumerate() {
+            let x = if idx == 0 {
+                1 + max(w - 1 - label_width(line), 0) / 2
+            } else {
+                1 + graph.box_border_padding_x
+            };
+            set_text(drawing, x, y + row as i32, line, |ch| {
+                wrap_text_in_color(
+                    ch.to_string(),
+                    node.style_class.styles.get("color"),
+                    &graph.style_type,
+                )
+            });
+        }
+        y += section.len() as i32;
+    }
+}
+
 fn draw_subgraph_label(sg: &Subgraph) -> (Drawing, DrawingCoord) {
     let width = sg.max_x - sg.min_x;
     let height = sg.max_y - sg.min_y;
@@ -616,15 +1055,19 @@ fn draw_subgraph_label(sg: &Subgraph) -> (Drawing, DrawingCoord) {
     }
     let mut drawing = mk_drawing(width, height);
     let label_y = 1;
-    let mut label_x = width / 2 - (sg.name.chars().count() as i32) / 2;
+    let mut label_x = width / 2 - label_width(&sg.name) / 2;
     if label_x < 1 {
         label_x = 1;
     }
-    for (i, ch) in sg.name.chars().enumerate() {
-        let x = label_x + i as i32;
-        if x < width {
-            set_cell(&mut drawing, x, label_y, &ch.to_string());
+    let mut x = label_x;
+    for ch in sg.name.chars() {
+        let ch_width = UnicodeWidthChar::width(ch).unwrap_or(0) as i32;
+        if x + ch_width > width {
+            break;
         }
+        x = set_text(&mut drawing, x, label_y, &ch.to_string(), |ch| {
+            ch.to_string()
+        });
     }
     (
         drawing,
@@ -635,6 +1078,87 @@ fn draw_subgraph_label(sg: &Subgraph) -> (Drawing, DrawingCoord) {
     )
 }
 
+/// Maps a Unicode corner glyph onto its ASCII counterpart for the given style.
+fn ascii_corner(corner: &str, style: AsciiCornerStyle) -> &'static str {
+    match (style, corner) {
+        (AsciiCornerStyle::Rounded, "┌" | "┐") => ".",
+        (AsciiCornerStyle::Rounded, "└" | "┘") => "'",
+        _ => "+",
+    }
+}
+
+/// Swaps the light line glyphs drawn so far for the configured charset.
+fn restyle_lines(drawing: &mut Drawing, charset: CharSet) {
+    if matches!(charset, CharSet::Light | CharSet::Ascii) {
+        return;
+    }
+    for cell in drawing.iter_mut().flatten() {
+        let mut chars = cell.chars();
+        if let (Some(glyph), None) = (chars.next(), chars.next()) {
+            *cell = charset.glyph(glyph).to_string();
+        }
+    }
+}
+
+/// Restyles an edge's glyphs from its `linkStyle`: a `stroke-width` of 2 or
+/// more switches to heavy lines (tees on a box border keep the light border
+/// stroke), and `stroke` colors them for HTML output.
+fn apply_link_style(
+    drawing: &mut Drawing,
+    style: &HashMap<String, String>,
+    use_ascii: bool,
+    style_type: &str,
+) {
+    let thick = style
+        .get("stroke-width")
+        .and_then(|w| w.trim().trim_end_matches("px").parse::<f32>().ok())
+        .is_some_and(|w| w >= 2.0);
+    let dashed = style.contains_key("stroke-dasharray");
+    for column in drawing.iter_mut() {
+        for cell in column.iter_mut() {
+            if cell == " " {
+                continue;
+            }
+            if thick {
+                let heavy = if use_ascii {
+                    match cell.as_str() {
+                        "-" => "=",
+                        other => other,
+                    }
+                } else {
+                    match cell.as_str() {
+                        "─" => "━",
+                        "│" => "┃",
+                        "┌" => "┏",
+                        "┐" => "┓",
+                        "└" => "┗",
+                        "┘" => "┛",
+                        "├" => "┝",
+                        "┤" => "┥",
+                        "┬" => "┰",
+                        "┴" => "┸",
+                        other => other,
+                    }
+                };
+                *cell = heavy.to_string();
+            }
+            if dashed {
+                let dash = match (cell.as_str(), use_ascii) {
+                    ("─", _) => "┄",
+                    ("│", _) => "┆",
+                    ("━", _) => "┅",
+                    ("┃", _) => "┇",
+                    ("-", true) => ".",
+                    ("|", true) => ":",
+                    (other, _) => other,
+                };
+                *cell = dash.to_string();
+            }
+            *cell = wrap_text_in_color(cell.clone(), style.get("stroke"), style_type);
+        }
+    }
+}
+
 fn wrap_text_in_color(text: String, color: Option<&String>, style_type: &str) -> String {
     let Some(color) = color else { return text };
     if style_type == "html" {
@@ -656,7 +1180,7 @@ pub(crate) fn mk_drawing(x: i32, y: i32) -> Drawing {
     drawing
 }
 
-fn get_drawing_size(drawing: &Drawing) -> (i32, i32) {
+pub(crate) fn get_drawing_size(drawing: &Drawing) -> (i32, i32) {
     if drawing.is_empty() {
         return (0, 0);
     }
@@ -678,23 +1202,113 @@ pub(crate) fn increase_size(drawing: &mut Drawing, x: i32, y: i32) {
     *drawing = new_drawing;
 }
 
+/// Coordinates of every cell holding something other than a space.
+fn non_blank_cells(drawing: &Drawing) -> Vec<(i32, i32)> {
+    let mut cells = Vec::new();
+    for (x, column) in drawing.iter().enumerate() {
+        for (y, cell) in column.iter().enumerate() {
+            if cell != " " {
+                cells.push((x as i32, y as i32));
+            }
+        }
+    }
+    cells
+}
+
+/// Cells covered by label text, counting the spaces between words so
+/// nothing shows through the middle of a label.
+fn label_cells(drawing: &Drawing) -> Vec<(i32, i32)> {
+    let mut rows: HashMap<i32, (i32, i32)> = HashMap::new();
+    for (x, y) in non_blank_cells(drawing) {
+        let span = rows.entry(y).or_insert((x, x));
+        span.0 = span.0.min(x);
+        span.1 = span.1.max(x);
+    }
+    rows.into_iter()
+        .flat_map(|(y, (start, end))| (start..=end).map(move |x| (x, y)))
+        .collect()
+}
+
 fn copy_canvas(drawing: &Drawing) -> Drawing {
     let (x, y) = get_drawing_size(drawing);
     mk_drawing(x, y)
 }
 
-fn drawing_to_string(drawing: &Drawing) -> String {
-    let (max_x, max_y) = get_drawing_size(drawing);
-    let mut out = String::new();
-    for y in 0..=max_y {
-        for x in 0..=max_x {
-            out.push_str(&drawing[x as usize][y as usize]);
+/// Writes the drawing one row at a time, each prefixed with `indent`,
+/// without a trailing newline.
+pub(crate) fn write_drawing(
+    drawing: &Drawing,
+    indent: &str,
+    writer: &mut dyn Write,
+) -> std::io::Result<()> {
+    let (max_x, mut max_y) = get_drawing_size(drawing);
+    let mut row = String::new();
+    // Shapes such as the state end marker pad a blank row under the drawing.
+    while max_y > 0 {
+        drawing_row(drawing, max_y, max_x, &mut row);
+        if !row.trim().is_empty() {
+            break;
         }
+        max_y -= 1;
+    }
+    for y in 0..=max_y {
+        drawing_row(drawing, y, max_x, &mut row);
+        writer.write_all(indent.as_bytes())?;
+        writer.write_all(row.as_bytes())?;
         if y != max_y {
-            out.push('\n');
+            writer.write_all(b"\n")?;
         }
     }
-    out
+    Ok(())
+}
+
+/// Display width of the widest row, ignoring trailing blanks.
+pub(crate) fn drawing_width(drawing: &Drawing) -> i32 {
+    let (max_x, max_y) = get_drawing_size(drawing);
+    let mut row = String::new();
+    let mut width = 0;
+    for y in 0..=max_y {
+        drawing_row(drawing, y, max_x, &mut row);
+        width = width.max(UnicodeWidthStr::width(row.trim_end()) as i32);
+    }
+    width
+}
+
+fn drawing_row(drawing: &Drawing, y: i32, max_x: i32, row: &mut String) {
+    row.clear();
+    for x in 0..=max_x {
+        row.push_str(&drawing[x as usize][y as usize]);
+    }
+}
+
+/// Unit drawing offset for one of the four straight directions.
+fn step(dir: Direction) -> Option<(i32, i32)> {
+    match dir {
+        d if d == UP => Some((0, -1)),
+        d if d == DOWN => Some((0, 1)),
+        d if d == LEFT => Some((-1, 0)),
+        d if d == RIGHT => Some((1, 0)),
+        _ => None,
+    }
+}
+
+/// Direction of travel from the first to the last of `points`, or `None`
+/// when there are fewer than two distinct points.
+fn segment_direction(points: &[GridCoord]) -> Option<Direction> {
+    let (first, last) = (points.first()?, points.last()?);
+    if points.len() < 2 || first.equals(*last) {
+        return None;
+    }
+    Some(determine_direction(
+        GenericCoord {
+            x: first.x,
+            y: first.y,
+        },
+        GenericCoord {
+            x: last.x,
+            y: last.y,
+        },
+    ))
 }
 
 fn set_cell(drawing: &mut Drawing, x: i32, y: i32, value: &str) {
@@ -713,218 +1327,13 @@ fn set_cell(drawing: &mut Drawing, x: i32, y: i32, value: &str) {
     }
 }
 
-fn merge_junctions(c1: &str, c2: &str) -> String {
-    let mut map = HashMap::new();
-    map.insert(
-        "─",
-        vec![
-            ("│", "┼"),
-            ("┌", "┬"),
-            ("┐", "┬"),
-            ("└", "┴"),
-            ("┘", "┴"),
-            ("├", "┼"),
-            ("┤", "┼"),
-            ("┬", "┬"),
-            ("┴", "┴"),
-        ],
-    );
-    map.insert(
-        "│",
-        vec![
-            ("─", "┼"),
-            ("┌", "├"),
-            ("┐", "┤"),
-            ("└", "├"),
-            ("┘", "┤"),
-            ("├", "├"),
-            ("┤", "┤"),
-            ("┬", "┼"),
-            ("┴", "┼"),
-        ],
-    );
-    map.insert(
-        "┌",
-        vec![
-            ("─", "┬"),
-            ("│", "├"),
-            ("┐", "┬"),
-            ("└", "├"),
-            ("┘", "┼"),
-            ("├", "├"),
-            ("┤", "┼"),
-            ("┬", "┬"),
-            ("┴", "┼"),
-        ],
-    );
-    map.insert(
-        "┐",
-        vec![
-            ("─", "┬"),
-            ("│", "┤"),
-            ("┌", "┬"),
-            ("└", "┼"),
-            ("┘", "┤"),
-            ("├", "┼"),
-            ("┤", "┤"),
-            ("┬", "┬"),
-            ("┴", "┼"),
-        ],
-    );
-    map.insert(
-        "└",
-        vec![
-            ("─", "┴"),
-            ("│", "├"),
-            ("┌", "├"),
-            ("┐", "┼"),
-            ("┘", "┴"),
-            ("├", "├"),
-            ("┤", "┼"),
-            ("┬", "┼"),
-            ("┴", "┴"),
-        ],
-    );
-    map.insert(
-        "┘",
-        vec![
-            ("─", "┴"),
-            ("│", "┤"),
-            ("┌", "┼"),
-            ("┐", "┤"),
-            ("└", "┴"),
-            ("├", "┼"),
-            ("┤", "┤"),
-            ("┬", "┼"),
-            ("┴", "┴"),
-        ],
-    );
-    map.insert(
-        "├",
-        vec![
-            ("─", "┼"),
-            ("│", "├"),
-            ("┌", "├"),
-            ("┐", "┼"),
-            ("└", "├"),
-            ("┘", "┼"),
-            ("┤", "┼"),
-            ("┬", "┼"),
-            ("┴", "┼"),
-        ],
-    );
-    map.insert(
-        "┤",
-        vec![
-            ("─", "┼"),
-            ("│", "┤"),
-            ("┌", "┼"),
-            ("┐", "┤"),
-            ("└", "┼"),
-            ("┘", "┤"),
-            ("├", "┼"),
-            ("┬", "┼"),
-            ("┴", "┼"),
-        ],
-    );
-    map.insert(
-        "┬",
-        vec![
-            ("─", "┬"),
-            ("│", "┼"),
-            ("┌", "┬"),
-            ("┐", "┬"),
-            ("└", "┼"),
-            ("┘", "┼"),
-            ("├", "┼"),
-            ("┤", "┼"),
-            ("┴", "┼"),
-        ],
-    );
-    map.insert(
-        "┴",
-        vec![
-            ("─", "┴"),
-            ("│", "┼"),
-            ("┌", "┼"),
-            ("┐", "┼"),
-            ("└", "┴"),
-            ("┘", "┴"),
-            ("├", "┼"),
-            ("┤", "┼"),
-            ("┬", "┼"),
-        ],
-    );
-
-    if let Some(entries) = map.get(c1) {
-        for (other, merged) in entries {
-            if *other == c2 {
-                return merged.to_string();
-            }
-        }
-    }
-    c1.to_string()
-}
-
-fn is_junction_char(c: &str) -> bool {
-    matches!(
-        c,
-        "─" | "│"
-            | "┌"
-            | "┐"
-            | "└"
-            | "┘"
-            | "├"
-            | "┤"
-            | "┬"
-            | "┴"
-            | "┼"
-            | "╴"
-            | "╵"
-            | "╶"
-            | "╷"
-    )
-}
-
-fn junction_dirs(c: &str) -> (bool, bool, bool, bool) {
-    match c {
-        "─" => (false, false, true, true),
-        "│" => (true, true, false, false),
-        "┌" => (false, true, false, true),
-        "┐" => (false, true, true, false),
-        "└" => (true, false, false, true),
-        "┘" => (true, false, true, false),
-        "├" => (true, true, false, true),
-        "┤" => (true, true, true, false),
-        "┬" => (false, true, true, true),
-        "┴" => (true, false, true, true),
-        "┼" => (true, true, true, true),
-        "╴" => (false, false, true, false),
-        "╵" => (true, false, false, false),
-        "╶" => (false, false, false, true),
-        "╷" => (false, true, false, false),
-        _ => (false, false, false, false),
-    }
-}
-
-fn junction_from_dirs(up: bool, down: bool, left: bool, right: bool) -> &'static str {
-    match (up, down, left, right) {
-        (true, true, true, true) => "┼",
-        (true, true, true, false) => "┤",
-        (true, true, false, true) => "├",
-        (true, false, true, true) => "┴",
-        (false, true, true, true) => "┬",
-        (false, true, false, true) => "┌",
-        (false, true, true, false) => "┐",
-        (true, false, false, true) => "└",
-        (true, false, true, false) => "┘",
-        (true, true, false, false) => "│",
-        (false, false, true, true) => "─",
-        (true, false, false, false) => "│",
-        (false, true, false, false) => "│",
-        (false, false, true, false) => "─",
-        (false, false, false, true) => "─",
-        _ => " ",
+/// The single glyph in a drawing cell, or a space for empty and multi-char
+/// cells.
+fn cell_char(cell: &str) -> char {
+    let mut chars = cell.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c,
+        _ => ' ',
     }
 }
 
@@ -967,15 +1376,12 @@ fn merge_drawings(
                     let target_x = (x as i32 + offset.x) as usize;
                     let target_y = (y as i32 + offset.y) as usize;
                     let current = merged[target_x][target_y].clone();
-                    if !use_ascii && is_junction_char(value) && is_junction_char(&current) {
-                        let merged_value = merge_junctions(&current, value);
-                        if merged_value == "┼" {
-                            let (mut up, mut down, mut left, mut right) = junction_dirs(&current);
-                            let (v_up, v_down, v_left, v_right) = junction_dirs(value);
-                            up |= v_up;
-                            down |= v_down;
-                            left |= v_left;
-                            right |= v_right;
+                    let (current_char, value_char) = (cell_char(&current), cell_char(value));
+                    if !use_ascii && is_junction(value_char) && is_junction(current_char) {
+                        let merged_value = merge(current_char, value_char);
+                        if merged_value == '┼' {
+                            let (mut up, mut down, mut left, mut right) =
+                                junction_dirs(merged_value);
 
                             let target_x_i32 = target_x as i32;
                             let target_y_i32 = target_y as i32;
@@ -984,40 +1390,40 @@ fn merge_drawings(
 
                             if up {
                                 let neighbor_up = get_cell(&merged, target_x_i32, target_y_i32 - 1)
-                                    .map(|c| junction_dirs(c).1)
+                                    .map(|c| junction_dirs(cell_char(c)).1)
                                     .unwrap_or(false)
                                     || get_cell(drawing, local_x, local_y - 1)
-                                        .map(|c| junction_dirs(c).1)
+                                        .map(|c| junction_dirs(cell_char(c)).1)
                                         .unwrap_or(false);
                                 up = neighbor_up;
                             }
                             if down {
                                 let neighbor_down =
                                     get_cell(&merged, target_x_i32, target_y_i32 + 1)
-                                        .map(|c| junction_dirs(c).0)
+                                        .map(|c| junction_dirs(cell_char(c)).0)
                                         .unwrap_or(false)
                                         || get_cell(drawing, local_x, local_y + 1)
-                                            .map(|c| junction_dirs(c).0)
+                                            .map(|c| junction_dirs(cell_char(c)).0)
                                             .unwrap_or(false);
                                 down = neighbor_down;
                             }
                             if left {
                                 let neighbor_left =
                                     get_cell(&merged, target_x_i32 - 1, target_y_i32)
-                                        .map(|c| junction_dirs(c).3)
+                                        .map(|c| junction_dirs(cell_char(c)).3)
                                         .unwrap_or(false)
                                         || get_cell(drawing, local_x - 1, local_y)
-                                            .map(|c| junction_dirs(c).3)
+                                            .map(|c| junction_dirs(cell_char(c)).3)
                                             .unwrap_or(false);
                                 left = neighbor_left;
                             }
                             if right {
                                 let neighbor_right =
                                     get_cell(&merged, target_x_i32 + 1, target_y_i32)
-                                        .map(|c| junction_dirs(c).2)
+                                        .map(|c| junction_dirs(cell_char(c)).2)
                                         .unwrap_or(false)
                                         || get_cell(drawing, local_x + 1, local_y)
-                                            .map(|c| junction_dirs(c).2)
+                                            .map(|c| junction_dirs(cell_char(c)).2)
                                             .unwrap_or(false);
                                 right = neighbor_right;
                             }
@@ -1025,7 +1431,7 @@ fn merge_drawings(
                             merged[target_x][target_y] =
                                 junction_from_dirs(up, down, left, right).to_string();
                         } else {
-                            merged[target_x][target_y] = merged_value;
+                            merged[target_x][target_y] = merged_value.to_string();
                         }
                     } else {
                         merged[target_x][target_y] = value.clone();
@@ -1048,7 +1454,14 @@ impl Graph {
     }
 }
 
-fn draw_text_on_line(drawing: &mut Drawing, line: &[DrawingCoord], label: &str) {
+/// Draws `label` on `line`, centered or, for `Start`/`End`, held one cell
+/// off the line's first or last point, which lie on node borders.
+fn draw_text_on_line(
+    drawing: &mut Drawing,
+    line: &[DrawingCoord],
+    label: &str,
+    position: EdgeLabelPosition,
+) {
     if line.len() < 2 {
         return;
     }
@@ -1062,23 +1475,133 @@ fn draw_text_on_line(drawing: &mut Drawing, line: &[DrawingCoord], label: &str)
     } else {
         (line[0].y, line[1].y)
     };
-    let middle_x = min_x + (max_x - min_x) / 2;
-    let middle_y = min_y + (max_y - min_y) / 2;
-    let start_x = middle_x - (label.chars().count() as i32) / 2;
-    draw_text(
-        drawing,
-        DrawingCoord {
-            x: start_x,
-            y: middle_y,
-        },
-        label,
-    );
+    let mut middle_x = min_x + (max_x - min_x) / 2;
+    let mut middle_y = min_y + (max_y - min_y) / 2;
+    let width = label_width(label);
+    let height = label_height(label);
+    // Gap from the anchor point to the near side of the text: one line cell
+    // past the border at the start, and past the arrowhead as well at the end.
+    let (anchor, gap) = match position {
+        EdgeLabelPosition::Middle => (None, 0),
+        EdgeLabelPosition::Start => (Some((line[0], line[1])), 2),
+        EdgeLabelPosition::End => (Some((line[1], line[0])), 3),
+    };
+    if let Some((near, far)) = anchor {
+        let (dx, dy) = ((far.x - near.x).signum(), (far.y - near.y).signum());
+        if dy == 0 {
+            let offset = gap + width / 2;
+            if offset < (max_x - min_x) / 2 {
+                middle_x = near.x + dx * offset;
+            }
+        } else {
+            let offset = gap + (height - 1) / 2;
+            if offset < (max_y - min_y) / 2 {
+                middle_y = near.y + dy * offset;
+            }
+        }
+    }
+    let start_y = middle_y - (height - 1) / 2;
+    for (row, text) in label.split('\n').enumerate() {
+        let start_x = middle_x - width / 2 + (width - label_width(text)) / 2;
+        draw_text(
+            drawing,
+            DrawingCoord {
+                x: start_x,
+                y: start_y + row as i32,
+            },
+            text,
+        );
+    }
 }
 
 fn draw_text(drawing: &mut Drawing, start: DrawingCoord, text: &str) {
-    increase_size(drawing, start.x + text.chars().count() as i32, start.y);
-    for (i, ch) in text.chars().enumerate() {
-        set_cell(drawing, start.x + i as i32, start.y, &ch.to_string());
+    increase_size(drawing, start.x + label_width(text), start.y);
+    set_text(drawing, start.x, start.y, text, |ch| ch.to_string());
+}
+
+/// Lists the labels `labels_as_legend` took off the edges, one `① text` line
+/// each, below a blank row.
+fn draw_legend(drawing: &mut Drawing, graph: &Graph) {
+    let (_, height) = get_drawing_size(drawing);
+    for (idx, label) in graph.legend.iter().enumerate() {
+        let line = format!("{} {}", legend_marker(idx + 1, graph.use_ascii), label);
+        draw_text(
+            drawing,
+            DrawingCoord {
+                x: 0,
+                y: height + 2 + idx as i32,
+            },
+            &line,
+        );
+    }
+}
+
+/// Writes `text` one cell per display column starting at `x`, returning the
+/// column after it. A wide character empties the cell it spills into and a
+/// zero-width one joins the previous cell, so rows keep their display width.
+fn set_text(
+    drawing: &mut Drawing,
+    x: i32,
+    y: i32,
+    text: &str,
+    paint: impl Fn(char) -> String,
+) -> i32 {
+    let mut x = x;
+    for ch in text.chars() {
+        match UnicodeWidthChar::width(ch).unwrap_or(0) {
+            0 if x > 0 && y >= 0 && (x as usize) <= drawing.len() => {
+                if let Some(cell) = drawing
+                    .get_mut(x as usize - 1)
+                    .and_then(|col| col.get_mut(y as usize))
+                {
+                    cell.push_str(&paint(ch));
+                }
+            }
+            0 => {}
+            width => {
+                set_cell(drawing, x, y, &paint(ch));
+                for filler in 1..width as i32 {
+                    set_cell(drawing, x + filler, y, "");
+                }
+                x += width as i32;
+            }
+        }
+    }
+    x
+}
+
+/// Marks every grid-cell corner that is still blank with a faint dot, leaving
+/// node interiors untouched.
+fn draw_grid_overlay(drawing: &mut Drawing, graph: &Graph) {
+    let inside_node = |x: i32, y: i32| {
+        graph.nodes.iter().any(|node| {
+            let (Some(coord), Some(box_drawing)) = (node.drawing_coord, node.drawing.as_ref())
+            else {
+                return false;
+            };
+            let (w, h) = get_drawing_size(box_drawing);
+            x >= coord.x && x <= coord.x + w && y >= coord.y && y <= coord.y + h
+        })
+    };
+    let boundaries = |sizes: &HashMap<i32, i32>, offset: i32| {
+        let last = sizes.keys().copied().max().unwrap_or(-1);
+        let mut positions = vec![offset];
+        let mut pos = offset;
+        for idx in 0..=last {
+            pos += sizes.get(&idx).copied().unwrap_or(0);
+            positions.push(pos);
+        }
+        positions
+    };
+    let xs = boundaries(&graph.column_width, graph.offset_x);
+    let ys = boundaries(&graph.row_height, graph.offset_y);
+    let dot = if graph.use_ascii { "." } else { "·" };
+    for &x in &xs {
+        for &y in &ys {
+            if get_cell(drawing, x, y) == Some(" ") && !inside_node(x, y) {
+                set_cell(drawing, x, y, dot);
+            }
+        }
     }
 }
 
@@ -1095,10 +1618,12 @@ fn debug_drawing_wrapper(drawing: &Drawing) -> Drawing {
         for y in 0..debug[0].len() {
             let src_x = x as i32 - 2;
             let src_y = y as i32 - 1;
-            if src_x >= 0 && src_y >= 0 {
-                if (src_x as usize) < drawing.len() && (src_y as usize) < drawing[0].len() {
-                    debug[x][y] = drawing[src_x as usize][src_y as usize].clone();
-                }
+            if src_x >= 0
+                && src_y >= 0
+                && (src_x as usize) < drawing.len()
+                && (src_y as usize) < drawing[0].len()
+            {
+                debug[x][y] = drawing[src_x as usize][src_y as usize].clone();
             }
         }
     }
@@ -1131,7 +1656,7 @@ fn debug_coord_wrapper(drawing: &Drawing, graph: &Graph) -> Drawing {
     merge_drawings(
         &debug,
         DrawingCoord { x: 1, y: 1 },
-        &[drawing.clone()],
+        std::slice::from_ref(drawing),
         graph.use_ascii,
     )
 }
@@ -0,0 +1,109 @@
+use crate::graph::draw::get_drawing_size;
+use crate::graph::layout::layout_graph;
+use crate::graph::types::{Graph, GraphProperties};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct LayoutJson {
+    nodes: Vec<NodeJson>,
+    edges: Vec<EdgeJson>,
+    subgraphs: Vec<SubgraphJson>,
+}
+
+#[derive(Debug, Serialize)]
+struct NodeJson {
+    id: String,
+    label: String,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct EdgeJson {
+    from: String,
+    to: String,
+    label: String,
+    bidirectional: bool,
+    points: Vec<PointJson>,
+}
+
+#[derive(Debug, Serialize)]
+struct PointJson {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct SubgraphJson {
+    name: String,
+    nodes: Vec<String>,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+/// Serializes the laid-out graph. All positions are character cells in the
+/// same grid the text renderer draws into, with (0, 0) at the top-left.
+pub(crate) fn layout_json(properties: &GraphProperties) -> Result<String, String> {
+    let graph = layout_graph(properties);
+    let layout = LayoutJson {
+        nodes: graph
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                let coord = node.drawing_coord?;
+                let (max_x, max_y) = get_drawing_size(node.drawing.as_ref()?);
+                Some(NodeJson {
+                    id: node.name.clone(),
+                    label: node.label.clone(),
+                    x: coord.x,
+                    y: coord.y,
+                    width: max_x + 1,
+                    height: max_y + 1,
+                })
+            })
+            .collect(),
+        edges: graph
+            .edges
+            .iter()
+            .map(|edge| EdgeJson {
+                from: graph.nodes[edge.from].name.clone(),
+                to: graph.nodes[edge.to].name.clone(),
+                label: edge.text.clone(),
+                bidirectional: edge.bidirectional,
+                points: edge
+                    .path
+                    .iter()
+                    .map(|coord| {
+                        let dc = graph.grid_to_drawing_coord(*coord, None);
+                        PointJson { x: dc.x, y: dc.y }
+                    })
+                    .collect(),
+            })
+            .collect(),
+        subgraphs: graph
+            .subgraphs
+            .iter()
+            .filter(|sg| !sg.nodes.is_empty())
+            .map(|sg| SubgraphJson {
+                name: sg.name.clone(),
+                nodes: node_names(&graph, &sg.nodes),
+                x: sg.min_x,
+                y: sg.min_y,
+                width: sg.max_x - sg.min_x + 1,
+                height: sg.max_y - sg.min_y + 1,
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&layout).map_err(|e| e.to_string())
+}
+
+fn node_names(graph: &Graph, indices: &[usize]) -> Vec<String> {
+    indices
+        .iter()
+        .map(|&idx| graph.nodes[idx].name.clone())
+        .collect()
+}
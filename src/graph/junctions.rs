@@ -0,0 +1,68 @@
+//! Merging of light box-drawing characters where two lines meet, so that a
+//! `─` drawn over a `│` becomes `┼` rather than replacing it.
+
+/// The arms of a light line glyph as `(up, down, left, right)`; any other
+/// character has none.
+pub fn junction_dirs(c: char) -> (bool, bool, bool, bool) {
+    match c {
+        '─' => (false, false, true, true),
+        '│' => (true, true, false, false),
+        '┌' => (false, true, false, true),
+        '┐' => (false, true, true, false),
+        '└' => (true, false, false, true),
+        '┘' => (true, false, true, false),
+        '├' => (true, true, false, true),
+        '┤' => (true, true, true, false),
+        '┬' => (false, true, true, true),
+        '┴' => (true, false, true, true),
+        '┼' => (true, true, true, true),
+        '╴' => (false, false, true, false),
+        '╵' => (true, false, false, false),
+        '╶' => (false, false, false, true),
+        '╷' => (false, true, false, false),
+        _ => (false, false, false, false),
+    }
+}
+
+/// The glyph with exactly these arms. A single arm is drawn as a full line
+/// and no arms as a space.
+pub fn junction_from_dirs(up: bool, down: bool, left: bool, right: bool) -> char {
+    match (up, down, left, right) {
+        (true, true, true, true) => '┼',
+        (true, true, true, false) => '┤',
+        (true, true, false, true) => '├',
+        (true, false, true, true) => '┴',
+        (false, true, true, true) => '┬',
+        (false, true, false, true) => '┌',
+        (false, true, true, false) => '┐',
+        (true, false, false, true) => '└',
+        (true, false, true, false) => '┘',
+        (true, true, false, false) | (true, false, false, false) | (false, true, false, false) => {
+            '│'
+        }
+        (false, false, true, true) | (false, false, true, false) | (false, false, false, true) => {
+            '─'
+        }
+        _ => ' ',
+    }
+}
+
+pub fn is_junction(c: char) -> bool {
+    junction_dirs(c) != (false, false, false, false)
+}
+
+/// The glyph left when `b` is drawn over `a`: both sets of arms when both are
+/// line glyphs, otherwise simply `b`.
+pub fn merge(a: char, b: char) -> char {
+    if !is_junction(a) || !is_junction(b) {
+        return b;
+    }
+    let (a_up, a_down, a_left, a_right) = junction_dirs(a);
+    let (b_up, b_down, b_left, b_right) = junction_dirs(b);
+    junction_from_dirs(
+        a_up || b_up,
+        a_down || b_down,
+        a_left || b_left,
+        a_right || b_right,
+    )
+}
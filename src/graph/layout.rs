@@ -1,10 +1,112 @@
+use crate::diagram::{EdgeLabelPosition, SelfLoopSide, truncate_label};
 use crate::graph::draw::{draw_box, increase_size, mk_drawing};
 use crate::graph::types::{
-    DrawingCoord, Graph, GraphProperties, GridCoord, MIDDLE, QueueItem, Subgraph,
-    determine_start_and_end_dir, heuristic, max, merge_path, min,
+    DOWN, Direction, DrawingCoord, Graph, GraphProperties, GridCoord, LEFT, MIDDLE, NodeShape,
+    QueueItem, RIGHT, Subgraph, UP, determine_start_and_end_dir, heuristic, label_height,
+    label_width, max, merge_path, min,
 };
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 
+/// Extra A* cost for stepping onto a cell an earlier parallel edge uses.
+const PARALLEL_EDGE_PENALTY: i32 = 8;
+/// Extra A* cost for changing direction, so routes prefer fewer bends.
+const TURN_PENALTY: i32 = 1;
+/// Extra A* cost for stepping onto a cell that touches a node box.
+const NODE_ADJACENT_PENALTY: i32 = 1;
+
+/// Builds the graph model and runs layout, leaving every node, edge and
+/// subgraph with final coordinates ready for a renderer.
+pub(crate) fn layout_graph(properties: &GraphProperties) -> Graph {
+    let mut graph = mk_graph(properties);
+    graph.set_style_classes(properties);
+    graph.padding_x = properties.padding_x;
+    graph.padding_y = properties.padding_y;
+    graph.box_border_padding_x = properties.box_border_padding_x;
+    graph.box_border_padding_y = properties.box_border_padding_y;
+    graph.use_ascii = properties.use_ascii;
+    graph.graph_direction = properties.graph_direction.clone();
+    graph.set_subgraphs(&properties.subgraphs);
+    if graph.labels_as_legend {
+        graph.move_labels_to_legend();
+    }
+    graph.create_mapping();
+    graph
+}
+
+/// Flips `LR`/`TD` when the declared direction is estimated to overflow
+/// `max_width` and the other one is narrower.
+pub(crate) fn fitting_direction(properties: &GraphProperties, max_width: i32) -> String {
+    let current = properties.graph_direction.as_str();
+    let other = if current == "LR" { "TD" } else { "LR" };
+    let width = estimated_width(properties, current);
+    if width > max_width && estimated_width(properties, other) < width {
+        other.to_string()
+    } else {
+        current.to_string()
+    }
+}
+
+/// Rough text width of the graph laid out in `direction`: ranks (the longest
+/// chain) run across for LR, the widest rank for TD.
+fn estimated_width(properties: &GraphProperties, direction: &str) -> i32 {
+    let mut incoming: HashSet<&str> = HashSet::new();
+    for edges in properties.data.values() {
+        for edge in edges {
+            if edge.child.name != edge.parent.name {
+                incoming.insert(&edge.child.name);
+            }
+        }
+    }
+    let mut levels: HashMap<&str, usize> = HashMap::new();
+    let mut queue: std::collections::VecDeque<&str> = properties
+        .data
+        .keys()
+        .map(String::as_str)
+        .filter(|name| !incoming.contains(name))
+        .collect();
+    if queue.is_empty()
+        && let Some(first) = properties.data.keys().next()
+    {
+        queue.push_back(first);
+    }
+    for name in &queue {
+        levels.insert(name, 0);
+    }
+    while let Some(name) = queue.pop_front() {
+        let level = levels[name];
+        for edge in &properties.data[name] {
+            if !levels.contains_key(edge.child.name.as_str()) {
+                levels.insert(&edge.child.name, level + 1);
+                queue.push_back(&edge.child.name);
+            }
+        }
+    }
+
+    let mut per_level: HashMap<usize, i32> = HashMap::new();
+    for level in levels.values() {
+        *per_level.entry(*level).or_default() += 1;
+    }
+    let node_width = properties
+        .data
+        .keys()
+        .map(|name| {
+            let label = properties.node_labels.get(name).unwrap_or(name);
+            label_width(label)
+        })
+        .max()
+        .unwrap_or(0)
+        + 2 * properties.box_border_padding_x
+        + 2;
+    let columns = if direction == "LR" {
+        per_level.len() as i32
+    } else {
+        per_level.values().copied().max().unwrap_or(0)
+    };
+    columns * (node_width + properties.padding_x)
+}
+
 pub(crate) fn mk_graph(properties: &GraphProperties) -> Graph {
     let mut graph = Graph {
         nodes: Vec::new(),
@@ -17,33 +119,80 @@ pub(crate) fn mk_graph(properties: &GraphProperties) -> Graph {
         style_type: properties.style_type.clone(),
         padding_x: properties.padding_x,
         padding_y: properties.padding_y,
-        box_border_padding: properties.box_border_padding,
+        box_border_padding_x: properties.box_border_padding_x,
+        box_border_padding_y: properties.box_border_padding_y,
         subgraphs: Vec::new(),
         offset_x: 0,
         offset_y: 0,
         use_ascii: properties.use_ascii,
         graph_direction: properties.graph_direction.clone(),
         node_index_by_name: HashMap::new(),
+        children_index: HashMap::new(),
+        parents_index: HashMap::new(),
+        label_center_bias: properties.label_center_bias,
+        ascii_corner_style: properties.ascii_corner_style,
+        edge_lane_padding: properties.edge_lane_padding,
+        self_loop_size: properties.self_loop_size,
+        self_loop_side: properties.self_loop_side,
+        node_label_align: properties.node_label_align,
+        fill_glyph: properties.fill_glyph,
+        node_min_width: properties.node_min_width,
+        charset: properties.charset,
+        labels_as_legend: properties.labels_as_legend,
+        legend: Vec::new(),
+        arrow_glyphs: properties.arrow_glyphs,
+        compact: properties.compact,
+        component_spacing: properties.component_spacing,
+        max_label_width: properties.max_label_width,
+        edge_label_position: properties.edge_label_position,
+        path_search_limit: properties.path_search_limit,
+        deterministic_edges: properties.deterministic_edges,
     };
 
-    for (node_name, children) in &properties.data {
-        let parent_label = properties
+    // Insert every node up front so node order follows first appearance in
+    // the source rather than the order edges happen to reach them.
+    for node_name in properties.data.keys() {
+        let label = properties
             .node_labels
             .get(node_name)
             .cloned()
             .unwrap_or_else(|| node_name.clone());
-        let (parent_idx, _) = graph.get_or_insert_node(node_name, &parent_label, "");
+        let (idx, _) = graph.get_or_insert_node(node_name, &label, "");
+        if let Some(shape) = properties.node_shapes.get(node_name) {
+            graph.nodes[idx].shape = *shape;
+        }
+        if let Some(compartments) = properties.node_compartments.get(node_name) {
+            graph.nodes[idx].compartments = compartments.clone();
+        }
+    }
+
+    for (node_name, children) in &properties.data {
+        let parent_idx = graph.node_index_by_name[node_name];
         for edge in children {
-            let child_label = properties
-                .node_labels
-                .get(&edge.child.name)
-                .cloned()
-                .unwrap_or_else(|| edge.child.label.clone());
-            let (child_idx, inserted) =
-                graph.get_or_insert_node(&edge.child.name, &child_label, &edge.get_child_style());
-            if inserted {
+            let child_idx = graph.node_index_by_name[&edge.child.name];
+            if !edge.parent.style_class.is_empty() {
                 graph.nodes[parent_idx].style_class_name = edge.parent.style_class.clone();
             }
+            let child_style = edge.get_child_style();
+            if !child_style.is_empty() {
+                graph.nodes[child_idx].style_class_name = child_style;
+            }
+            // Repeating an identical edge would only draw the same route twice.
+            if graph.edges.iter().any(|existing| {
+                existing.from == parent_idx
+                    && existing.to == child_idx
+                    && existing.text == edge.label
+                    && existing.end == edge.end
+                    && existing.invisible == edge.invisible
+                    && existing.cardinality == edge.cardinality
+                    && existing.tail == edge.tail
+            }) {
+                continue;
+            }
+            let mut style = properties.default_link_style.clone();
+            if let Some(link_style) = properties.link_styles.get(&edge.index) {
+                style.extend(link_style.clone());
+            }
             graph.edges.push(crate::graph::types::Edge {
                 from: parent_idx,
                 to: child_idx,
@@ -52,14 +201,69 @@ pub(crate) fn mk_graph(properties: &GraphProperties) -> Graph {
                 label_line: Vec::new(),
                 start_dir: MIDDLE,
                 end_dir: MIDDLE,
+                bidirectional: false,
+                back_edge: false,
+                style,
+                end: edge.end,
+                invisible: edge.invisible,
+                extra_length: edge.extra_length,
+                cardinality: edge.cardinality,
+                tail: edge.tail,
             });
         }
     }
 
+    if properties.merge_bidirectional {
+        graph.merge_bidirectional_edges();
+    }
+
     graph
 }
 
 impl Graph {
+    /// Replaces each edge label with a numbered marker, keeping the text for
+    /// the legend drawn under the graph.
+    pub(crate) fn move_labels_to_legend(&mut self) {
+        for edge in &mut self.edges {
+            if edge.text.is_empty() {
+                continue;
+            }
+            self.legend.push(edge.text.replace('\n', " "));
+            edge.text = legend_marker(self.legend.len(), self.use_ascii);
+        }
+    }
+
+    /// Collapses each `A --> B` / `B --> A` pair into one edge with arrow
+    /// heads on both ends, keeping whichever label is set. Pairs labeled on
+    /// both sides stay apart so neither label is lost.
+    pub(crate) fn merge_bidirectional_edges(&mut self) {
+        let mut merged: Vec<crate::graph::types::Edge> = Vec::new();
+        for edge in std::mem::take(&mut self.edges) {
+            let reverse = merged.iter_mut().find(|other| {
+                !other.bidirectional
+                    && other.end == edge.end
+                    && other.invisible == edge.invisible
+                    && other.cardinality.is_none()
+                    && edge.cardinality.is_none()
+                    && other.tail.is_none()
+                    && edge.tail.is_none()
+                    && other.from == edge.to
+                    && other.to == edge.from
+                    && edge.from != edge.to
+                    && (other.text.is_empty() || edge.text.is_empty())
+            });
+            if let Some(reverse) = reverse {
+                reverse.bidirectional = true;
+                if reverse.text.is_empty() {
+                    reverse.text = edge.text;
+                }
+                continue;
+            }
+            merged.push(edge);
+        }
+        self.edges = merged;
+    }
+
     pub(crate) fn get_or_insert_node(
         &mut self,
         name: &str,
@@ -67,10 +271,10 @@ impl Graph {
         style_class: &str,
     ) -> (usize, bool) {
         if let Some(idx) = self.node_index_by_name.get(name) {
-            if let Some(node) = self.nodes.get_mut(*idx) {
-                if label != name {
-                    node.label = label.to_string();
-                }
+            if let Some(node) = self.nodes.get_mut(*idx)
+                && node.label == name
+            {
+                node.label = label.to_string();
             }
             return (*idx, false);
         }
@@ -85,6 +289,8 @@ impl Graph {
             index: idx,
             style_class_name: style_class.to_string(),
             style_class: crate::graph::types::StyleClass::default(),
+            shape: NodeShape::Box,
+            compartments: Vec::new(),
         });
         self.node_index_by_name.insert(name.to_string(), idx);
         (idx, true)
@@ -96,10 +302,16 @@ impl Graph {
         self.padding_x = properties.padding_x;
         self.padding_y = properties.padding_y;
         for node in &mut self.nodes {
-            if !node.style_class_name.is_empty() {
-                if let Some(class) = self.style_classes.get(&node.style_class_name) {
-                    node.style_class = class.clone();
-                }
+            if let Some(class_name) = properties.node_class_names.get(&node.name) {
+                node.style_class_name = class_name.clone();
+            }
+            if !node.style_class_name.is_empty()
+                && let Some(class) = self.style_classes.get(&node.style_class_name)
+            {
+                node.style_class = class.clone();
+            }
+            if let Some(styles) = properties.node_styles.get(&node.name) {
+                node.style_class.styles.extend(styles.clone());
             }
         }
     }
@@ -118,6 +330,7 @@ impl Graph {
                 nodes,
                 parent: None,
                 children: Vec::new(),
+                direction: tsg.direction.clone(),
                 min_x: 0,
                 min_y: 0,
                 max_x: 0,
@@ -133,20 +346,117 @@ impl Graph {
         }
     }
 
+    /// Indexes edges by endpoint so child/parent lookups during layout do not
+    /// rescan every edge. Back edges are left out of the child index so level
+    /// assignment never follows a cycle.
+    pub(crate) fn build_edge_index(&mut self) {
+        self.mark_back_edges();
+        self.children_index.clear();
+        self.parents_index.clear();
+        for edge in &self.edges {
+            if !edge.back_edge {
+                self.children_index
+                    .entry(edge.from)
+                    .or_default()
+                    .push(edge.to);
+            }
+            self.parents_index
+                .entry(edge.to)
+                .or_default()
+                .push(edge.from);
+        }
+    }
+
+    /// Groups node indices into connected parts, ignoring edge direction,
+    /// ordered by their first declared node. A graph without edges is kept
+    /// as a single part so its nodes still line up as a chain.
+    fn components(&self) -> Vec<Vec<usize>> {
+        if self.edges.is_empty() {
+            return vec![(0..self.nodes.len()).collect()];
+        }
+        let mut parent: Vec<usize> = (0..self.nodes.len()).collect();
+        fn find(parent: &mut [usize], mut idx: usize) -> usize {
+            while parent[idx] != idx {
+                parent[idx] = parent[parent[idx]];
+                idx = parent[idx];
+            }
+            idx
+        }
+        for edge in &self.edges {
+            let (a, b) = (find(&mut parent, edge.from), find(&mut parent, edge.to));
+            parent[a.max(b)] = a.min(b);
+        }
+        let mut components: Vec<Vec<usize>> = Vec::new();
+        let mut slot: HashMap<usize, usize> = HashMap::new();
+        for idx in 0..self.nodes.len() {
+            let root = find(&mut parent, idx);
+            let next = components.len();
+            let at = *slot.entry(root).or_insert(next);
+            if at == next {
+                components.push(Vec::new());
+            }
+            components[at].push(idx);
+        }
+        components
+    }
+
+    /// Flags every edge that closes a cycle, found by a depth-first walk that
+    /// starts from nodes in declaration order.
+    pub(crate) fn mark_back_edges(&mut self) {
+        let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+        for (edge_idx, edge) in self.edges.iter_mut().enumerate() {
+            edge.back_edge = false;
+            outgoing[edge.from].push(edge_idx);
+        }
+
+        // 0 = unvisited, 1 = on the current path, 2 = finished.
+        let mut state = vec![0u8; self.nodes.len()];
+        for start in 0..self.nodes.len() {
+            if state[start] != 0 {
+                continue;
+            }
+            state[start] = 1;
+            let mut stack: Vec<(usize, usize)> = vec![(start, 0)];
+            while let Some((node, next)) = stack.last_mut() {
+                let node = *node;
+                if let Some(&edge_idx) = outgoing[node].get(*next) {
+                    *next += 1;
+                    let to = self.edges[edge_idx].to;
+                    match state[to] {
+                        0 => {
+                            state[to] = 1;
+                            stack.push((to, 0));
+                        }
+                        1 => self.edges[edge_idx].back_edge = true,
+                        _ => {}
+                    }
+                } else {
+                    state[node] = 2;
+                    stack.pop();
+                }
+            }
+        }
+    }
+
     pub(crate) fn create_mapping(&mut self) {
+        if self.deterministic_edges {
+            // Stable, so parallel edges keep their declared order.
+            self.edges.sort_by_key(|edge| (edge.from, edge.to));
+        }
+        self.build_edge_index();
         let mut highest_position_per_level = vec![0; 100];
 
-        let mut nodes_found: HashSet<String> = HashSet::new();
-        let mut root_nodes: Vec<usize> = Vec::new();
-        for node in &self.nodes {
-            if !nodes_found.contains(&node.name) {
-                root_nodes.push(node.index);
-            }
-            nodes_found.insert(node.name.clone());
-            for child in self.get_children(node.index) {
-                nodes_found.insert(self.nodes[child].name.clone());
+        // Roots are the nodes nothing points at, whatever order the edges
+        // were declared in. Self-loops don't count against a node.
+        let mut in_degree = vec![0; self.nodes.len()];
+        for edge in &self.edges {
+            if edge.from != edge.to {
+                in_degree[edge.to] += 1;
             }
         }
+        let root_nodes: Vec<usize> = (0..self.nodes.len())
+            .filter(|idx| in_degree[*idx] == 0)
+            .collect();
 
         let mut has_external_roots = false;
         let mut has_subgraph_roots_with_edges = false;
@@ -176,94 +486,203 @@ impl Graph {
             external_root_nodes = root_nodes.clone();
         }
 
-        for idx in &external_root_nodes {
-            let coord = if self.graph_direction == "LR" {
-                self.reserve_spot_in_grid(
-                    *idx,
-                    GridCoord {
-                        x: 0,
-                        y: highest_position_per_level[0],
-                    },
-                )
-            } else {
-                self.reserve_spot_in_grid(
-                    *idx,
-                    GridCoord {
-                        x: highest_position_per_level[0],
-                        y: 0,
-                    },
-                )
-            };
-            self.nodes[*idx].grid_coord = Some(coord);
-            highest_position_per_level[0] += 4;
+        // Disconnected parts are laid out one after another, each in its own
+        // band across the levels, so their nodes never interleave. Separated
+        // subgraph roots already keep to their own level instead.
+        let components = if should_separate {
+            vec![(0..self.nodes.len()).collect()]
+        } else {
+            self.components()
+        };
+        let mut component_of = vec![0; self.nodes.len()];
+        for (component, members) in components.iter().enumerate() {
+            for &idx in members {
+                component_of[idx] = component;
+            }
         }
-
-        if should_separate && !subgraph_root_nodes.is_empty() {
-            let subgraph_level = 4;
-            for idx in &subgraph_root_nodes {
-                let coord = if self.graph_direction == "LR" {
+        let mut queued = vec![false; self.nodes.len()];
+        let mut band_gaps = Vec::new();
+        for (component, members) in components.iter().enumerate() {
+            if component > 0 {
+                let floor = highest_position_per_level
+                    .iter()
+                    .copied()
+                    .max()
+                    .unwrap_or(0);
+                highest_position_per_level.fill(floor);
+                band_gaps.push(floor - 1);
+            }
+            // Without any edges there are no levels, so lay the nodes out along
+            // the primary axis like a chain instead of stacking them.
+            let roots_along_primary = self.edges.is_empty();
+            for idx in external_root_nodes
+                .iter()
+                .filter(|idx| component_of[**idx] == component)
+            {
+                let coord = if (self.graph_direction == "LR") != roots_along_primary {
                     self.reserve_spot_in_grid(
                         *idx,
                         GridCoord {
-                            x: subgraph_level,
-                            y: highest_position_per_level[subgraph_level as usize],
+                            x: 0,
+                            y: highest_position_per_level[0],
                         },
                     )
                 } else {
                     self.reserve_spot_in_grid(
                         *idx,
                         GridCoord {
-                            x: highest_position_per_level[subgraph_level as usize],
-                            y: subgraph_level,
+                            x: highest_position_per_level[0],
+                            y: 0,
                         },
                     )
                 };
                 self.nodes[*idx].grid_coord = Some(coord);
-                highest_position_per_level[subgraph_level as usize] += 4;
+                highest_position_per_level[0] += 4;
+                self.place_subgraph_chain(*idx, &mut highest_position_per_level);
             }
-        }
 
-        for idx in 0..self.nodes.len() {
-            let grid_coord = self.nodes[idx].grid_coord.unwrap();
-            let child_level = if self.graph_direction == "LR" {
-                grid_coord.x + 4
-            } else {
-                grid_coord.y + 4
-            };
-            let mut highest_position = highest_position_per_level[child_level as usize];
-            let children = self.get_children(idx);
-            for child_idx in children {
-                if self.nodes[child_idx].grid_coord.is_some() {
-                    continue;
+            if should_separate && !subgraph_root_nodes.is_empty() {
+                let subgraph_level = 4;
+                for idx in subgraph_root_nodes
+                    .iter()
+                    .filter(|idx| component_of[**idx] == component)
+                {
+                    let coord = if self.graph_direction == "LR" {
+                        self.reserve_spot_in_grid(
+                            *idx,
+                            GridCoord {
+                                x: subgraph_level,
+                                y: highest_position_per_level[subgraph_level as usize],
+                            },
+                        )
+                    } else {
+                        self.reserve_spot_in_grid(
+                            *idx,
+                            GridCoord {
+                                x: highest_position_per_level[subgraph_level as usize],
+                                y: subgraph_level,
+                            },
+                        )
+                    };
+                    self.nodes[*idx].grid_coord = Some(coord);
+                    highest_position_per_level[subgraph_level as usize] += 4;
+                    self.place_subgraph_chain(*idx, &mut highest_position_per_level);
                 }
-                let coord = if self.graph_direction == "LR" {
-                    self.reserve_spot_in_grid(
-                        child_idx,
-                        GridCoord {
-                            x: child_level,
-                            y: highest_position,
-                        },
-                    )
+            }
+
+            // Children are placed from their parent, so keep visiting the
+            // earliest declared placed node until none is left; a node
+            // declared before its parent is picked up once it gets placed.
+            let mut ready: BinaryHeap<Reverse<usize>> = BinaryHeap::new();
+            for &idx in members {
+                if self.nodes[idx].grid_coord.is_some() {
+                    queued[idx] = true;
+                    ready.push(Reverse(idx));
+                }
+            }
+            let mut unplaced = members.iter().copied();
+            loop {
+                let next = match ready.pop() {
+                    Some(Reverse(idx)) => Some(idx),
+                    None => unplaced.find(|idx| self.nodes[*idx].grid_coord.is_none()),
+                };
+                let Some(idx) = next else {
+                    break;
+                };
+                queued[idx] = true;
+                let grid_coord = match self.nodes[idx].grid_coord {
+                    Some(coord) => coord,
+                    None => {
+                        // Only reachable through a cycle: start a new root.
+                        let requested = if self.graph_direction == "LR" {
+                            GridCoord {
+                                x: 0,
+                                y: highest_position_per_level[0],
+                            }
+                        } else {
+                            GridCoord {
+                                x: highest_position_per_level[0],
+                                y: 0,
+                            }
+                        };
+                        let coord = self.reserve_spot_in_grid(idx, requested);
+                        self.nodes[idx].grid_coord = Some(coord);
+                        highest_position_per_level[0] += 4;
+                        self.place_subgraph_chain(idx, &mut highest_position_per_level);
+                        coord
+                    }
+                };
+                let child_level = if self.graph_direction == "LR" {
+                    grid_coord.x + 4
                 } else {
-                    self.reserve_spot_in_grid(
-                        child_idx,
-                        GridCoord {
-                            x: highest_position,
-                            y: child_level,
-                        },
-                    )
+                    grid_coord.y + 4
                 };
-                self.nodes[child_idx].grid_coord = Some(coord);
-                highest_position_per_level[child_level as usize] = highest_position + 4;
-                highest_position = highest_position_per_level[child_level as usize];
+                let children = self.get_children(idx);
+                for child_idx in children {
+                    if self.nodes[child_idx].grid_coord.is_some() {
+                        continue;
+                    }
+                    // Longer links (`--->`) push the child further along the axis.
+                    let level = child_level + 4 * self.extra_length(idx, child_idx) as i32;
+                    if level as usize >= highest_position_per_level.len() {
+                        highest_position_per_level.resize(level as usize + 1, 0);
+                    }
+                    let highest_position = highest_position_per_level[level as usize];
+                    let coord = if self.graph_direction == "LR" {
+                        self.reserve_spot_in_grid(
+                            child_idx,
+                            GridCoord {
+                                x: level,
+                                y: highest_position,
+                            },
+                        )
+                    } else {
+                        self.reserve_spot_in_grid(
+                            child_idx,
+                            GridCoord {
+                                x: highest_position,
+                                y: level,
+                            },
+                        )
+                    };
+                    self.nodes[child_idx].grid_coord = Some(coord);
+                    highest_position_per_level[level as usize] = highest_position + 4;
+                    self.place_subgraph_chain(child_idx, &mut highest_position_per_level);
+                }
+
+                // Everything this step placed hangs off `idx`.
+                let mut stack = vec![idx];
+                while let Some(node) = stack.pop() {
+                    for child in self.get_children(node) {
+                        if !queued[child] && self.nodes[child].grid_coord.is_some() {
+                            queued[child] = true;
+                            ready.push(Reverse(child));
+                            stack.push(child);
+                        }
+                    }
+                }
             }
         }
 
         for idx in 0..self.nodes.len() {
             self.set_column_width(idx);
         }
+        if let Some(spacing) = self.component_spacing {
+            for gap in band_gaps {
+                if gap >= 0 {
+                    self.widen_band_gap(gap, spacing);
+                }
+            }
+        }
+        for edge_idx in 0..self.edges.len() {
+            self.reserve_skipped_ranks(edge_idx);
+        }
 
         for edge_idx in 0..self.edges.len() {
+            // Invisible links only influence placement; they get no route.
+            if self.edges[edge_idx].invisible {
+                continue;
+            }
             self.determine_path(edge_idx);
             let path = self.edges[edge_idx].path.clone();
             self.increase_grid_size_for_path(&path);
@@ -282,15 +701,116 @@ impl Graph {
         self.offset_drawing_for_subgraphs();
     }
 
+    /// Lays out the children `idx` reaches inside a subgraph with its own
+    /// `direction` right away, so the local chain claims its cells before
+    /// unrelated nodes of the same rank do.
+    fn place_subgraph_chain(&mut self, idx: usize, highest_position_per_level: &mut Vec<i32>) {
+        for child in self.get_children(idx) {
+            if self.nodes[child].grid_coord.is_none()
+                && self.place_in_subgraph_direction(idx, child, highest_position_per_level)
+            {
+                self.place_subgraph_chain(child, highest_position_per_level);
+            }
+        }
+    }
+
+    /// Places `child` beside `parent` along the `direction` of the innermost
+    /// subgraph holding both, when it crosses the graph's. Returns false,
+    /// leaving the child to the graph-wide ranks, otherwise.
+    fn place_in_subgraph_direction(
+        &mut self,
+        parent: usize,
+        child: usize,
+        highest_position_per_level: &mut Vec<i32>,
+    ) -> bool {
+        let Some(direction) = self
+            .subgraphs
+            .iter()
+            .rev()
+            .find(|sg| {
+                sg.direction.is_some() && sg.nodes.contains(&parent) && sg.nodes.contains(&child)
+            })
+            .and_then(|sg| sg.direction.clone())
+        else {
+            return false;
+        };
+        if direction == self.graph_direction {
+            return false;
+        }
+        let from = self.nodes[parent].grid_coord.unwrap();
+        // The local axis is the graph's secondary one, so the next free
+        // spot is found by the usual collision walk.
+        let requested = if direction == "LR" {
+            GridCoord {
+                x: from.x + 4,
+                y: from.y,
+            }
+        } else {
+            GridCoord {
+                x: from.x,
+                y: from.y + 4,
+            }
+        };
+        let coord = self.reserve_spot_in_grid(child, requested);
+        self.nodes[child].grid_coord = Some(coord);
+        let (level, position) = if self.graph_direction == "LR" {
+            (coord.x, coord.y)
+        } else {
+            (coord.y, coord.x)
+        };
+        if level as usize >= highest_position_per_level.len() {
+            highest_position_per_level.resize(level as usize + 1, 0);
+        }
+        let highest = &mut highest_position_per_level[level as usize];
+        *highest = (*highest).max(position + 4);
+        true
+    }
+
+    /// The node label as drawn, cut down to `max_label_width` when set. The
+    /// node itself keeps the full label.
+    pub(crate) fn display_label<'a>(&self, label: &'a str) -> Cow<'a, str> {
+        match self.max_label_width {
+            Some(max_width) => Cow::Owned(truncate_label(label, max_width, self.use_ascii)),
+            None => Cow::Borrowed(label),
+        }
+    }
+
     pub(crate) fn set_column_width(&mut self, idx: usize) {
         let node = &self.nodes[idx];
         let grid_coord = node.grid_coord.unwrap();
-        let name_len = node.label.chars().count() as i32;
+        let label = self.display_label(&node.label);
+        let name_len = label_width(&label);
         let col1 = 1;
-        let col2 = 2 * self.box_border_padding + name_len;
+        let col2 = max(
+            2 * self.box_border_padding_x + name_len,
+            self.node_min_width,
+        );
         let col3 = 1;
-        let cols = [col1, col2, col3];
-        let rows = [1, 1 + 2 * self.box_border_padding, 1];
+        let mut cols = [col1, col2, col3];
+        let mut rows = [1, label_height(&label) + 2 * self.box_border_padding_y, 1];
+        // Markers are a single glyph with no label or padding around it.
+        // A start marker is only ever left, so it needs no border on its
+        // leading sides either.
+        if node.shape != NodeShape::Box {
+            cols[1] = 1;
+            rows[1] = 1;
+        }
+        if node.shape == NodeShape::Start {
+            cols[0] = 0;
+            rows[0] = 0;
+        }
+        // Compartments stack their lines with a divider between sections.
+        if !node.compartments.is_empty() {
+            let lines = node.compartments.iter().flatten();
+            cols[1] = max(
+                2 * self.box_border_padding_x
+                    + lines.map(|line| label_width(line)).max().unwrap_or(0),
+                self.node_min_width,
+            );
+            rows[1] = node.compartments.iter().map(Vec::len).sum::<usize>() as i32
+                + node.compartments.len() as i32
+                - 1;
+        }
 
         for (offset, col) in cols.iter().enumerate() {
             let x = grid_coord.x + offset as i32;
@@ -316,12 +836,43 @@ impl Graph {
         }
     }
 
+    /// Sets the blank row (LR) or column (TD) between two component bands to
+    /// `spacing` cells.
+    fn widen_band_gap(&mut self, gap: i32, spacing: i32) {
+        if self.graph_direction == "LR" {
+            self.row_height.insert(gap, spacing);
+        } else {
+            self.column_width.insert(gap, spacing);
+        }
+    }
+
+    /// Gives the empty ranks skipped by a longer link some width, otherwise
+    /// they would collapse and the hint would have no visible effect.
+    fn reserve_skipped_ranks(&mut self, edge_idx: usize) {
+        let edge = &self.edges[edge_idx];
+        if edge.extra_length == 0 {
+            return;
+        }
+        let from = self.nodes[edge.from].grid_coord.unwrap();
+        let to = self.nodes[edge.to].grid_coord.unwrap();
+        let skipped: Vec<GridCoord> = if self.graph_direction == "LR" {
+            (from.x + 3..to.x)
+                .map(|x| GridCoord { x, y: from.y })
+                .collect()
+        } else {
+            (from.y + 3..to.y)
+                .map(|y| GridCoord { x: from.x, y })
+                .collect()
+        };
+        self.increase_grid_size_for_path(&skipped);
+    }
+
     pub(crate) fn increase_grid_size_for_path(&mut self, path: &[GridCoord]) {
+        let lane_x = self.edge_lane_padding.unwrap_or(self.padding_x / 2);
+        let lane_y = self.edge_lane_padding.unwrap_or(self.padding_y / 2);
         for coord in path {
-            self.column_width
-                .entry(coord.x)
-                .or_insert(self.padding_x / 2);
-            self.row_height.entry(coord.y).or_insert(self.padding_y / 2);
+            self.column_width.entry(coord.x).or_insert(lane_x);
+            self.row_height.entry(coord.y).or_insert(lane_y);
         }
     }
 
@@ -359,12 +910,29 @@ impl Graph {
         coord
     }
 
-    pub(crate) fn get_children(&self, node_idx: usize) -> Vec<usize> {
+    /// Longest length hint among the edges from `from` to `to`.
+    fn extra_length(&self, from: usize, to: usize) -> usize {
         self.edges
             .iter()
-            .filter(|edge| edge.from == node_idx)
-            .map(|edge| edge.to)
-            .collect()
+            .filter(|edge| edge.from == from && edge.to == to)
+            .map(|edge| edge.extra_length)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn get_children(&self, node_idx: usize) -> Vec<usize> {
+        self.children_index
+            .get(&node_idx)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn has_parent_outside_subgraph(&self, node_idx: usize, subgraph: usize) -> bool {
+        self.parents_index.get(&node_idx).is_some_and(|parents| {
+            parents
+                .iter()
+                .any(|parent| self.get_node_subgraph(*parent) != Some(subgraph))
+        })
     }
 
     pub(crate) fn grid_to_drawing_coord(
@@ -395,8 +963,22 @@ impl Graph {
     }
 
     pub(crate) fn determine_path(&mut self, edge_idx: usize) {
+        let edge = &self.edges[edge_idx];
+        if edge.from == edge.to
+            && let Some((path, start_dir, end_dir)) = self.self_loop_path(edge.from)
+        {
+            // Every lane cell needs width, not just the corners, or larger
+            // loops collapse onto the box.
+            self.increase_grid_size_for_path(&path_cells(&path));
+            self.edges[edge_idx].start_dir = start_dir;
+            self.edges[edge_idx].end_dir = end_dir;
+            self.edges[edge_idx].path = path;
+            return;
+        }
+
         let (preferred_dir, preferred_opp, alternative_dir, alternative_opp) =
             determine_start_and_end_dir(self.graph_direction.as_str(), &self.edges[edge_idx], self);
+        let avoid = self.parallel_edge_cells(edge_idx);
 
         let from = self.nodes[self.edges[edge_idx].from]
             .grid_coord
@@ -407,15 +989,7 @@ impl Graph {
             .unwrap()
             .direction(preferred_opp);
 
-        let preferred_path = match self.get_path(from, to) {
-            Ok(path) => merge_path(path),
-            Err(_) => {
-                self.edges[edge_idx].start_dir = alternative_dir;
-                self.edges[edge_idx].end_dir = alternative_opp;
-                self.edges[edge_idx].path = Vec::new();
-                return;
-            }
-        };
+        let preferred_path = self.get_path(from, to, &avoid).map(merge_path);
 
         let from_alt = self.nodes[self.edges[edge_idx].from]
             .grid_coord
@@ -425,43 +999,105 @@ impl Graph {
             .grid_coord
             .unwrap()
             .direction(alternative_opp);
+        let alternative_path = self.get_path(from_alt, to_alt, &avoid).map(merge_path);
 
-        let alternative_path = match self.get_path(from_alt, to_alt) {
-            Ok(path) => merge_path(path),
-            Err(_) => {
-                self.edges[edge_idx].start_dir = preferred_dir;
-                self.edges[edge_idx].end_dir = preferred_opp;
-                self.edges[edge_idx].path = preferred_path;
+        // Either search may give up at the path search limit; the edge is
+        // only left unrouted when both do.
+        let (start_dir, end_dir, path) = match (preferred_path, alternative_path) {
+            (Ok(preferred), Ok(alternative)) if alternative.len() < preferred.len() => {
+                (alternative_dir, alternative_opp, alternative)
+            }
+            (Ok(preferred), _) => (preferred_dir, preferred_opp, preferred),
+            (Err(_), Ok(alternative)) => (alternative_dir, alternative_opp, alternative),
+            (Err(_), Err(_)) => {
+                self.edges[edge_idx].start_dir = alternative_dir;
+                self.edges[edge_idx].end_dir = alternative_opp;
+                self.edges[edge_idx].path = Vec::new();
                 return;
             }
         };
+        self.edges[edge_idx].start_dir = start_dir;
+        self.edges[edge_idx].end_dir = end_dir;
+        self.edges[edge_idx].path = path;
 
-        if preferred_path.len() <= alternative_path.len() {
-            self.edges[edge_idx].start_dir = preferred_dir;
-            self.edges[edge_idx].end_dir = preferred_opp;
-            self.edges[edge_idx].path = preferred_path;
+        if !avoid.is_empty() {
+            self.route_around_parallel_edge(edge_idx, &avoid);
+        }
+    }
+
+    /// Cells already used by earlier edges joining the same two nodes.
+    fn parallel_edge_cells(&self, edge_idx: usize) -> HashSet<GridCoord> {
+        let edge = &self.edges[edge_idx];
+        self.edges[..edge_idx]
+            .iter()
+            .filter(|other| {
+                (other.from == edge.from && other.to == edge.to)
+                    || (other.from == edge.to && other.to == edge.from)
+            })
+            .flat_map(|other| path_cells(&other.path))
+            .collect()
+    }
+
+    /// When both candidate routes still overlap an earlier parallel edge
+    /// (e.g. two labelled edges between neighbours), leave and enter through
+    /// the side perpendicular to the flow instead.
+    fn route_around_parallel_edge(&mut self, edge_idx: usize, avoid: &HashSet<GridCoord>) {
+        let overlap = |path: &[GridCoord]| {
+            path_cells(path)
+                .iter()
+                .filter(|cell| avoid.contains(cell))
+                .count()
+        };
+        let current = overlap(&self.edges[edge_idx].path);
+        if current == 0 {
+            return;
+        }
+        let side = if self.graph_direction == "LR" {
+            DOWN
         } else {
-            self.edges[edge_idx].start_dir = alternative_dir;
-            self.edges[edge_idx].end_dir = alternative_opp;
-            self.edges[edge_idx].path = alternative_path;
+            RIGHT
+        };
+        let edge = &self.edges[edge_idx];
+        let from = self.nodes[edge.from].grid_coord.unwrap().direction(side);
+        let to = self.nodes[edge.to].grid_coord.unwrap().direction(side);
+        if let Ok(path) = self.get_path(from, to, avoid) {
+            let path = merge_path(path);
+            if overlap(&path) < current {
+                self.edges[edge_idx].start_dir = side;
+                self.edges[edge_idx].end_dir = side;
+                self.edges[edge_idx].path = path;
+            }
         }
     }
 
     pub(crate) fn determine_label_line(&mut self, edge_idx: usize) {
-        let label_len = self.edges[edge_idx].text.chars().count() as i32;
+        let label_len = label_width(&self.edges[edge_idx].text);
         if label_len == 0 {
             return;
         }
-        let path = self.edges[edge_idx].path.clone();
+        let mut path = self.edges[edge_idx].path.clone();
         if path.len() < 2 {
             return;
         }
+        // An end label looks for room starting from the arrowhead.
+        let from_end = self.edge_label_position == EdgeLabelPosition::End;
+        if from_end {
+            path.reverse();
+        }
         let mut prev_step = path[0];
         let mut largest_line = vec![prev_step, path[1]];
         let mut largest_line_size = 0;
         for step in path.iter().skip(1) {
             let line = vec![prev_step, *step];
-            let line_width = self.calculate_line_width(&line);
+            // A one-column hop out of a node box has its middle inside the
+            // box border column; widening that column detaches the border.
+            let leaves_box = (line[0].x - line[1].x).abs() == 1
+                && line.iter().any(|coord| !self.is_free_in_grid(*coord));
+            let line_width = if leaves_box && path.len() > 2 {
+                0
+            } else {
+                self.calculate_line_width(&line)
+            };
             if line_width >= label_len {
                 largest_line = line;
                 break;
@@ -477,12 +1113,59 @@ impl Graph {
         } else {
             (largest_line[1].x, largest_line[0].x)
         };
-        let middle_x = min_x + (max_x - min_x) / 2;
+        let mut middle_x = match self.edge_label_position {
+            EdgeLabelPosition::Middle => min_x + (max_x - min_x) / 2,
+            // The first free column next to the node the label sits by.
+            _ => (min_x..=max_x)
+                .filter(|&x| !self.is_box_column(x))
+                .min_by_key(|&x| (x - largest_line[0].x).abs())
+                .unwrap_or(min_x + (max_x - min_x) / 2),
+        };
+        if self.is_box_border_column(middle_x)
+            && let Some(x) = (min_x..=max_x)
+                .filter(|&x| !self.is_box_border_column(x))
+                .min_by_key(|&x| (self.is_box_column(x), (x - middle_x).abs()))
+        {
+            middle_x = x;
+        }
+        // ER ends take two cells at each end of a horizontal line, plus a
+        // gap so they do not run into the label.
+        let ends = if self.edges[edge_idx].cardinality.is_some()
+            && largest_line[0].y == largest_line[1].y
+        {
+            6
+        } else {
+            0
+        };
         let entry = self.column_width.entry(middle_x).or_insert(0);
-        *entry = max(*entry, label_len + 2);
+        *entry = max(*entry, label_len + 2 + ends);
+        if largest_line[0].y == largest_line[1].y {
+            let rows = label_height(&self.edges[edge_idx].text);
+            let entry = self.row_height.entry(largest_line[0].y).or_insert(0);
+            *entry = max(*entry, rows);
+        }
+        if from_end {
+            largest_line.reverse();
+        }
         self.edges[edge_idx].label_line = largest_line;
     }
 
+    /// Whether some node draws its left or right border in column `x`; those
+    /// columns must stay one cell wide.
+    fn is_box_border_column(&self, x: i32) -> bool {
+        self.nodes.iter().any(|node| {
+            node.grid_coord
+                .is_some_and(|coord| coord.x == x || coord.x + 2 == x)
+        })
+    }
+
+    fn is_box_column(&self, x: i32) -> bool {
+        self.nodes.iter().any(|node| {
+            node.grid_coord
+                .is_some_and(|coord| (coord.x..=coord.x + 2).contains(&x))
+        })
+    }
+
     pub(crate) fn calculate_line_width(&self, line: &[GridCoord]) -> i32 {
         line.iter()
             .map(|c| *self.column_width.get(&c.x).unwrap_or(&0))
@@ -619,15 +1302,13 @@ impl Graph {
     }
 
     pub(crate) fn is_node_in_any_subgraph(&self, node_idx: usize) -> bool {
-        self.subgraphs
-            .iter()
-            .any(|sg| sg.nodes.iter().any(|idx| *idx == node_idx))
+        self.subgraphs.iter().any(|sg| sg.nodes.contains(&node_idx))
     }
 
     pub(crate) fn get_node_subgraph(&self, node_idx: usize) -> Option<usize> {
         self.subgraphs
             .iter()
-            .position(|sg| sg.nodes.iter().any(|idx| *idx == node_idx))
+            .position(|sg| sg.nodes.contains(&node_idx))
     }
 
     pub(crate) fn has_incoming_edge_from_outside_subgraph(&self, node_idx: usize) -> bool {
@@ -636,17 +1317,7 @@ impl Graph {
             None => return false,
         };
 
-        let mut has_external_edge = false;
-        for edge in &self.edges {
-            if edge.to == node_idx {
-                let source_subgraph = self.get_node_subgraph(edge.from);
-                if source_subgraph != Some(node_subgraph) {
-                    has_external_edge = true;
-                    break;
-                }
-            }
-        }
-        if !has_external_edge {
+        if !self.has_parent_outside_subgraph(node_idx, node_subgraph) {
             return false;
         }
 
@@ -658,24 +1329,12 @@ impl Graph {
             if other_coord.is_none() {
                 continue;
             }
-            let mut other_has_external = false;
-            for edge in &self.edges {
-                if edge.to == *other {
-                    let source_subgraph = self.get_node_subgraph(edge.from);
-                    if source_subgraph != Some(node_subgraph) {
-                        other_has_external = true;
-                        break;
-                    }
-                }
-            }
-            if other_has_external {
-                if let (Some(other_coord), Some(node_coord)) =
+            if self.has_parent_outside_subgraph(*other, node_subgraph)
+                && let (Some(other_coord), Some(node_coord)) =
                     (other_coord, self.nodes[node_idx].grid_coord)
-                {
-                    if other_coord.y < node_coord.y {
-                        return false;
-                    }
-                }
+                && other_coord.y < node_coord.y
+            {
+                return false;
             }
         }
 
@@ -689,20 +1348,26 @@ impl Graph {
         increase_size(drawing, max_x - 1, max_y - 1);
     }
 
+    /// A* search between two grid cells. Cells in `avoid` stay passable but
+    /// cost extra, so routes only share them when there is no way around.
     pub(crate) fn get_path(
         &self,
         from: GridCoord,
         to: GridCoord,
+        avoid: &HashSet<GridCoord>,
     ) -> Result<Vec<GridCoord>, String> {
+        // Search states carry the heading so bends can be charged for.
+        type State = (GridCoord, Option<usize>);
         let mut pq = BinaryHeap::new();
         pq.push(QueueItem {
             coord: from,
+            heading: None,
             priority: 0,
         });
-        let mut cost_so_far: HashMap<GridCoord, i32> = HashMap::new();
-        let mut came_from: HashMap<GridCoord, Option<GridCoord>> = HashMap::new();
-        cost_so_far.insert(from, 0);
-        came_from.insert(from, None);
+        let mut cost_so_far: HashMap<State, i32> = HashMap::new();
+        let mut came_from: HashMap<State, Option<State>> = HashMap::new();
+        cost_so_far.insert((from, None), 0);
+        came_from.insert((from, None), None);
 
         let directions = [
             GridCoord { x: 1, y: 0 },
@@ -711,34 +1376,56 @@ impl Graph {
             GridCoord { x: 0, y: -1 },
         ];
 
-        while let Some(current) = pq.pop().map(|item| item.coord) {
-            if current.equals(to) {
+        // The grid is unbounded to the right and below, so an unreachable
+        // target would otherwise be searched for forever.
+        let limit = self
+            .path_search_limit
+            .unwrap_or_else(|| self.default_path_search_limit(from, to));
+        let mut expanded = 0;
+        while let Some(item) = pq.pop() {
+            expanded += 1;
+            if expanded > limit {
+                break;
+            }
+            let current = (item.coord, item.heading);
+            if item.coord.equals(to) {
                 let mut path = Vec::new();
-                let mut c = Some(current);
-                while let Some(coord) = c {
-                    path.insert(0, coord);
-                    c = came_from.get(&coord).and_then(|v| *v);
+                let mut state = Some(current);
+                while let Some(s) = state {
+                    path.insert(0, s.0);
+                    state = came_from.get(&s).and_then(|v| *v);
                 }
                 return Ok(path);
             }
 
-            for dir in &directions {
+            for (heading, dir) in directions.iter().enumerate() {
                 let next = GridCoord {
-                    x: current.x + dir.x,
-                    y: current.y + dir.y,
+                    x: item.coord.x + dir.x,
+                    y: item.coord.y + dir.y,
                 };
                 if !self.is_free_in_grid(next) && !next.equals(to) {
                     continue;
                 }
-                let new_cost = cost_so_far.get(&current).unwrap_or(&0) + 1;
-                if !cost_so_far.contains_key(&next) || new_cost < *cost_so_far.get(&next).unwrap() {
-                    cost_so_far.insert(next, new_cost);
-                    let priority = new_cost + heuristic(next, to);
+                let mut step_cost = 1;
+                if avoid.contains(&next) {
+                    step_cost += PARALLEL_EDGE_PENALTY;
+                }
+                if item.heading.is_some_and(|h| h != heading) {
+                    step_cost += TURN_PENALTY;
+                }
+                if !next.equals(to) && self.is_beside_node(next) {
+                    step_cost += NODE_ADJACENT_PENALTY;
+                }
+                let state = (next, Some(heading));
+                let new_cost = cost_so_far[&current] + step_cost;
+                if cost_so_far.get(&state).is_none_or(|cost| new_cost < *cost) {
+                    cost_so_far.insert(state, new_cost);
                     pq.push(QueueItem {
                         coord: next,
-                        priority,
+                        heading: Some(heading),
+                        priority: new_cost + heuristic(next, to),
                     });
-                    came_from.insert(next, Some(current));
+                    came_from.insert(state, Some(current));
                 }
             }
         }
@@ -746,6 +1433,94 @@ impl Graph {
         Err("no path found".to_string())
     }
 
+    /// Every heading of every cell in the occupied grid, widened by a margin
+    /// for routes that detour around its edge.
+    fn default_path_search_limit(&self, from: GridCoord, to: GridCoord) -> usize {
+        const MARGIN: i32 = 8;
+        let (max_x, max_y) = self
+            .grid
+            .keys()
+            .chain([&from, &to])
+            .fold((0, 0), |(x, y), coord| (x.max(coord.x), y.max(coord.y)));
+        ((max_x + 2 * MARGIN) * (max_y + 2 * MARGIN) * 4) as usize
+    }
+
+    /// Builds the rectangular loop for a self-referencing edge, `self_loop_size`
+    /// grid cells away from the box. Returns `None` when the loop would leave
+    /// the grid or cross another node, in which case the pathfinder routes it.
+    pub(crate) fn self_loop_path(
+        &self,
+        node_idx: usize,
+    ) -> Option<(Vec<GridCoord>, Direction, Direction)> {
+        let g = self.nodes[node_idx].grid_coord?;
+        let n = self.self_loop_size;
+        let at = |x: i32, y: i32| GridCoord {
+            x: g.x + x,
+            y: g.y + y,
+        };
+        let side = self
+            .self_loop_side
+            .unwrap_or(if self.graph_direction == "LR" {
+                SelfLoopSide::Right
+            } else {
+                SelfLoopSide::Bottom
+            });
+        let (path, start_dir, end_dir) = match side {
+            SelfLoopSide::Right => (
+                vec![
+                    at(2, 1),
+                    at(2 + n, 1),
+                    at(2 + n, 2 + n),
+                    at(1, 2 + n),
+                    at(1, 2),
+                ],
+                RIGHT,
+                DOWN,
+            ),
+            SelfLoopSide::Bottom => (
+                vec![
+                    at(1, 2),
+                    at(1, 2 + n),
+                    at(2 + n, 2 + n),
+                    at(2 + n, 1),
+                    at(2, 1),
+                ],
+                DOWN,
+                RIGHT,
+            ),
+            SelfLoopSide::Left => (
+                vec![at(0, 1), at(-n, 1), at(-n, -n), at(1, -n), at(1, 0)],
+                LEFT,
+                UP,
+            ),
+            SelfLoopSide::Top => (
+                vec![at(1, 0), at(1, -n), at(-n, -n), at(-n, 1), at(0, 1)],
+                UP,
+                LEFT,
+            ),
+        };
+
+        let end = path[path.len() - 1];
+        if path_cells(&path)
+            .iter()
+            .skip(1)
+            .any(|cell| !cell.equals(end) && !self.is_free_in_grid(*cell))
+        {
+            return None;
+        }
+        Some((path, start_dir, end_dir))
+    }
+
+    /// Whether a node occupies one of the four cells around `coord`.
+    fn is_beside_node(&self, coord: GridCoord) -> bool {
+        [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|(dx, dy)| {
+            self.grid.contains_key(&GridCoord {
+                x: coord.x + dx,
+                y: coord.y + dy,
+            })
+        })
+    }
+
     pub(crate) fn is_free_in_grid(&self, coord: GridCoord) -> bool {
         if coord.x < 0 || coord.y < 0 {
             return false;
@@ -753,3 +1528,31 @@ impl Graph {
         !self.grid.contains_key(&coord)
     }
 }
+
+/// Expands a path of corner points into every grid cell it passes through.
+fn path_cells(path: &[GridCoord]) -> Vec<GridCoord> {
+    let mut cells = path.first().copied().into_iter().collect::<Vec<_>>();
+    for pair in path.windows(2) {
+        let (dx, dy) = (
+            (pair[1].x - pair[0].x).signum(),
+            (pair[1].y - pair[0].y).signum(),
+        );
+        let mut cell = pair[0];
+        while !cell.equals(pair[1]) {
+            cell = GridCoord {
+                x: cell.x + dx,
+                y: cell.y + dy,
+            };
+            cells.push(cell);
+        }
+    }
+    cells
+}
+
+/// `①`..`⑳`, or `[n]` past twenty and with `use_ascii`.
+pub(crate) fn legend_marker(number: usize, use_ascii: bool) -> String {
+    match char::from_u32(0x245F + number as u32) {
+        Some(marker) if !use_ascii && number <= 20 => marker.to_string(),
+        _ => format!("[{}]", number),
+    }
+}
@@ -1,10 +1,17 @@
 mod draw;
+mod json;
+pub mod junctions;
 mod layout;
+mod model;
 mod parse;
+mod svg;
 mod types;
 
-use crate::diagram::{Config, Diagram};
-use types::GraphProperties;
+use crate::diagram::{Config, Diagram, OutputFormat, center_line};
+pub use model::{GraphModel, ModelEdge, ModelNode, ModelSubgraph, NodeClick, to_mermaid};
+pub(crate) use parse::direction_statement;
+use std::io::Write;
+pub(crate) use types::{Cardinality, EdgeEnd, GraphProperties, NodeShape};
 
 #[derive(Debug, Clone, Default)]
 pub struct GraphDiagram {
@@ -19,21 +26,208 @@ impl Diagram for GraphDiagram {
     }
 
     fn render(&self, config: &Config) -> Result<String, String> {
+        let properties = self.render_properties(config)?;
+        if config.output_format == OutputFormat::Svg {
+            return svg::draw_svg(&properties);
+        }
+        if config.output_format == OutputFormat::Json {
+            return json::layout_json(&properties);
+        }
+        Ok(render_text(properties, config).0)
+    }
+
+    fn diagram_type(&self) -> &'static str {
+        "graph"
+    }
+
+    fn render_to(&self, config: &Config, writer: &mut dyn Write) -> Result<(), String> {
+        if matches!(config.output_format, OutputFormat::Svg | OutputFormat::Json) {
+            let output = self.render(config)?;
+            return writer
+                .write_all(output.as_bytes())
+                .map_err(|e| e.to_string());
+        }
+        let properties = self.render_properties(config)?;
+        let title = properties.title.clone();
+        let (drawing, _) = draw_text(properties, config);
+        write_text(&drawing, title.as_deref(), config, writer).map_err(|e| e.to_string())
+    }
+}
+
+/// Parses flowchart source into a [`GraphModel`] using the default layout
+/// settings.
+pub fn parse(input: &str) -> Result<GraphModel, String> {
+    let properties = parse::mermaid_to_graph_properties(input, "cli", &Config::default_config())?;
+    Ok(GraphModel::from_properties(&properties))
+}
+
+/// Where a node's box landed in rendered text: zero-based `row`/`col` of its
+/// top-left corner plus its size, all in terminal cells.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeRect {
+    pub id: String,
+    pub row: usize,
+    pub col: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Layout internals for diagnosing placement bugs: the size in cells of
+/// every grid column and row, and where each node landed.
+#[cfg(feature = "debug")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutReport {
+    pub column_width: std::collections::BTreeMap<i32, i32>,
+    pub row_height: std::collections::BTreeMap<i32, i32>,
+    pub nodes: Vec<NodeLayout>,
+}
+
+/// A node's top-left grid cell and the drawing position of its box, both as
+/// `(x, y)`.
+#[cfg(feature = "debug")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeLayout {
+    pub id: String,
+    pub grid: (i32, i32),
+    pub drawing: (i32, i32),
+}
+
+impl GraphDiagram {
+    /// Wraps properties built by another front end, such as state diagrams.
+    pub(crate) fn from_properties(properties: GraphProperties) -> GraphDiagram {
+        GraphDiagram {
+            properties: Some(properties),
+        }
+    }
+
+    /// Renders as text and reports every node's bounding rectangle in that
+    /// text, for hit-testing. Coordinate rulers (`show_coords`) are never
+    /// drawn here since they would shift the rectangles.
+    pub fn render_with_node_map(&self, config: &Config) -> Result<(String, Vec<NodeRect>), String> {
+        let mut config = config.clone();
+        config.show_coords = false;
+        let properties = self.render_properties(&config)?;
+        let title_rows = if properties.title.is_some() { 2 } else { 0 };
+        let top = title_rows + config.margin_top.max(0) as usize;
+        let left = config.margin_left.max(0) as usize;
+        let (output, graph) = render_text(properties, &config);
+        let rects = graph
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                let coord = node.drawing_coord?;
+                let (max_x, max_y) = draw::get_drawing_size(node.drawing.as_ref()?);
+                Some(NodeRect {
+                    id: node.name.clone(),
+                    row: coord.y.max(0) as usize + top,
+                    col: coord.x.max(0) as usize + left,
+                    width: (max_x + 1) as usize,
+                    height: (max_y + 1) as usize,
+                })
+            })
+            .collect();
+        Ok((output, rects))
+    }
+
+    /// Lays the graph out as `render` would and reports the grid behind it.
+    #[cfg(feature = "debug")]
+    pub fn layout_report(&self, config: &Config) -> Result<LayoutReport, String> {
+        let properties = self.render_properties(config)?;
+        let (_, graph) = draw_text(properties, config);
+        Ok(LayoutReport {
+            column_width: graph.column_width.into_iter().collect(),
+            row_height: graph.row_height.into_iter().collect(),
+            nodes: graph
+                .nodes
+                .iter()
+                .filter_map(|node| {
+                    let grid = node.grid_coord?;
+                    let drawing = node.drawing_coord?;
+                    Some(NodeLayout {
+                        id: node.name.clone(),
+                        grid: (grid.x, grid.y),
+                        drawing: (drawing.x, drawing.y),
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    fn render_properties(&self, config: &Config) -> Result<GraphProperties, String> {
         let mut properties = self
             .properties
             .clone()
             .ok_or_else(|| "graph diagram not parsed: call parse() before render()".to_string())?;
-        let style_type = if config.style_type.is_empty() {
+        properties.style_type = if config.style_type.is_empty() {
             "cli".to_string()
         } else {
             config.style_type.clone()
         };
-        properties.style_type = style_type;
-        properties.use_ascii = config.use_ascii;
-        draw::draw_map(&properties, config.show_coords)
+        properties.use_ascii = config.ascii_output();
+        properties.charset = config.charset;
+        properties.labels_as_legend = config.labels_as_legend
+            && !matches!(config.output_format, OutputFormat::Svg | OutputFormat::Json);
+        Ok(properties)
     }
+}
 
-    fn diagram_type(&self) -> &'static str {
-        "graph"
+fn render_text(properties: GraphProperties, config: &Config) -> (String, types::Graph) {
+    let title = properties.title.clone();
+    let (drawing, graph) = draw_text(properties, config);
+    let mut output = Vec::new();
+    write_text(&drawing, title.as_deref(), config, &mut output)
+        .expect("writing to a Vec cannot fail");
+    (
+        String::from_utf8(output).expect("drawing cells are valid UTF-8"),
+        graph,
+    )
+}
+
+fn draw_text(mut properties: GraphProperties, config: &Config) -> (types::Drawing, types::Graph) {
+    if config.auto_direction
+        && let Some(max_width) = config.max_width
+    {
+        properties.graph_direction = layout::fitting_direction(&properties, max_width);
+    }
+    let mut rendered = draw::draw_map(&properties, config.show_coords);
+    if let Some(max_width) = config.max_width {
+        // Wide LR layouts usually fit once stacked top-down instead. This
+        // runs after auto_direction's estimate, so it also catches LR graphs
+        // the estimate wrongly judged to fit.
+        let width = draw::drawing_width(&rendered.0);
+        if width > max_width && properties.graph_direction == "LR" {
+            properties.graph_direction = "TD".to_string();
+            let stacked = draw::draw_map(&properties, config.show_coords);
+            if draw::drawing_width(&stacked.0) < width {
+                rendered = stacked;
+            }
+        }
+        let width = draw::drawing_width(&rendered.0);
+        if width > max_width {
+            eprintln!(
+                "warning: graph needs {} columns, exceeding --width {}",
+                width, max_width
+            );
+        }
+    }
+    rendered
+}
+
+/// Streams the drawing, preceded by the front-matter title when present and
+/// shifted by the configured margins.
+fn write_text(
+    drawing: &types::Drawing,
+    title: Option<&str>,
+    config: &Config,
+    writer: &mut dyn Write,
+) -> std::io::Result<()> {
+    let indent = " ".repeat(config.margin_left.max(0) as usize);
+    for _ in 0..config.margin_top {
+        writeln!(writer)?;
+    }
+    if let Some(title) = title {
+        let width = draw::drawing_width(drawing) as usize;
+        writeln!(writer, "{}{}\n", indent, center_line(title, width))?;
     }
+    draw::write_drawing(drawing, &indent, writer)
 }
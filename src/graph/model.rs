@@ -0,0 +1,329 @@
+use crate::graph::types::{EdgeEnd, GraphProperties, TextEdge};
+use std::collections::BTreeMap;
+
+/// A parsed flowchart without any layout: the nodes, edges, subgraphs and
+/// styles as written, ready to be inspected or written back out with
+/// [`to_mermaid`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphModel {
+    /// `TD` or `LR`.
+    pub direction: String,
+    /// Direction as written in the header: `TB`, `TD` or `LR`.
+    pub direction_keyword: String,
+    pub title: Option<String>,
+    /// In order of first appearance.
+    pub nodes: Vec<ModelNode>,
+    /// In source order, so `linkStyle` indices address them directly.
+    pub edges: Vec<ModelEdge>,
+    /// In source order; a subgraph always comes after its parent.
+    pub subgraphs: Vec<ModelSubgraph>,
+    pub class_defs: BTreeMap<String, BTreeMap<String, String>>,
+    pub node_styles: BTreeMap<String, BTreeMap<String, String>>,
+    pub link_styles: BTreeMap<usize, BTreeMap<String, String>>,
+    pub default_link_style: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelNode {
+    pub id: String,
+    pub label: String,
+    /// Style class attached with `:::` or a `class` statement.
+    pub class: Option<String>,
+    /// Interaction attached with `click`; kept but never drawn.
+    pub click: Option<NodeClick>,
+}
+
+/// A `click <node> ...` directive: either a link or a callback name, with an
+/// optional tooltip and link target.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeClick {
+    pub href: Option<String>,
+    /// Callback name, including its arguments when written with `call`.
+    pub callback: Option<String>,
+    pub tooltip: Option<String>,
+    /// Link target such as `_blank`.
+    pub target: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelEdge {
+    pub from: String,
+    pub to: String,
+    pub label: String,
+    /// Normalized link operator, such as `-->`, `---x` or `~~~`.
+    pub link: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelSubgraph {
+    pub name: String,
+    /// Nodes placed directly in this subgraph, excluding nested ones.
+    pub nodes: Vec<String>,
+    pub parent: Option<usize>,
+    /// Local `direction` (`TD` or `LR`), when the subgraph sets one.
+    pub direction: Option<String>,
+}
+
+impl GraphModel {
+    pub(crate) fn from_properties(properties: &GraphProperties) -> GraphModel {
+        let mut edges: Vec<&TextEdge> = properties.data.values().flatten().collect();
+        edges.sort_by_key(|edge| edge.index);
+
+        let mut classes: BTreeMap<&str, &str> = BTreeMap::new();
+        for edge in &edges {
+            for node in [&edge.parent, &edge.child] {
+                if !node.style_class.is_empty() {
+                    classes.insert(&node.name, &node.style_class);
+                }
+            }
+        }
+        for (node, class) in &properties.node_class_names {
+            classes.insert(node, class);
+        }
+
+        GraphModel {
+            direction: properties.graph_direction.clone(),
+            direction_keyword: properties.direction_keyword.clone(),
+            title: properties.title.clone(),
+            nodes: properties
+                .data
+                .keys()
+                .map(|id| ModelNode {
+                    id: id.clone(),
+                    label: properties
+                        .node_labels
+                        .get(id)
+                        .cloned()
+                        .unwrap_or_else(|| id.clone()),
+                    class: classes.get(id.as_str()).map(|class| class.to_string()),
+                    click: properties.node_clicks.get(id).cloned(),
+                })
+                .collect(),
+            edges: edges
+                .iter()
+                .map(|edge| ModelEdge {
+                    from: edge.parent.name.clone(),
+                    to: edge.child.name.clone(),
+                    label: edge.label.clone(),
+                    link: link_operator(edge),
+                })
+                .collect(),
+            subgraphs: properties
+                .subgraphs
+                .iter()
+                .map(|subgraph| ModelSubgraph {
+                    name: subgraph.name.clone(),
+                    nodes: subgraph
+                        .nodes
+                        .iter()
+                        .filter(|node| {
+                            !subgraph
+                                .children
+                                .iter()
+                                .any(|child| properties.subgraphs[*child].nodes.contains(node))
+                        })
+                        .cloned()
+                        .collect(),
+                    parent: subgraph.parent,
+                    direction: subgraph.direction.clone(),
+                })
+                .collect(),
+            class_defs: properties
+                .style_classes
+                .values()
+                .map(|class| (class.name.clone(), sorted(&class.styles)))
+                .collect(),
+            node_styles: properties
+                .node_styles
+                .iter()
+                .map(|(node, styles)| (node.clone(), sorted(styles)))
+                .collect(),
+            link_styles: properties
+                .link_styles
+                .iter()
+                .map(|(index, styles)| (*index, sorted(styles)))
+                .collect(),
+            default_link_style: sorted(&properties.default_link_style),
+        }
+    }
+}
+
+fn sorted(styles: &std::collections::HashMap<String, String>) -> BTreeMap<String, String> {
+    styles
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn link_operator(edge: &TextEdge) -> String {
+    if edge.invisible {
+        return "~".repeat(3 + edge.extra_length);
+    }
+    if edge.end == EdgeEnd::Open {
+        return "-".repeat(3 + edge.extra_length);
+    }
+    let marker = match edge.end {
+        EdgeEnd::Cross => "x",
+        EdgeEnd::Circle => "o",
+        _ => ">",
+    };
+    format!("{}{}", "-".repeat(2 + edge.extra_length), marker)
+}
+
+/// Serializes a model back into normalized Mermaid source: every node is
+/// declared up front, then the subgraphs, one edge per line, and finally
+/// the class definitions and styles.
+pub fn to_mermaid(model: &GraphModel) -> String {
+    let mut lines = Vec::new();
+    if let Some(title) = &model.title {
+        lines.push("---".to_string());
+        lines.push(format!("title: {}", title));
+        lines.push("---".to_string());
+    }
+    let keyword = if model.direction_keyword.is_empty() {
+        &model.direction
+    } else {
+        &model.direction_keyword
+    };
+    lines.push(format!("graph {}", keyword));
+
+    for node in &model.nodes {
+        if node.label == node.id {
+            lines.push(format!("    {}", quote_id(&node.id)));
+        } else {
+            lines.push(format!(
+                "    {}[{}]",
+                quote_id(&node.id),
+                quote_label(&node.label)
+            ));
+        }
+    }
+
+    for (idx, subgraph) in model.subgraphs.iter().enumerate() {
+        if subgraph.parent.is_none() {
+            write_subgraph(model, idx, 1, &mut lines);
+        }
+    }
+
+    // A node's class is attached where it is first connected.
+    let mut classes: BTreeMap<&str, &str> = model
+        .nodes
+        .iter()
+        .filter_map(|node| Some((node.id.as_str(), node.class.as_deref()?)))
+        .collect();
+    let mut endpoint = |id: &str| match classes.remove(id) {
+        Some(class) => format!("{}:::{}", quote_id(id), class),
+        None => quote_id(id),
+    };
+    for edge in &model.edges {
+        let from = endpoint(&edge.from);
+        let to = endpoint(&edge.to);
+        if edge.label.is_empty() || edge.link.starts_with('~') {
+            lines.push(format!("    {} {} {}", from, edge.link, to));
+        } else {
+            lines.push(format!(
+                "    {} {}|{}| {}",
+                from,
+                edge.link,
+                quote_label(&edge.label),
+                to
+            ));
+        }
+    }
+
+    for node in &model.nodes {
+        if let Some(click) = &node.click {
+            lines.push(format!("    click {}", click_directive(&node.id, click)));
+        }
+    }
+
+    for (name, styles) in &model.class_defs {
+        lines.push(format!("    classDef {} {}", name, style_list(styles)));
+    }
+    for (node, styles) in &model.node_styles {
+        lines.push(format!("    style {} {}", node, style_list(styles)));
+    }
+    if !model.default_link_style.is_empty() {
+        lines.push(format!(
+            "    linkStyle default {}",
+            style_list(&model.default_link_style)
+        ));
+    }
+    for (index, styles) in &model.link_styles {
+        lines.push(format!("    linkStyle {} {}", index, style_list(styles)));
+    }
+
+    format!("{}\n", lines.join("\n"))
+}
+
+fn click_directive(id: &str, click: &NodeClick) -> String {
+    let mut parts = vec![quote_id(id)];
+    if let Some(href) = &click.href {
+        parts.push(format!("href \"{}\"", href));
+    } else if let Some(callback) = &click.callback {
+        if callback.contains('(') {
+            parts.push(format!("call {}", callback));
+        } else {
+            parts.push(callback.clone());
+        }
+    }
+    if let Some(tooltip) = &click.tooltip {
+        parts.push(format!("\"{}\"", tooltip));
+    }
+    if let Some(target) = &click.target {
+        parts.push(target.clone());
+    }
+    parts.join(" ")
+}
+
+fn write_subgraph(model: &GraphModel, idx: usize, depth: usize, lines: &mut Vec<String>) {
+    let indent = "    ".repeat(depth);
+    let subgraph = &model.subgraphs[idx];
+    lines.push(format!("{}subgraph {}", indent, subgraph.name));
+    if let Some(direction) = &subgraph.direction {
+        lines.push(format!("{}    direction {}", indent, direction));
+    }
+    for node in &subgraph.nodes {
+        lines.push(format!("{}    {}", indent, quote_id(node)));
+    }
+    for (child, candidate) in model.subgraphs.iter().enumerate() {
+        if candidate.parent == Some(idx) {
+            write_subgraph(model, child, depth + 1, lines);
+        }
+    }
+    lines.push(format!("{}end", indent));
+}
+
+/// Ids with spaces or other syntax characters are written as `"My Node"`.
+fn quote_id(id: &str) -> String {
+    if id
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        id.to_string()
+    } else {
+        format!("\"{}\"", id)
+    }
+}
+
+/// Labels keep their line breaks as `<br>` and are quoted once they contain
+/// anything the parser would read as syntax.
+fn quote_label(label: &str) -> String {
+    let label = label.replace('\n', "<br>");
+    if label
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == ' ' || c == '_' || c == '-')
+    {
+        label
+    } else {
+        format!("\"{}\"", label)
+    }
+}
+
+fn style_list(styles: &BTreeMap<String, String>) -> String {
+    styles
+        .iter()
+        .map(|(key, value)| format!("{}:{}", key, value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
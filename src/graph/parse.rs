@@ -1,56 +1,97 @@
-use crate::diagram::Config;
-use crate::graph::types::{GraphProperties, StyleClass, TextEdge, TextNode, TextSubgraph};
+use crate::diagram::{Config, comment_start};
+use crate::graph::model::NodeClick;
+use crate::graph::types::{EdgeEnd, GraphProperties, StyleClass, TextEdge, TextNode, TextSubgraph};
 use indexmap::IndexMap;
+#[cfg(feature = "logging")]
 use log::debug;
 use regex::Regex;
 use std::collections::HashSet;
+use std::sync::LazyLock;
+
+/// Without the `logging` feature `debug!` compiles to nothing, though its
+/// arguments are still type-checked.
+#[cfg(not(feature = "logging"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+static NEWLINE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n|\\n").unwrap());
+static PADDING_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^padding([xy])\s*=\s*(\d+)$").unwrap());
+static SUBGRAPH_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*subgraph\s+(.+)$").unwrap());
+static DIRECTION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^direction\s+(LR|TB|TD)$").unwrap());
+static END_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*end\s*$").unwrap());
+// Spaces around the link are optional (`A-->B`), so the left side may not end
+// in a link character and the right side of a plain arrow may not open a
+// `|label|`.
+static ARROW_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(.*[^\s\-=~])\s*(-{2,}[>xo]|-{3,}|={2,}>|~{3,})\s*([^\s|].*)$").unwrap()
+});
+static LABEL_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(.*[^\s\-=~])\s*(-{2,}[>xo]|-{3,}|={2,}>)\s*\|\s*("[^"]*"|.+)\|\s*(.+)$"#)
+        .unwrap()
+});
+static CLASS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^classDef\s+(.+)\s+(.+)$").unwrap());
+static CLASS_ASSIGN_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^class\s+(.+?)\s+(\S+)$").unwrap());
+static STYLE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^style\s+(\S+)\s+(.+)$").unwrap());
+static LINK_STYLE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^linkStyle\s+(\S+)\s+(.+)$").unwrap());
+static CLICK_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^click\s+(\S+)\s+(.+)$").unwrap());
+static CLICK_ARGS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^(?:href\s+"([^"]*)"|"([^"]*)"|call\s+([^"\s(]+\((?:"[^"]*"|[^")])*\)|[^"\s(]+)|([^"\s]+))(?:\s+"([^"]*)")?(?:\s+(_\w+))?$"#,
+    )
+    .unwrap()
+});
+static AMP_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+) & (.+)$").unwrap());
+static NODE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(.+):::(.+)$").unwrap());
+static BR_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\s*<br\s*/?>\s*").unwrap());
 
 pub(crate) fn mermaid_to_graph_properties(
     mermaid: &str,
     style_type: &str,
     config: &Config,
 ) -> Result<GraphProperties, String> {
-    let newline_re = Regex::new(r"\n|\\n").unwrap();
-    let raw_lines: Vec<String> = newline_re.split(mermaid).map(|s| s.to_string()).collect();
+    let mut raw_lines: Vec<String> = NEWLINE_RE.split(mermaid).map(|s| s.to_string()).collect();
+    let front_matter = take_front_matter(&mut raw_lines)?;
 
     let mut lines: Vec<String> = Vec::new();
     for mut line in raw_lines {
-        if line == "---" {
+        if line == "---" && front_matter.is_none() {
             break;
         }
         let trimmed = line.trim();
         if trimmed.starts_with("%%") {
             continue;
         }
-        if let Some(idx) = line.find("%%") {
+        if let Some(idx) = comment_start(&line) {
             line = line[..idx].trim().to_string();
         }
-        if !line.trim().is_empty() {
-            lines.push(line);
+        for statement in split_statements(&line) {
+            if !statement.trim().is_empty() {
+                lines.push(statement.trim_end().to_string());
+            }
         }
     }
 
-    let mut properties = GraphProperties {
-        data: IndexMap::new(),
-        style_classes: std::collections::HashMap::new(),
-        node_labels: std::collections::HashMap::new(),
-        graph_direction: String::new(),
-        style_type: style_type.to_string(),
-        padding_x: config.padding_between_x,
-        padding_y: config.padding_between_y,
-        box_border_padding: config.box_border_padding,
-        subgraphs: Vec::new(),
-        use_ascii: config.use_ascii,
-    };
+    let mut properties = GraphProperties::new(style_type, config);
+    properties.title = front_matter.and_then(|fm| fm.title);
 
-    let padding_re = Regex::new(r"(?i)^padding([xy])\s*=\s*(\d+)$").unwrap();
     while !lines.is_empty() {
         let trimmed = lines[0].trim();
         if trimmed.is_empty() {
             lines.remove(0);
             continue;
         }
-        if let Some(caps) = padding_re.captures(trimmed) {
+        if let Some(caps) = PADDING_RE.captures(trimmed) {
             let axis = caps.get(1).map(|m| m.as_str()).unwrap_or("");
             let value: i32 = caps
                 .get(2)
@@ -85,16 +126,15 @@ pub(crate) fn mermaid_to_graph_properties(
             ));
         }
     }
+    properties.direction_keyword = lines[0].rsplit(' ').next().unwrap_or_default().to_string();
     lines.remove(0);
 
-    let subgraph_re = Regex::new(r"^\s*subgraph\s+(.+)$").unwrap();
-    let end_re = Regex::new(r"^\s*end\s*$").unwrap();
     let mut subgraph_stack: Vec<usize> = Vec::new();
 
     for line in lines {
         let trimmed = line.trim();
 
-        if let Some(caps) = subgraph_re.captures(trimmed) {
+        if let Some(caps) = SUBGRAPH_RE.captures(trimmed) {
             let name = caps.get(1).unwrap().as_str().trim().to_string();
             let parent = subgraph_stack.last().copied();
             let idx = properties.subgraphs.len();
@@ -103,6 +143,7 @@ pub(crate) fn mermaid_to_graph_properties(
                 nodes: Vec::new(),
                 parent,
                 children: Vec::new(),
+                direction: None,
             });
             if let Some(parent_idx) = parent {
                 properties.subgraphs[parent_idx].children.push(idx);
@@ -111,25 +152,50 @@ pub(crate) fn mermaid_to_graph_properties(
             continue;
         }
 
-        if end_re.is_match(trimmed) {
+        if let Some(direction) = direction_statement(trimmed)
+            && let Some(&idx) = subgraph_stack.last()
+        {
+            properties.subgraphs[idx].direction = Some(direction);
+            continue;
+        }
+
+        if END_RE.is_match(trimmed) {
             subgraph_stack.pop();
             continue;
         }
 
         let existing_nodes: HashSet<String> = properties.data.keys().cloned().collect();
+        let existing_edges = edge_count(&properties.data);
 
-        if let Ok(nodes) = properties.parse_string(&line) {
+        // Nodes named on a line without any edge are explicit membership
+        // listings and move into the enclosing subgraph even if defined earlier.
+        let mut listed: Vec<String> = Vec::new();
+        if let Some(nodes) = properties.parse_string(&line)? {
+            let is_listing = edge_count(&properties.data) == existing_edges;
             for node in nodes {
                 add_node(&node, &mut properties.data, &mut properties.node_labels);
+                if is_listing {
+                    listed.push(node.name.clone());
+                }
             }
         } else {
             let node = parse_node(&line);
             add_node(&node, &mut properties.data, &mut properties.node_labels);
+            listed.push(node.name.clone());
         }
 
         if !subgraph_stack.is_empty() {
+            for name in &listed {
+                if existing_nodes.contains(name) {
+                    for (idx, subgraph) in properties.subgraphs.iter_mut().enumerate() {
+                        if !subgraph_stack.contains(&idx) {
+                            subgraph.nodes.retain(|n| n != name);
+                        }
+                    }
+                }
+            }
             for key in properties.data.keys() {
-                if !existing_nodes.contains(key) {
+                if !existing_nodes.contains(key) || listed.contains(key) {
                     for idx in &subgraph_stack {
                         let subgraph = &mut properties.subgraphs[*idx];
                         if !subgraph.nodes.contains(key) {
@@ -141,89 +207,251 @@ pub(crate) fn mermaid_to_graph_properties(
         }
     }
 
+    if properties.data.is_empty() {
+        return Err("graph has no nodes".to_string());
+    }
+
+    let total_edges = edge_count(&properties.data);
+    if let Some(index) = properties
+        .link_styles
+        .keys()
+        .filter(|index| **index >= total_edges)
+        .min()
+    {
+        return Err(format!(
+            "linkStyle index {} is out of range: graph has {} edges",
+            index, total_edges
+        ));
+    }
+
     Ok(properties)
 }
 
 impl GraphProperties {
-    pub(crate) fn parse_string(&mut self, line: &str) -> Result<Vec<TextNode>, String> {
+    /// Empty properties carrying the layout settings from `config`.
+    pub(crate) fn new(style_type: &str, config: &Config) -> GraphProperties {
+        GraphProperties {
+            data: IndexMap::new(),
+            style_classes: std::collections::HashMap::new(),
+            node_styles: std::collections::HashMap::new(),
+            link_styles: std::collections::HashMap::new(),
+            default_link_style: std::collections::HashMap::new(),
+            node_labels: std::collections::HashMap::new(),
+            node_clicks: std::collections::HashMap::new(),
+            node_class_names: std::collections::HashMap::new(),
+            graph_direction: String::new(),
+            direction_keyword: String::new(),
+            style_type: style_type.to_string(),
+            padding_x: config.padding_between_x,
+            padding_y: config.padding_between_y,
+            box_border_padding_x: config
+                .box_border_padding_x
+                .unwrap_or(config.box_border_padding),
+            box_border_padding_y: config
+                .box_border_padding_y
+                .unwrap_or(config.box_border_padding),
+            subgraphs: Vec::new(),
+            use_ascii: config.use_ascii,
+            label_center_bias: config.label_center_bias,
+            ascii_corner_style: config.ascii_corner_style,
+            merge_bidirectional: config.merge_bidirectional,
+            edge_lane_padding: config.edge_lane_padding,
+            grid_overlay: config.grid_overlay,
+            self_loop_size: config.self_loop_size,
+            self_loop_side: config.self_loop_side,
+            title: None,
+            node_shapes: std::collections::HashMap::new(),
+            node_compartments: std::collections::HashMap::new(),
+            node_label_align: config.node_label_align,
+            fill_glyph: config.fill_glyph,
+            node_min_width: config.node_min_width,
+            charset: config.charset,
+            labels_as_legend: config.labels_as_legend,
+            arrow_glyphs: config.arrow_glyphs,
+            compact: config.compact,
+            component_spacing: config.component_spacing,
+            max_label_width: config.max_label_width,
+            edge_label_position: config.edge_label_position,
+            path_search_limit: config.path_search_limit,
+            deterministic_edges: config.deterministic_edges,
+        }
+    }
+
+    /// Declares a node without connecting it. A label equal to the name keeps
+    /// any label set earlier.
+    pub(crate) fn add_node(&mut self, name: &str, label: &str) {
+        let node = TextNode {
+            name: name.to_string(),
+            label: label.to_string(),
+            style_class: String::new(),
+        };
+        add_node(&node, &mut self.data, &mut self.node_labels);
+    }
+
+    /// Adds a plain arrow from `from` to `to`, declaring both nodes, and
+    /// returns the new edge for further adjustment.
+    pub(crate) fn add_edge(&mut self, from: &str, to: &str, label: &str) -> &mut TextEdge {
+        let node = |name: &str| TextNode {
+            name: name.to_string(),
+            label: name.to_string(),
+            style_class: String::new(),
+        };
+        set_arrow_with_label(
+            &[node(from)],
+            &[node(to)],
+            label,
+            Link::from_operator("-->"),
+            &mut self.data,
+            &mut self.node_labels,
+        );
+        self.data[from].last_mut().unwrap()
+    }
+
+    /// Parses one statement into the nodes it names, or `None` when the line
+    /// is no statement and should be read as a bare node. Malformed
+    /// statements such as a bad `click` are errors.
+    pub(crate) fn parse_string(&mut self, line: &str) -> Result<Option<Vec<TextNode>>, String> {
         debug!("Parsing line: {}", line);
         let line = line.trim();
 
         if line.is_empty() {
-            return Ok(Vec::new());
+            return Ok(Some(Vec::new()));
         }
 
-        let arrow_re = Regex::new(r"^(.+)\s+-->\s+(.+)$").unwrap();
-        let label_re = Regex::new(r"^(.+)\s+-->\|(.+)\|\s+(.+)$").unwrap();
-        let class_re = Regex::new(r"^classDef\s+(.+)\s+(.+)$").unwrap();
-        let amp_re = Regex::new(r"^(.+) & (.+)$").unwrap();
+        // Match on a copy with quoted, bracketed and piped text blanked out,
+        // so arrows and `&` inside ids and labels are not mistaken for syntax.
+        let masked = mask_text(line);
+        let group = |caps: &regex::Captures, idx: usize| &line[caps.get(idx).unwrap().range()];
 
-        if let Some(caps) = arrow_re.captures(line) {
-            let lhs = caps.get(1).unwrap().as_str();
-            let rhs = caps.get(2).unwrap().as_str();
+        if let Some(caps) = ARROW_RE.captures(&masked) {
+            let lhs = group(&caps, 1);
+            let link = Link::from_operator(group(&caps, 2));
+            let rhs = group(&caps, 3);
             let left_nodes = self
-                .parse_string(lhs)
-                .unwrap_or_else(|_| vec![parse_node(lhs)]);
+                .parse_string(lhs)?
+                .unwrap_or_else(|| vec![parse_node(lhs)]);
             let right_nodes = self
-                .parse_string(rhs)
-                .unwrap_or_else(|_| vec![parse_node(rhs)]);
-            return Ok(set_arrow(
+                .parse_string(rhs)?
+                .unwrap_or_else(|| vec![parse_node(rhs)]);
+            return Ok(Some(set_arrow(
                 &left_nodes,
                 &right_nodes,
+                link,
                 &mut self.data,
                 &mut self.node_labels,
-            ));
+            )));
         }
 
-        if let Some(caps) = label_re.captures(line) {
-            let lhs = caps.get(1).unwrap().as_str();
-            let label = caps.get(2).unwrap().as_str();
-            let rhs = caps.get(3).unwrap().as_str();
+        if let Some(caps) = LABEL_RE.captures(&masked) {
+            let lhs = group(&caps, 1);
+            let link = Link::from_operator(group(&caps, 2));
+            let label = split_label_breaks(unquote_label(group(&caps, 3)));
+            let rhs = group(&caps, 4);
             let left_nodes = self
-                .parse_string(lhs)
-                .unwrap_or_else(|_| vec![parse_node(lhs)]);
+                .parse_string(lhs)?
+                .unwrap_or_else(|| vec![parse_node(lhs)]);
             let right_nodes = self
-                .parse_string(rhs)
-                .unwrap_or_else(|_| vec![parse_node(rhs)]);
-            return Ok(set_arrow_with_label(
+                .parse_string(rhs)?
+                .unwrap_or_else(|| vec![parse_node(rhs)]);
+            return Ok(Some(set_arrow_with_label(
                 &left_nodes,
                 &right_nodes,
-                label,
+                &label,
+                link,
                 &mut self.data,
                 &mut self.node_labels,
-            ));
+            )));
         }
 
-        if let Some(caps) = class_re.captures(line) {
+        if let Some(caps) = CLASS_RE.captures(line) {
             let class_name = caps.get(1).unwrap().as_str();
             let styles = caps.get(2).unwrap().as_str();
             let class = parse_style_class(class_name, styles);
             self.style_classes.insert(class.name.clone(), class);
-            return Ok(Vec::new());
+            return Ok(Some(Vec::new()));
+        }
+
+        if let Some(caps) = CLASS_ASSIGN_RE.captures(line) {
+            let class_name = caps.get(2).unwrap().as_str();
+            for node_name in caps.get(1).unwrap().as_str().split(',') {
+                self.node_class_names
+                    .insert(node_name.trim().to_string(), class_name.to_string());
+            }
+            return Ok(Some(Vec::new()));
+        }
+
+        if let Some(caps) = LINK_STYLE_RE.captures(line) {
+            let target = caps.get(1).unwrap().as_str();
+            let styles = parse_style_class(target, caps.get(2).unwrap().as_str()).styles;
+            if target == "default" {
+                self.default_link_style.extend(styles);
+                return Ok(Some(Vec::new()));
+            }
+            for index in target.split(',') {
+                let index: usize = index
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid linkStyle index '{}'", index))?;
+                self.link_styles
+                    .entry(index)
+                    .or_default()
+                    .extend(styles.clone());
+            }
+            return Ok(Some(Vec::new()));
         }
 
-        if let Some(caps) = amp_re.captures(line) {
-            let lhs = caps.get(1).unwrap().as_str();
-            let rhs = caps.get(2).unwrap().as_str();
+        if let Some(caps) = CLICK_RE.captures(line) {
+            let node_name = unquote_id(caps.get(1).unwrap().as_str());
+            let args = caps.get(2).unwrap().as_str().trim();
+            let args = CLICK_ARGS_RE
+                .captures(args)
+                .ok_or_else(|| format!("invalid click directive: {}", line))?;
+            let text = |idx: usize| args.get(idx).map(|m| m.as_str().to_string());
+            let click = NodeClick {
+                href: text(1).or_else(|| text(2)),
+                callback: text(3).or_else(|| text(4)),
+                tooltip: text(5),
+                target: text(6),
+            };
+            self.node_clicks.insert(node_name.to_string(), click);
+            return Ok(Some(Vec::new()));
+        }
+
+        if let Some(caps) = STYLE_RE.captures(line) {
+            let node_name = caps.get(1).unwrap().as_str();
+            let styles = parse_style_class(node_name, caps.get(2).unwrap().as_str()).styles;
+            self.node_styles
+                .entry(node_name.to_string())
+                .or_default()
+                .extend(styles);
+            return Ok(Some(Vec::new()));
+        }
+
+        if let Some(caps) = AMP_RE.captures(&masked) {
+            let lhs = group(&caps, 1);
+            let rhs = group(&caps, 2);
             let left_nodes = self
-                .parse_string(lhs)
-                .unwrap_or_else(|_| vec![parse_node(lhs)]);
+                .parse_string(lhs)?
+                .unwrap_or_else(|| vec![parse_node(lhs)]);
             let right_nodes = self
-                .parse_string(rhs)
-                .unwrap_or_else(|_| vec![parse_node(rhs)]);
+                .parse_string(rhs)?
+                .unwrap_or_else(|| vec![parse_node(rhs)]);
             let mut merged = left_nodes;
             merged.extend(right_nodes);
-            return Ok(merged);
+            return Ok(Some(merged));
         }
 
-        Err(format!("could not parse line: {}", line))
+        Ok(None)
     }
 }
 
+fn edge_count(data: &IndexMap<String, Vec<TextEdge>>) -> usize {
+    data.values().map(|edges| edges.len()).sum()
+}
+
 fn parse_node(line: &str) -> TextNode {
     let trimmed = line.trim();
-    let node_re = Regex::new(r"^(.+):::(.+)$").unwrap();
-    if let Some(caps) = node_re.captures(trimmed) {
+    if let Some(caps) = NODE_RE.captures(trimmed) {
         let raw_name = caps.get(1).unwrap().as_str().trim();
         let (name, label) = parse_node_label(raw_name);
         TextNode {
@@ -243,7 +471,12 @@ fn parse_node(line: &str) -> TextNode {
 
 fn parse_node_label(input: &str) -> (String, String) {
     let trimmed = input.trim();
-    let mut chars = trimmed.char_indices();
+    // A quoted id such as `"My Node"` may itself contain spaces and brackets.
+    let id_end = trimmed
+        .strip_prefix('"')
+        .and_then(|rest| rest.find('"'))
+        .map_or(0, |idx| idx + 2);
+    let mut chars = trimmed.char_indices().skip_while(|(idx, _)| *idx < id_end);
     let split_idx = loop {
         match chars.next() {
             Some((idx, '[')) => break Some((idx, ']')),
@@ -256,10 +489,13 @@ fn parse_node_label(input: &str) -> (String, String) {
 
     let (start_idx, close_char) = match split_idx {
         Some(value) => value,
-        None => return (trimmed.to_string(), trimmed.to_string()),
+        None => {
+            let name = unquote_id(trimmed);
+            return (name.to_string(), name.to_string());
+        }
     };
 
-    let name = trimmed[..start_idx].trim();
+    let name = unquote_id(trimmed[..start_idx].trim());
     if name.is_empty() {
         return (trimmed.to_string(), trimmed.to_string());
     }
@@ -278,7 +514,106 @@ fn parse_node_label(input: &str) -> (String, String) {
     }
 
     let final_label = if label.is_empty() { name } else { label };
-    (name.to_string(), final_label.to_string())
+    (name.to_string(), split_label_breaks(final_label))
+}
+
+/// Strips the quotes from a `"My Node"` id; the inner text is both the key
+/// and the default label.
+fn unquote_id(id: &str) -> &str {
+    if id.len() >= 2 && id.starts_with('"') && id.ends_with('"') {
+        &id[1..id.len() - 1]
+    } else {
+        id
+    }
+}
+
+/// Splits `A --> B; B --> C` into its statements, dropping the `;`s. A `;`
+/// inside quotes, brackets or an `|edge label|` is kept as text.
+fn split_statements(line: &str) -> Vec<&str> {
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut piped = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            _ if quoted => {}
+            '[' | '(' | '{' => depth += 1,
+            ']' | ')' | '}' => depth -= 1,
+            '|' if depth == 0 => piped = !piped,
+            ';' if depth == 0 && !piped => {
+                statements.push(&line[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&line[start..]);
+    statements
+}
+
+/// Replaces every character between double quotes, inside brackets or
+/// inside an `|edge label|` with `x`, keeping byte offsets, so regex matches
+/// on the result can slice the original line.
+/// The layout direction set by a `direction LR|TB|TD` line, normalized to
+/// `LR` or `TD`.
+pub(crate) fn direction_statement(line: &str) -> Option<String> {
+    let caps = DIRECTION_RE.captures(line)?;
+    Some(if &caps[1] == "LR" { "LR" } else { "TD" }.to_string())
+}
+
+fn mask_text(line: &str) -> String {
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut piped = false;
+    line.chars()
+        .map(|c| {
+            let masked = match c {
+                '"' => {
+                    quoted = !quoted;
+                    false
+                }
+                _ if quoted => true,
+                '[' | '(' | '{' => {
+                    depth += 1;
+                    depth > 1 || piped
+                }
+                ']' | ')' | '}' => {
+                    // An unmatched closer, as in the `A>flag]` shape, must not
+                    // leave the rest of the line looking unbracketed.
+                    depth = (depth - 1).max(0);
+                    depth > 0 || piped
+                }
+                '|' if depth == 0 => {
+                    piped = !piped;
+                    false
+                }
+                _ => depth > 0 || piped,
+            };
+            if masked {
+                "x".repeat(c.len_utf8())
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Strips the quotes from a `"..."` edge label, keeping its inner text
+/// verbatim; unquoted labels are trimmed.
+fn unquote_label(label: &str) -> &str {
+    let trimmed = label.trim();
+    if trimmed.len() >= 2 && trimmed.starts_with('"') && trimmed.ends_with('"') {
+        &trimmed[1..trimmed.len() - 1]
+    } else {
+        trimmed
+    }
+}
+
+/// Converts `<br>`/`<br/>` tags into newlines so labels render on stacked rows.
+fn split_label_breaks(label: &str) -> String {
+    BR_RE.replace_all(label, "\n").into_owned()
 }
 
 fn parse_style_class(name: &str, styles: &str) -> StyleClass {
@@ -299,6 +634,7 @@ fn set_arrow_with_label(
     lhs: &[TextNode],
     rhs: &[TextNode],
     label: &str,
+    link: Link,
     data: &mut IndexMap<String, Vec<TextEdge>>,
     node_labels: &mut std::collections::HashMap<String, String>,
 ) -> Vec<TextNode> {
@@ -314,6 +650,12 @@ fn set_arrow_with_label(
                     parent: l.clone(),
                     child: r.clone(),
                     label: label.to_string(),
+                    index: edge_count(data),
+                    end: link.end,
+                    invisible: link.invisible,
+                    extra_length: link.extra_length,
+                    cardinality: None,
+                    tail: None,
                 },
                 data,
                 node_labels,
@@ -326,10 +668,85 @@ fn set_arrow_with_label(
 fn set_arrow(
     lhs: &[TextNode],
     rhs: &[TextNode],
+    link: Link,
     data: &mut IndexMap<String, Vec<TextEdge>>,
     node_labels: &mut std::collections::HashMap<String, String>,
 ) -> Vec<TextNode> {
-    set_arrow_with_label(lhs, rhs, "", data, node_labels)
+    set_arrow_with_label(lhs, rhs, "", link, data, node_labels)
+}
+
+/// How a link operator such as `-->`, `===>` or `~~~` should be laid out.
+#[derive(Clone, Copy)]
+struct Link {
+    end: EdgeEnd,
+    invisible: bool,
+    extra_length: usize,
+}
+
+impl Link {
+    fn from_operator(op: &str) -> Link {
+        if op.starts_with('~') {
+            return Link {
+                end: EdgeEnd::Arrow,
+                invisible: true,
+                extra_length: op.len() - 3,
+            };
+        }
+        // `---` is the shortest open link, with no arrowhead.
+        if op.ends_with('-') {
+            return Link {
+                end: EdgeEnd::Open,
+                invisible: false,
+                extra_length: op.len() - 3,
+            };
+        }
+        // The final character is the arrow marker; `-->` and `==>` are the
+        // shortest forms.
+        Link {
+            end: EdgeEnd::from_marker(&op[op.len() - 1..]),
+            invisible: false,
+            extra_length: op.len() - 3,
+        }
+    }
+}
+
+struct FrontMatter {
+    title: Option<String>,
+}
+
+/// Strips a leading `---` ... `---` block from `lines`, returning the
+/// recognized keys. Returns `None` when the input has no front-matter.
+fn take_front_matter(lines: &mut Vec<String>) -> Result<Option<FrontMatter>, String> {
+    let Some(start) = lines.iter().position(|line| !line.trim().is_empty()) else {
+        return Ok(None);
+    };
+    if lines[start].trim() != "---" {
+        return Ok(None);
+    }
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.trim() == "---")
+        .map(|offset| start + 1 + offset)
+        .ok_or_else(|| "front-matter is missing its closing ---".to_string())?;
+
+    let mut front_matter = FrontMatter { title: None };
+    for line in &lines[start + 1..end] {
+        if let Some((key, value)) = line.split_once(':')
+            && key.trim() == "title"
+        {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            if !value.is_empty() {
+                front_matter.title = Some(value.to_string());
+            }
+        }
+    }
+    lines.drain(..=end);
+    Ok(Some(front_matter))
 }
 
 fn add_node(
@@ -361,11 +778,13 @@ fn set_data(
     register_label(&edge.child, node_labels);
 }
 
+/// Records a node's label. As in Mermaid, the first explicit label sticks:
+/// later references, bare or relabelled, leave it alone.
 fn register_label(node: &TextNode, node_labels: &mut std::collections::HashMap<String, String>) {
     let entry = node_labels
         .entry(node.name.clone())
         .or_insert_with(|| node.label.clone());
-    if node.label != node.name {
+    if *entry == node.name {
         *entry = node.label.clone();
     }
 }
@@ -0,0 +1,195 @@
+use crate::graph::draw::get_drawing_size;
+use crate::graph::layout::layout_graph;
+use crate::graph::types::{
+    Cardinality, DrawingCoord, EdgeEnd, Graph, GraphProperties, label_height,
+};
+
+const CELL_WIDTH: i32 = 8;
+const CELL_HEIGHT: i32 = 16;
+
+/// One `<marker>` per edge terminator, referenced by `marker_id`.
+const MARKER_DEFS: &str = concat!(
+    "<defs>",
+    "<marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto-start-reverse\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"black\"/></marker>",
+    "<marker id=\"cross\" viewBox=\"0 0 10 10\" refX=\"5\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto-start-reverse\"><path d=\"M 1 1 L 9 9 M 1 9 L 9 1\" stroke=\"black\" stroke-width=\"2\"/></marker>",
+    "<marker id=\"circle\" viewBox=\"0 0 10 10\" refX=\"9\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto-start-reverse\"><circle cx=\"5\" cy=\"5\" r=\"4\" fill=\"white\" stroke=\"black\"/></marker>",
+    "<marker id=\"triangle\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto-start-reverse\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"white\" stroke=\"black\"/></marker>",
+    "<marker id=\"diamond\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto-start-reverse\"><path d=\"M 0 5 L 5 1 L 10 5 L 5 9 z\" fill=\"white\" stroke=\"black\"/></marker>",
+    "<marker id=\"filled-diamond\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto-start-reverse\"><path d=\"M 0 5 L 5 1 L 10 5 L 5 9 z\" fill=\"black\"/></marker>",
+    "<marker id=\"one\" viewBox=\"0 0 20 10\" refX=\"20\" refY=\"5\" markerWidth=\"12\" markerHeight=\"6\" orient=\"auto-start-reverse\"><path d=\"M 14 0 L 14 10 M 8 0 L 8 10\" stroke=\"black\"/></marker>",
+    "<marker id=\"zero-one\" viewBox=\"0 0 20 10\" refX=\"20\" refY=\"5\" markerWidth=\"12\" markerHeight=\"6\" orient=\"auto-start-reverse\"><path d=\"M 14 0 L 14 10\" stroke=\"black\"/><circle cx=\"6\" cy=\"5\" r=\"3\" fill=\"white\" stroke=\"black\"/></marker>",
+    "<marker id=\"one-many\" viewBox=\"0 0 20 10\" refX=\"20\" refY=\"5\" markerWidth=\"12\" markerHeight=\"6\" orient=\"auto-start-reverse\"><path d=\"M 12 5 L 20 0 M 12 5 L 20 10 M 8 0 L 8 10\" stroke=\"black\"/></marker>",
+    "<marker id=\"zero-many\" viewBox=\"0 0 20 10\" refX=\"20\" refY=\"5\" markerWidth=\"12\" markerHeight=\"6\" orient=\"auto-start-reverse\"><path d=\"M 12 5 L 20 0 M 12 5 L 20 10\" stroke=\"black\"/><circle cx=\"6\" cy=\"5\" r=\"3\" fill=\"white\" stroke=\"black\"/></marker>",
+    "</defs>\n"
+);
+
+/// Renders the laid-out graph as SVG, scaling each drawing cell to an
+/// 8x16 pixel box so positions match the text renderer exactly.
+pub(crate) fn draw_svg(properties: &GraphProperties) -> Result<String, String> {
+    let graph = layout_graph(properties);
+    let (max_x, max_y) = get_drawing_size(&graph.drawing);
+    let width = (max_x + 1) * CELL_WIDTH;
+    let height = (max_y + 1) * CELL_HEIGHT;
+
+    let mut out = String::new();
+    out.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"monospace\" font-size=\"14\">\n",
+        width, height, width, height
+    ));
+    out.push_str(MARKER_DEFS);
+
+    for sg in &graph.subgraphs {
+        if sg.nodes.is_empty() {
+            continue;
+        }
+        out.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"gray\"/>\n",
+            cell_center_x(sg.min_x),
+            cell_center_y(sg.min_y),
+            (sg.max_x - sg.min_x) * CELL_WIDTH,
+            (sg.max_y - sg.min_y) * CELL_HEIGHT
+        ));
+        out.push_str(&text_element(
+            (sg.min_x + sg.max_x) * CELL_WIDTH / 2 + CELL_WIDTH / 2,
+            cell_center_y(sg.min_y + 1),
+            &sg.name,
+            None,
+        ));
+    }
+
+    for node in &graph.nodes {
+        let (Some(coord), Some(drawing)) = (node.drawing_coord, node.drawing.as_ref()) else {
+            continue;
+        };
+        let (w, h) = get_drawing_size(drawing);
+        let fill = node
+            .style_class
+            .styles
+            .get("fill")
+            .map(|s| s.as_str())
+            .unwrap_or("none");
+        out.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"black\"/>\n",
+            cell_center_x(coord.x),
+            cell_center_y(coord.y),
+            w * CELL_WIDTH,
+            h * CELL_HEIGHT,
+            escape(fill)
+        ));
+        let center_x = cell_center_x(coord.x) + w * CELL_WIDTH / 2;
+        let top = coord.y + h / 2 - (label_height(&node.label) - 1) / 2;
+        for (row, line) in node.label.split('\n').enumerate() {
+            out.push_str(&text_element(
+                center_x,
+                cell_center_y(top + row as i32),
+                line,
+                node.style_class.styles.get("color").map(|s| s.as_str()),
+            ));
+        }
+    }
+
+    for edge in &graph.edges {
+        if edge.path.is_empty() {
+            continue;
+        }
+        let points: Vec<String> = edge
+            .path
+            .iter()
+            .map(|coord| {
+                let dc = graph.grid_to_drawing_coord(*coord, None);
+                format!("{},{}", cell_center_x(dc.x), cell_center_y(dc.y))
+            })
+            .collect();
+        let (start, end) = match edge.cardinality {
+            Some([parent, child]) => (
+                Some(cardinality_marker_id(parent)),
+                Some(cardinality_marker_id(child)),
+            ),
+            None if edge.bidirectional => (marker_id(edge.end), marker_id(edge.end)),
+            None => (edge.tail.and_then(marker_id), marker_id(edge.end)),
+        };
+        let marker = |attr: &str, id: Option<&str>| {
+            id.map(|id| format!(" {}=\"url(#{})\"", attr, id))
+                .unwrap_or_default()
+        };
+        out.push_str(&format!(
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"black\"{}{}/>\n",
+            points.join(" "),
+            marker("marker-start", start),
+            marker("marker-end", end)
+        ));
+        if !edge.text.is_empty() && edge.label_line.len() >= 2 {
+            let middle = label_anchor(&graph, &edge.label_line);
+            let top = middle.y - (label_height(&edge.text) - 1) / 2;
+            for (row, line) in edge.text.split('\n').enumerate() {
+                out.push_str(&text_element(
+                    cell_center_x(middle.x),
+                    cell_center_y(top + row as i32),
+                    line,
+                    None,
+                ));
+            }
+        }
+    }
+
+    out.push_str("</svg>\n");
+    Ok(out)
+}
+
+/// The `<marker>` drawn for an edge terminator; open links get none.
+fn marker_id(end: EdgeEnd) -> Option<&'static str> {
+    match end {
+        EdgeEnd::Open => None,
+        EdgeEnd::Cross => Some("cross"),
+        EdgeEnd::Circle => Some("circle"),
+        EdgeEnd::Arrow => Some("arrow"),
+        EdgeEnd::Triangle => Some("triangle"),
+        EdgeEnd::Diamond => Some("diamond"),
+        EdgeEnd::FilledDiamond => Some("filled-diamond"),
+    }
+}
+
+/// The crow's-foot `<marker>` for one end of an ER relationship.
+fn cardinality_marker_id(cardinality: Cardinality) -> &'static str {
+    match (cardinality.optional, cardinality.many) {
+        (false, false) => "one",
+        (true, false) => "zero-one",
+        (false, true) => "one-many",
+        (true, true) => "zero-many",
+    }
+}
+
+fn label_anchor(graph: &Graph, line: &[crate::graph::types::GridCoord]) -> DrawingCoord {
+    let a = graph.grid_to_drawing_coord(line[0], None);
+    let b = graph.grid_to_drawing_coord(line[1], None);
+    DrawingCoord {
+        x: (a.x + b.x) / 2,
+        y: (a.y + b.y) / 2,
+    }
+}
+
+fn cell_center_x(x: i32) -> i32 {
+    x * CELL_WIDTH + CELL_WIDTH / 2
+}
+
+fn cell_center_y(y: i32) -> i32 {
+    y * CELL_HEIGHT + CELL_HEIGHT / 2
+}
+
+fn text_element(x: i32, y: i32, text: &str, color: Option<&str>) -> String {
+    let fill = color.unwrap_or("black");
+    format!(
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" dominant-baseline=\"middle\" fill=\"{}\">{}</text>\n",
+        x,
+        y,
+        escape(fill),
+        escape(text)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,7 +1,13 @@
+use crate::diagram::{
+    ArrowGlyphs, AsciiCornerStyle, CharSet, EdgeLabelPosition, LabelCenterBias, NodeLabelAlign,
+    SelfLoopSide,
+};
+use crate::graph::model::NodeClick;
 use indexmap::IndexMap;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Debug, Clone)]
 pub(crate) struct TextNode {
@@ -15,6 +21,51 @@ pub(crate) struct TextEdge {
     pub(crate) parent: TextNode,
     pub(crate) child: TextNode,
     pub(crate) label: String,
+    /// Position in source order, as addressed by `linkStyle`.
+    pub(crate) index: usize,
+    pub(crate) end: EdgeEnd,
+    /// `~~~` links constrain the layout but are never drawn.
+    pub(crate) invisible: bool,
+    /// Extra ranks requested by a longer link such as `--->`.
+    pub(crate) extra_length: usize,
+    /// ER relationship ends (parent side, child side), drawn instead of an
+    /// arrowhead.
+    pub(crate) cardinality: Option<[Cardinality; 2]>,
+    /// Terminator drawn where the edge leaves its source, such as the
+    /// triangle of `<|--`.
+    pub(crate) tail: Option<EdgeEnd>,
+}
+
+/// Terminator drawn where an edge meets its target: `-->`, `--x` or `--o`,
+/// plus the class diagram heads (`<|--`, `*--`, `o--`) and no head at all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum EdgeEnd {
+    #[default]
+    Arrow,
+    Cross,
+    Circle,
+    Open,
+    Triangle,
+    Diamond,
+    FilledDiamond,
+}
+
+impl EdgeEnd {
+    pub(crate) fn from_marker(marker: &str) -> EdgeEnd {
+        match marker {
+            "x" => EdgeEnd::Cross,
+            "o" => EdgeEnd::Circle,
+            _ => EdgeEnd::Arrow,
+        }
+    }
+}
+
+/// One end of an ER relationship: whether zero is allowed (`o`) and whether
+/// many are (the crow's foot `{`/`}`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Cardinality {
+    pub(crate) optional: bool,
+    pub(crate) many: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -23,20 +74,60 @@ pub(crate) struct TextSubgraph {
     pub(crate) nodes: Vec<String>,
     pub(crate) parent: Option<usize>,
     pub(crate) children: Vec<usize>,
+    /// `direction LR`/`TD` inside the block, overriding the graph's.
+    pub(crate) direction: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct GraphProperties {
     pub(crate) data: IndexMap<String, Vec<TextEdge>>,
     pub(crate) style_classes: HashMap<String, StyleClass>,
+    /// Inline `style <node> ...` declarations, applied over any class styles.
+    pub(crate) node_styles: HashMap<String, HashMap<String, String>>,
+    /// `linkStyle` declarations keyed by edge index in source order.
+    pub(crate) link_styles: HashMap<usize, HashMap<String, String>>,
+    pub(crate) default_link_style: HashMap<String, String>,
     pub(crate) node_labels: HashMap<String, String>,
+    /// `click` directives by node name; parsed for the model only.
+    pub(crate) node_clicks: HashMap<String, NodeClick>,
+    /// `class <nodes> <className>` assignments by node name.
+    pub(crate) node_class_names: HashMap<String, String>,
     pub(crate) graph_direction: String,
+    /// Direction as written in the header (`TB`, `TD` or `LR`), while
+    /// `graph_direction` holds the normalized `TD`/`LR` used for layout.
+    pub(crate) direction_keyword: String,
     pub(crate) style_type: String,
     pub(crate) padding_x: i32,
     pub(crate) padding_y: i32,
-    pub(crate) box_border_padding: i32,
+    pub(crate) box_border_padding_x: i32,
+    pub(crate) box_border_padding_y: i32,
     pub(crate) subgraphs: Vec<TextSubgraph>,
     pub(crate) use_ascii: bool,
+    pub(crate) label_center_bias: Option<LabelCenterBias>,
+    pub(crate) ascii_corner_style: AsciiCornerStyle,
+    pub(crate) merge_bidirectional: bool,
+    pub(crate) edge_lane_padding: Option<i32>,
+    pub(crate) grid_overlay: bool,
+    pub(crate) self_loop_size: i32,
+    pub(crate) self_loop_side: Option<SelfLoopSide>,
+    /// `title:` from a leading `---` front-matter block.
+    pub(crate) title: Option<String>,
+    /// Nodes drawn as something other than a box, keyed by name.
+    pub(crate) node_shapes: HashMap<String, NodeShape>,
+    /// Class diagram boxes split into sections (name, attributes, methods).
+    pub(crate) node_compartments: HashMap<String, Vec<Vec<String>>>,
+    pub(crate) node_label_align: NodeLabelAlign,
+    pub(crate) fill_glyph: Option<char>,
+    pub(crate) node_min_width: i32,
+    pub(crate) charset: CharSet,
+    pub(crate) labels_as_legend: bool,
+    pub(crate) arrow_glyphs: Option<ArrowGlyphs>,
+    pub(crate) compact: bool,
+    pub(crate) component_spacing: Option<i32>,
+    pub(crate) max_label_width: Option<usize>,
+    pub(crate) edge_label_position: EdgeLabelPosition,
+    pub(crate) path_search_limit: Option<usize>,
+    pub(crate) deterministic_edges: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -147,6 +238,18 @@ pub(crate) struct Node {
     pub(crate) index: usize,
     pub(crate) style_class_name: String,
     pub(crate) style_class: StyleClass,
+    pub(crate) shape: NodeShape,
+    pub(crate) compartments: Vec<Vec<String>>,
+}
+
+/// How a node is drawn. The markers are the filled start and end points of a
+/// state diagram, drawn as a single glyph instead of a labelled box.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum NodeShape {
+    #[default]
+    Box,
+    Start,
+    End,
 }
 
 #[derive(Debug, Clone)]
@@ -158,6 +261,14 @@ pub(crate) struct Edge {
     pub(crate) label_line: Vec<GridCoord>,
     pub(crate) start_dir: Direction,
     pub(crate) end_dir: Direction,
+    pub(crate) bidirectional: bool,
+    pub(crate) back_edge: bool,
+    pub(crate) style: HashMap<String, String>,
+    pub(crate) end: EdgeEnd,
+    pub(crate) invisible: bool,
+    pub(crate) extra_length: usize,
+    pub(crate) cardinality: Option<[Cardinality; 2]>,
+    pub(crate) tail: Option<EdgeEnd>,
 }
 
 #[derive(Debug, Clone)]
@@ -166,6 +277,7 @@ pub(crate) struct Subgraph {
     pub(crate) nodes: Vec<usize>,
     pub(crate) parent: Option<usize>,
     pub(crate) children: Vec<usize>,
+    pub(crate) direction: Option<String>,
     pub(crate) min_x: i32,
     pub(crate) min_y: i32,
     pub(crate) max_x: i32,
@@ -184,13 +296,36 @@ pub(crate) struct Graph {
     pub(crate) style_type: String,
     pub(crate) padding_x: i32,
     pub(crate) padding_y: i32,
-    pub(crate) box_border_padding: i32,
+    pub(crate) box_border_padding_x: i32,
+    pub(crate) box_border_padding_y: i32,
     pub(crate) subgraphs: Vec<Subgraph>,
     pub(crate) offset_x: i32,
     pub(crate) offset_y: i32,
     pub(crate) use_ascii: bool,
     pub(crate) graph_direction: String,
     pub(crate) node_index_by_name: HashMap<String, usize>,
+    pub(crate) children_index: HashMap<usize, Vec<usize>>,
+    pub(crate) parents_index: HashMap<usize, Vec<usize>>,
+    pub(crate) label_center_bias: Option<LabelCenterBias>,
+    pub(crate) ascii_corner_style: AsciiCornerStyle,
+    pub(crate) edge_lane_padding: Option<i32>,
+    pub(crate) self_loop_size: i32,
+    pub(crate) self_loop_side: Option<SelfLoopSide>,
+    pub(crate) node_label_align: NodeLabelAlign,
+    pub(crate) fill_glyph: Option<char>,
+    pub(crate) node_min_width: i32,
+    pub(crate) charset: CharSet,
+    pub(crate) labels_as_legend: bool,
+    /// Edge labels moved out of the drawing by `labels_as_legend`, in marker
+    /// order.
+    pub(crate) legend: Vec<String>,
+    pub(crate) arrow_glyphs: Option<ArrowGlyphs>,
+    pub(crate) compact: bool,
+    pub(crate) component_spacing: Option<i32>,
+    pub(crate) max_label_width: Option<usize>,
+    pub(crate) edge_label_position: EdgeLabelPosition,
+    pub(crate) path_search_limit: Option<usize>,
+    pub(crate) deterministic_edges: bool,
 }
 
 impl TextEdge {
@@ -202,6 +337,8 @@ impl TextEdge {
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub(crate) struct QueueItem {
     pub(crate) coord: GridCoord,
+    /// Index of the step direction that reached `coord`; `None` at the start.
+    pub(crate) heading: Option<usize>,
     pub(crate) priority: i32,
 }
 
@@ -402,6 +539,19 @@ pub(crate) fn determine_start_and_end_dir(
     (preferred_dir, preferred_opp, alt_dir, alt_opp)
 }
 
+/// Display width of the widest line of a (possibly multi-line) label.
+pub(crate) fn label_width(label: &str) -> i32 {
+    label
+        .split('\n')
+        .map(|line| UnicodeWidthStr::width(line) as i32)
+        .max()
+        .unwrap_or(0)
+}
+
+pub(crate) fn label_height(label: &str) -> i32 {
+    label.split('\n').count() as i32
+}
+
 pub(crate) fn min(x: i32, y: i32) -> i32 {
     if x < y { x } else { y }
 }
@@ -409,7 +559,3 @@ pub(crate) fn min(x: i32, y: i32) -> i32 {
 pub(crate) fn max(x: i32, y: i32) -> i32 {
     if x > y { x } else { y }
 }
-
-pub(crate) fn ceil_div(x: i32, y: i32) -> i32 {
-    if x % y == 0 { x / y } else { x / y + 1 }
-}
@@ -0,0 +1,163 @@
+use crate::diagram::{Config, Diagram, OutputFormat, apply_margins, remove_comments, split_lines};
+use regex::Regex;
+use std::sync::LazyLock;
+use unicode_width::UnicodeWidthStr;
+
+const JOURNEY_KEYWORD: &str = "journey";
+/// Scores run from 1 to 5, one bar cell each.
+const MAX_SCORE: u32 = 5;
+
+static TITLE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^title\s+(.+)$").unwrap());
+static SECTION_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^section\s+(.+)$").unwrap());
+static TASK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([^:]+?)\s*:\s*(\d+)\s*(?::\s*(.*))?$").unwrap());
+
+/// A user `journey`: each section lists its tasks with the score drawn as a
+/// bar and the actors taking part.
+#[derive(Debug, Clone, Default)]
+pub struct JourneyDiagram {
+    title: Option<String>,
+    sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Section {
+    /// Empty for tasks listed before the first `section`.
+    name: String,
+    tasks: Vec<Task>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Task {
+    name: String,
+    score: u32,
+    actors: Vec<String>,
+}
+
+pub fn is_journey(input: &str) -> bool {
+    for line in input.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("%%") {
+            continue;
+        }
+        return trimmed == JOURNEY_KEYWORD;
+    }
+    false
+}
+
+impl Diagram for JourneyDiagram {
+    fn parse(&mut self, input: &str, _config: &Config) -> Result<(), String> {
+        let lines = remove_comments(&split_lines(input.trim()));
+        let Some(header) = lines.first() else {
+            return Err("no content found".to_string());
+        };
+        if !is_journey(header) {
+            return Err(format!("expected \"{}\" keyword", JOURNEY_KEYWORD));
+        }
+
+        self.title = None;
+        self.sections.clear();
+        for (idx, line) in lines.iter().enumerate().skip(1) {
+            let trimmed = line.trim();
+            if let Some(caps) = TITLE_RE.captures(trimmed) {
+                self.title = Some(caps[1].trim().to_string());
+            } else if let Some(caps) = SECTION_RE.captures(trimmed) {
+                self.sections.push(Section {
+                    name: caps[1].trim().to_string(),
+                    tasks: Vec::new(),
+                });
+            } else if let Some(caps) = TASK_RE.captures(trimmed) {
+                let score = caps[2]
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|score| (1..=MAX_SCORE).contains(score))
+                    .ok_or_else(|| {
+                        format!(
+                            "line {}: score must be between 1 and {}: \"{}\"",
+                            idx + 1,
+                            MAX_SCORE,
+                            trimmed
+                        )
+                    })?;
+                let actors = caps
+                    .get(3)
+                    .map(|m| {
+                        m.as_str()
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|actor| !actor.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                if self.sections.is_empty() {
+                    self.sections.push(Section::default());
+                }
+                self.sections.last_mut().unwrap().tasks.push(Task {
+                    name: caps[1].to_string(),
+                    score,
+                    actors,
+                });
+            } else {
+                return Err(format!("line {}: invalid syntax: \"{}\"", idx + 1, trimmed));
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self, config: &Config) -> Result<String, String> {
+        if config.output_format == OutputFormat::Svg {
+            return Err("SVG output is only supported for graph diagrams".to_string());
+        }
+        if config.output_format == OutputFormat::Json {
+            return Err("JSON output is only supported for graph diagrams".to_string());
+        }
+        let (branch, last_branch, filled, empty) = if config.ascii_output() {
+            ("+- ", "\\- ", "#", ".")
+        } else {
+            ("├─ ", "└─ ", "█", "░")
+        };
+        let name_width = self
+            .sections
+            .iter()
+            .flat_map(|section| &section.tasks)
+            .map(|task| UnicodeWidthStr::width(task.name.as_str()))
+            .max()
+            .unwrap_or(0);
+
+        let mut lines = Vec::new();
+        if let Some(title) = &self.title {
+            lines.push(title.clone());
+            lines.push(String::new());
+        }
+        for section in &self.sections {
+            if !section.name.is_empty() {
+                lines.push(section.name.clone());
+            }
+            for (idx, task) in section.tasks.iter().enumerate() {
+                let pad = name_width - UnicodeWidthStr::width(task.name.as_str());
+                let line = format!(
+                    "{}{}{}  {}{} {}  {}",
+                    if idx + 1 == section.tasks.len() {
+                        last_branch
+                    } else {
+                        branch
+                    },
+                    task.name,
+                    " ".repeat(pad),
+                    filled.repeat(task.score as usize),
+                    empty.repeat((MAX_SCORE - task.score) as usize),
+                    task.score,
+                    task.actors.join(", ")
+                );
+                lines.push(line.trim_end().to_string());
+            }
+        }
+        apply_margins(&mut lines, config);
+        Ok(lines.join("\n"))
+    }
+
+    fn diagram_type(&self) -> &'static str {
+        "journey"
+    }
+}
@@ -1,9 +1,163 @@
+pub mod class;
 pub mod diagram;
+pub mod er;
 pub mod graph;
+pub mod journey;
+pub mod mindmap;
 pub mod sequence;
+pub mod state;
+
+/// Renders a graph diagram as text together with the position of every node
+/// box in that text. Only graph diagrams are supported.
+pub fn render_diagram_with_node_map(
+    input: &str,
+    config: &diagram::Config,
+) -> Result<(String, Vec<graph::NodeRect>), String> {
+    if diagram::detect_diagram_type(input) == Some("sequence") {
+        return Err("node maps are only supported for graph diagrams".to_string());
+    }
+    let input = diagram::expand_tabs(input, config.tab_width);
+    let mut diag = graph::GraphDiagram::default();
+    diagram::Diagram::parse(&mut diag, &input, config)?;
+    diag.render_with_node_map(config)
+}
+
+/// Lays out a graph diagram and reports its grid and node coordinates; the
+/// programmatic counterpart of `--coords`. Only graph diagrams are supported.
+#[cfg(feature = "debug")]
+pub fn layout_report(input: &str, config: &diagram::Config) -> Result<graph::LayoutReport, String> {
+    if diagram::detect_diagram_type(input) == Some("sequence") {
+        return Err("layout reports are only supported for graph diagrams".to_string());
+    }
+    let input = diagram::expand_tabs(input, config.tab_width);
+    let mut diag = graph::GraphDiagram::default();
+    diagram::Diagram::parse(&mut diag, &input, config)?;
+    diag.layout_report(config)
+}
+
+/// Parses `input` without rendering it, reporting the first parse error.
+pub fn check_diagram(input: &str, config: &diagram::Config) -> Result<(), String> {
+    let input = diagram::expand_tabs(input, config.tab_width);
+    let mut diag = diagram::diagram_factory(&input)?;
+    diag.parse(&input, config)
+}
 
 pub fn render_diagram(input: &str, config: &diagram::Config) -> Result<String, String> {
-    let mut diag = diagram::diagram_factory(input)?;
-    diag.parse(input, config)?;
-    diag.render(config)
+    let mut output = Vec::new();
+    render_diagram_to(input, config, &mut output)?;
+    String::from_utf8(output).map_err(|e| e.to_string())
+}
+
+/// Renders `input` as a grid of cells, `cells[row][col]`, for placing in a
+/// viewport: one row per output line, each padded with spaces to the width
+/// of the widest, and one cell per terminal column. A wide character is
+/// followed by a `'\0'` cell for the column it spills into; zero-width
+/// characters are dropped.
+pub fn render_to_cells(input: &str, config: &diagram::Config) -> Result<Vec<Vec<char>>, String> {
+    let output = render_diagram(input, config)?;
+    let mut cells: Vec<Vec<char>> = output
+        .trim_end_matches('\n')
+        .split('\n')
+        .map(|line| {
+            let mut row = Vec::new();
+            for ch in line.chars() {
+                let width = unicode_width::UnicodeWidthChar::width(ch).unwrap_or(0);
+                if width > 0 {
+                    row.push(ch);
+                    row.resize(row.len() + width - 1, '\0');
+                }
+            }
+            row
+        })
+        .collect();
+    let width = cells.iter().map(Vec::len).max().unwrap_or(0);
+    for row in &mut cells {
+        row.resize(width, ' ');
+    }
+    Ok(cells)
+}
+
+/// Renders every diagram in a document (see [`diagram::split_diagrams`]),
+/// separating the outputs with a blank line. Errors name the 1-based block.
+pub fn render_all(input: &str, config: &diagram::Config) -> Result<String, String> {
+    render_blocks(&diagram::split_diagrams(input), config)
+}
+
+/// Renders every fenced `mermaid` block of a Markdown document (see
+/// [`diagram::extract_mermaid_blocks`]) the way [`render_all`] renders
+/// diagrams.
+pub fn render_markdown(md: &str, config: &diagram::Config) -> Result<String, String> {
+    render_blocks(&diagram::extract_mermaid_blocks(md), config)
+}
+
+fn render_blocks(blocks: &[String], config: &diagram::Config) -> Result<String, String> {
+    let mut outputs = Vec::new();
+    for (idx, block) in blocks.iter().enumerate() {
+        let output =
+            render_diagram(block, config).map_err(|err| format!("diagram {}: {}", idx + 1, err))?;
+        outputs.push(output.trim_end_matches('\n').to_string());
+    }
+    if outputs.is_empty() {
+        return Err("no diagrams found".to_string());
+    }
+    Ok(outputs.join("\n\n"))
+}
+
+/// Renders straight into `writer`, emitting text output row by row instead
+/// of building the whole diagram as one `String` first. A caption needs the
+/// full output to center itself, so captioned text is still buffered.
+pub fn render_diagram_to<W: std::io::Write>(
+    input: &str,
+    config: &diagram::Config,
+    mut writer: W,
+) -> Result<(), String> {
+    let input = diagram::expand_tabs(input, config.tab_width);
+    let mut diag = diagram::diagram_factory(&input)?;
+    diag.parse(&input, config)?;
+    write_parsed(diag.as_ref(), config, &mut writer)
+}
+
+/// Renders a graph (`graph`/`flowchart`) diagram without detecting the
+/// diagram type first; any other input is a parse error.
+pub fn render_graph(input: &str, config: &diagram::Config) -> Result<String, String> {
+    render_as(graph::GraphDiagram::default(), input, config)
+}
+
+/// Renders a `sequenceDiagram` without detecting the diagram type first;
+/// any other input is a parse error.
+pub fn render_sequence(input: &str, config: &diagram::Config) -> Result<String, String> {
+    render_as(sequence::SequenceDiagram::default(), input, config)
+}
+
+fn render_as<D: diagram::Diagram>(
+    mut diag: D,
+    input: &str,
+    config: &diagram::Config,
+) -> Result<String, String> {
+    let input = diagram::expand_tabs(input, config.tab_width);
+    diag.parse(&input, config)?;
+    let mut output = Vec::new();
+    write_parsed(&diag, config, &mut output)?;
+    String::from_utf8(output).map_err(|e| e.to_string())
+}
+
+fn write_parsed(
+    diag: &dyn diagram::Diagram,
+    config: &diagram::Config,
+    writer: &mut dyn std::io::Write,
+) -> Result<(), String> {
+    match &config.caption {
+        Some(caption)
+            if !matches!(
+                config.output_format,
+                diagram::OutputFormat::Svg | diagram::OutputFormat::Json
+            ) =>
+        {
+            let output = diagram::append_caption(&diag.render(config)?, caption);
+            writer
+                .write_all(output.as_bytes())
+                .map_err(|e| e.to_string())
+        }
+        _ => diag.render_to(config, writer),
+    }
 }
@@ -1,19 +1,25 @@
-use std::io::{self, IsTerminal, Read};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 
-use clap::{CommandFactory, Parser};
+use clap::{CommandFactory, Parser, ValueEnum};
+use console_mermaid::diagram::CharSet;
 
 #[derive(Parser, Debug)]
 #[command(name = "console-mermaid")]
 #[command(about = "Render Mermaid diagrams to ASCII/Unicode in the terminal")]
 struct Cli {
-    /// Input file path, or "-" to read from stdin
-    input: Option<PathBuf>,
+    /// Input file paths, or "-" to read from stdin; several files are
+    /// rendered in turn, each under a "=== path ===" header
+    input: Vec<PathBuf>,
 
     /// Use ASCII-only characters
     #[arg(long)]
     ascii: bool,
 
+    /// Line glyphs
+    #[arg(long, value_enum, default_value_t = CharSetArg::Light)]
+    charset: CharSetArg,
+
     /// Show layout coordinates
     #[arg(long)]
     coords: bool,
@@ -37,49 +43,64 @@ struct Cli {
     /// Graph direction: LR or TD
     #[arg(long, default_value = "LR", value_parser = ["LR", "TD"])]
     graph_direction: String,
+
+    /// Constrain output to this many columns where possible
+    #[arg(long)]
+    width: Option<i32>,
+
+    /// Write the rendered diagram to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Only parse the input: print "OK" or the parse error, without rendering
+    #[arg(long)]
+    check: bool,
+
+    /// Don't end the output with a newline
+    #[arg(long)]
+    no_trailing_newline: bool,
+
+    /// Read the input as Markdown and render each ```mermaid block; implied
+    /// for .md files
+    #[arg(long)]
+    markdown: bool,
 }
 
-fn main() {
-    let cli = Cli::parse();
+/// `--charset` values, mapped onto the library's `CharSet`.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum CharSetArg {
+    Light,
+    Heavy,
+    /// Light lines with arc corners
+    Rounded,
+    Double,
+    Ascii,
+}
 
-    let mut input = String::new();
-    match cli.input {
-        Some(path) if path.as_os_str() == "-" => {
-            if io::stdin().read_to_string(&mut input).is_err() {
-                eprintln!("failed to read stdin");
-                std::process::exit(1);
-            }
-        }
-        Some(path) => {
-            match std::fs::read_to_string(&path) {
-                Ok(contents) => input = contents,
-                Err(err) => {
-                    eprintln!("failed to read {}: {}", path.display(), err);
-                    std::process::exit(1);
-                }
-            }
-        }
-        None => {
-            if io::stdin().is_terminal() {
-                eprintln!("no input provided; pass a file path or '-' for stdin");
-                let mut cmd = Cli::command();
-                let _ = cmd.print_help();
-                eprintln!();
-                std::process::exit(2);
-            }
-            if io::stdin().read_to_string(&mut input).is_err() {
-                eprintln!("failed to read stdin");
-                std::process::exit(1);
-            }
+impl From<CharSetArg> for CharSet {
+    fn from(arg: CharSetArg) -> CharSet {
+        match arg {
+            CharSetArg::Light => CharSet::Light,
+            CharSetArg::Heavy => CharSet::Heavy,
+            CharSetArg::Rounded => CharSet::Rounded,
+            CharSetArg::Double => CharSet::Double,
+            CharSetArg::Ascii => CharSet::Ascii,
         }
     }
+}
 
-    if input.trim().is_empty() {
-        eprintln!("no input provided");
-        std::process::exit(1);
+fn main() {
+    let cli = Cli::parse();
+
+    if cli.input.is_empty() && io::stdin().is_terminal() {
+        eprintln!("no input provided; pass a file path or '-' for stdin");
+        let mut cmd = Cli::command();
+        let _ = cmd.print_help();
+        eprintln!();
+        std::process::exit(2);
     }
 
-    let config = match console_mermaid::diagram::Config::new_cli_config(
+    let mut config = match console_mermaid::diagram::Config::new_cli_config(
         cli.ascii,
         cli.coords,
         cli.verbose,
@@ -94,11 +115,104 @@ fn main() {
             std::process::exit(1);
         }
     };
-    match console_mermaid::render_diagram(&input, &config) {
-        Ok(output) => println!("{}", output),
-        Err(err) => {
+    config.max_width = cli.width;
+    config.charset = cli.charset.into();
+    if let Err(err) = config.validate() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+
+    let writer: Box<dyn Write> = match &cli.output {
+        Some(path) => match std::fs::File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("failed to write {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = io::BufWriter::new(writer);
+
+    // No paths reads stdin, as does a lone `-`.
+    let stdin = [PathBuf::from("-")];
+    let paths = if cli.input.is_empty() {
+        &stdin[..]
+    } else {
+        &cli.input[..]
+    };
+    let batch = paths.len() > 1;
+    let mut failed = false;
+    for (idx, path) in paths.iter().enumerate() {
+        // Every output ends with exactly one newline, bar the last one with
+        // --no-trailing-newline.
+        let newline = !cli.no_trailing_newline || idx + 1 < paths.len();
+        if batch && let Err(err) = writeln!(writer, "=== {} ===", path.display()) {
             eprintln!("{}", err);
             std::process::exit(1);
         }
+        let markdown = cli.markdown || is_markdown_path(path);
+        let result = read_input(path).and_then(|input| {
+            if cli.check && markdown {
+                let blocks = console_mermaid::diagram::extract_mermaid_blocks(&input);
+                if blocks.is_empty() {
+                    return Err("no diagrams found".to_string());
+                }
+                for (idx, block) in blocks.iter().enumerate() {
+                    console_mermaid::check_diagram(block, &config)
+                        .map_err(|err| format!("diagram {}: {}", idx + 1, err))?;
+                }
+                write!(writer, "OK").map_err(|e| e.to_string())?;
+            } else if cli.check {
+                console_mermaid::check_diagram(&input, &config)?;
+                write!(writer, "OK").map_err(|e| e.to_string())?;
+            } else if markdown {
+                let output = console_mermaid::render_markdown(&input, &config)?;
+                write!(writer, "{}", output).map_err(|e| e.to_string())?;
+            } else {
+                console_mermaid::render_diagram_to(&input, &config, &mut writer)?;
+            }
+            if newline {
+                writeln!(writer).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            if batch {
+                eprintln!("{}: {}", path.display(), err);
+            } else {
+                eprintln!("{}", err);
+            }
+            failed = true;
+        }
+    }
+    if let Err(err) = writer.flush() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn is_markdown_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+/// Reads one input, `-` meaning stdin, rejecting blank ones.
+fn read_input(path: &Path) -> Result<String, String> {
+    let mut input = String::new();
+    if path.as_os_str() == "-" {
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|_| "failed to read stdin".to_string())?;
+    } else {
+        input = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+    }
+    if input.trim().is_empty() {
+        return Err("no input provided".to_string());
     }
+    Ok(input)
 }
@@ -0,0 +1,140 @@
+use crate::diagram::{Config, Diagram, OutputFormat, apply_margins, comment_start, split_lines};
+
+const MINDMAP_KEYWORD: &str = "mindmap";
+
+/// A `mindmap`, drawn as an indented tree below its root. Node shapes such as
+/// `((root))` only contribute their text.
+#[derive(Debug, Clone, Default)]
+pub struct MindmapDiagram {
+    /// In source order; the root is always first.
+    nodes: Vec<MindmapNode>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MindmapNode {
+    label: String,
+    children: Vec<usize>,
+}
+
+pub fn is_mindmap(input: &str) -> bool {
+    for line in input.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("%%") {
+            continue;
+        }
+        return trimmed == MINDMAP_KEYWORD;
+    }
+    false
+}
+
+/// The text of a node, without the id and brackets of a shape like
+/// `id[text]`, `id((text))` or `id{{text}}`.
+fn node_label(text: &str) -> String {
+    let label = match text.find(['[', '(', '{', ')']) {
+        Some(idx) => text[idx..].trim_matches(|c| "[](){}".contains(c)).trim(),
+        None => text,
+    };
+    let label = if label.is_empty() { text } else { label };
+    label
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .unwrap_or(label)
+        .to_string()
+}
+
+impl MindmapDiagram {
+    fn draw_children(&self, idx: usize, prefix: &str, ascii: bool, lines: &mut Vec<String>) {
+        let (branch, last_branch, rail) = if ascii {
+            ("+- ", "\\- ", "|  ")
+        } else {
+            ("├─ ", "└─ ", "│  ")
+        };
+        let children = &self.nodes[idx].children;
+        for (position, child) in children.iter().enumerate() {
+            let last = position + 1 == children.len();
+            lines.push(format!(
+                "{}{}{}",
+                prefix,
+                if last { last_branch } else { branch },
+                self.nodes[*child].label
+            ));
+            let nested = format!("{}{}", prefix, if last { "   " } else { rail });
+            self.draw_children(*child, &nested, ascii, lines);
+        }
+    }
+}
+
+impl Diagram for MindmapDiagram {
+    fn parse(&mut self, input: &str, _config: &Config) -> Result<(), String> {
+        let lines = split_lines(input.trim());
+        let mut lines = lines.iter().enumerate().filter_map(|(idx, line)| {
+            let line = match comment_start(line) {
+                Some(end) => &line[..end],
+                None => line.as_str(),
+            };
+            let line = line.trim_end();
+            (!line.trim().is_empty() && !line.trim_start().starts_with("%%")).then_some((idx, line))
+        });
+        match lines.next() {
+            Some((_, header)) if is_mindmap(header) => {}
+            Some(_) => return Err(format!("expected \"{}\" keyword", MINDMAP_KEYWORD)),
+            None => return Err("no content found".to_string()),
+        }
+
+        self.nodes.clear();
+        // Indentation of each node on the path from the root to the last one.
+        let mut path: Vec<(usize, usize)> = Vec::new();
+        for (idx, line) in lines {
+            let text = line.trim_start();
+            // Icons and classes decorate the node above; they draw nothing.
+            if text.starts_with("::icon(") || text.starts_with(":::") {
+                continue;
+            }
+            let indent = line.len() - text.len();
+            while path.last().is_some_and(|(level, _)| *level >= indent) {
+                path.pop();
+            }
+            let node = self.nodes.len();
+            match path.last() {
+                Some((_, parent)) => self.nodes[*parent].children.push(node),
+                None if node > 0 => {
+                    return Err(format!(
+                        "line {}: mindmap can only have one root: \"{}\"",
+                        idx + 1,
+                        text
+                    ));
+                }
+                None => {}
+            }
+            self.nodes.push(MindmapNode {
+                label: node_label(text),
+                children: Vec::new(),
+            });
+            path.push((indent, node));
+        }
+        if self.nodes.is_empty() {
+            return Err("mindmap has no root".to_string());
+        }
+        Ok(())
+    }
+
+    fn render(&self, config: &Config) -> Result<String, String> {
+        let Some(root) = self.nodes.first() else {
+            return Err("mindmap has no root".to_string());
+        };
+        if config.output_format == OutputFormat::Svg {
+            return Err("SVG output is only supported for graph diagrams".to_string());
+        }
+        if config.output_format == OutputFormat::Json {
+            return Err("JSON output is only supported for graph diagrams".to_string());
+        }
+        let mut lines = vec![root.label.clone()];
+        self.draw_children(0, "", config.ascii_output(), &mut lines);
+        apply_margins(&mut lines, config);
+        Ok(lines.join("\n"))
+    }
+
+    fn diagram_type(&self) -> &'static str {
+        "mindmap"
+    }
+}
@@ -1,14 +1,25 @@
-use crate::diagram::{Config, Diagram, remove_comments, split_lines};
+use crate::diagram::{
+    CharSet, Config, Diagram, OutputFormat, apply_margins, remove_comments, split_lines,
+    truncate_label,
+};
+use crate::graph::junctions::merge;
 use regex::Regex;
 use unicode_width::UnicodeWidthStr;
 
 const SEQUENCE_DIAGRAM_KEYWORD: &str = "sequenceDiagram";
 const SOLID_ARROW_SYNTAX: &str = "->>";
+const DOTTED_ARROW_SYNTAX: &str = "-->>";
+const ASYNC_ARROW_SYNTAX: &str = "-)";
+const CROSS_ARROW_SYNTAX: &str = "-x";
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArrowType {
     Solid,
     Dotted,
+    /// `-)`: asynchronous message drawn with an open arrowhead.
+    AsyncOpen,
+    /// `-x`: lost message ending in a cross.
+    Cross,
 }
 
 impl std::fmt::Display for ArrowType {
@@ -16,31 +27,98 @@ impl std::fmt::Display for ArrowType {
         match self {
             ArrowType::Solid => write!(f, "solid"),
             ArrowType::Dotted => write!(f, "dotted"),
+            ArrowType::AsyncOpen => write!(f, "async"),
+            ArrowType::Cross => write!(f, "cross"),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParticipantKind {
+    #[default]
+    Participant,
+    Actor,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Participant {
     pub id: String,
     pub label: String,
     pub index: usize,
+    pub kind: ParticipantKind,
+    /// `create participant`: the box is drawn just above this message
+    /// instead of at the top.
+    pub created: Option<usize>,
+    /// `destroy`: the lifeline ends with a cross after this message.
+    pub destroyed: Option<usize>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Message {
     pub from: usize,
     pub to: usize,
     pub label: String,
     pub arrow_type: ArrowType,
     pub number: usize,
+    /// Extra blank lifeline rows requested with `+space N` after this message.
+    pub extra_space: usize,
+    /// `->>+B` / `->>-B` suffix on the arrow.
+    pub activation: Option<ActivationChange>,
 }
 
-#[derive(Debug, Clone, Default)]
+/// Whether an activation opens or closes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivationChange {
+    Activate,
+    Deactivate,
+}
+
+/// An `activate A` or `deactivate A` line before message `position`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activation {
+    pub participant: String,
+    pub change: ActivationChange,
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SequenceDiagram {
     pub participants: Vec<Participant>,
     pub messages: Vec<Message>,
     pub autonumber: bool,
+    /// Heading from a `title` line, rendered centered above the diagram.
+    pub title: Option<String>,
+    /// `participantSpacing=N` directive, overriding `Config`.
+    pub participant_spacing: Option<i32>,
+    /// `messageSpacing=N` directive, overriding `Config`.
+    pub message_spacing: Option<i32>,
+    /// `rect` blocks, in the order they close.
+    pub rects: Vec<MessageRect>,
+    pub notes: Vec<Note>,
+    /// `activate`/`deactivate` lines; checked but not drawn.
+    pub activations: Vec<Activation>,
+}
+
+/// A `Note over A` or `Note over A,B` box, drawn before message `position`
+/// (or after the last message when `position` is `messages.len()`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    /// Leftmost participant covered.
+    pub first: usize,
+    /// Rightmost participant covered; equals `first` for a single one.
+    pub last: usize,
+    pub text: String,
+    pub position: usize,
+}
+
+/// A `rect <color>` ... `end` block: a shaded background behind messages
+/// `start..end`, nested `depth` blocks deep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageRect {
+    pub color: String,
+    pub start: usize,
+    pub end: usize,
+    pub depth: usize,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -51,16 +129,23 @@ pub struct BoxChars {
     pub bottom_right: char,
     pub horizontal: char,
     pub vertical: char,
+    /// Drawn down from each participant; `vertical` unless dotted.
+    pub lifeline: char,
     pub tee_down: char,
     pub tee_right: char,
     pub tee_left: char,
     pub cross: char,
     pub arrow_right: char,
     pub arrow_left: char,
+    pub open_arrow_right: char,
+    pub open_arrow_left: char,
+    pub cross_end: char,
     pub solid_line: char,
     pub dotted_line: char,
     pub self_top_right: char,
     pub self_bottom: char,
+    pub destroyed: char,
+    pub actor: [&'static str; 3],
 }
 
 pub const ASCII: BoxChars = BoxChars {
@@ -70,16 +155,22 @@ pub const ASCII: BoxChars = BoxChars {
     bottom_right: '+',
     horizontal: '-',
     vertical: '|',
+    lifeline: '|',
     tee_down: '+',
     tee_right: '+',
     tee_left: '+',
     cross: '+',
     arrow_right: '>',
     arrow_left: '<',
+    open_arrow_right: '>',
+    open_arrow_left: '<',
+    cross_end: 'x',
     solid_line: '-',
     dotted_line: '.',
     self_top_right: '+',
     self_bottom: '+',
+    destroyed: 'X',
+    actor: [" O ", "/|\\", "/ \\"],
 };
 
 pub const UNICODE: BoxChars = BoxChars {
@@ -89,18 +180,85 @@ pub const UNICODE: BoxChars = BoxChars {
     bottom_right: '┘',
     horizontal: '─',
     vertical: '│',
+    lifeline: '│',
     tee_down: '┬',
     tee_right: '├',
     tee_left: '┤',
     cross: '┼',
     arrow_right: '►',
     arrow_left: '◄',
+    open_arrow_right: '>',
+    open_arrow_left: '<',
+    cross_end: '✗',
     solid_line: '─',
     dotted_line: '┈',
     self_top_right: '┐',
     self_bottom: '┘',
+    destroyed: '×',
+    actor: [" ○ ", "─┼─", "╱ ╲"],
 };
 
+/// The glyphs for `config`: `ASCII`, or `UNICODE` restyled by
+/// `config.charset`, with dotted lifelines when asked for.
+fn box_chars(config: &Config) -> BoxChars {
+    let ascii = config.ascii_output();
+    let mut chars = if ascii {
+        ASCII
+    } else {
+        restyled_chars(config.charset)
+    };
+    if config.sequence_dotted_lifelines {
+        chars.lifeline = if ascii { ':' } else { '┆' };
+    }
+    chars
+}
+
+/// Glyphs for participant boxes: `participant_box_style` in place of
+/// `chars`, with a bottom tee that still meets the lifeline below.
+fn header_chars(config: &Config, chars: BoxChars) -> BoxChars {
+    let Some(style) = config.participant_box_style else {
+        return chars;
+    };
+    if chars.vertical == ASCII.vertical || style == CharSet::Ascii {
+        return chars;
+    }
+    let boxed = restyled_chars(style);
+    let tee_down = match style {
+        _ if boxed.vertical == chars.vertical => boxed.tee_down,
+        CharSet::Heavy if chars.vertical == UNICODE.vertical => '┯',
+        CharSet::Double if chars.vertical == UNICODE.vertical => '╤',
+        CharSet::Light | CharSet::Rounded if chars.vertical == '┃' => '┰',
+        CharSet::Light | CharSet::Rounded if chars.vertical == '║' => '╥',
+        _ => chars.tee_down,
+    };
+    BoxChars {
+        tee_down,
+        lifeline: chars.lifeline,
+        ..boxed
+    }
+}
+
+fn restyled_chars(set: CharSet) -> BoxChars {
+    BoxChars {
+        top_left: set.glyph(UNICODE.top_left),
+        top_right: set.glyph(UNICODE.top_right),
+        bottom_left: set.glyph(UNICODE.bottom_left),
+        bottom_right: set.glyph(UNICODE.bottom_right),
+        horizontal: set.glyph(UNICODE.horizontal),
+        vertical: set.glyph(UNICODE.vertical),
+        lifeline: set.glyph(UNICODE.lifeline),
+        tee_down: set.glyph(UNICODE.tee_down),
+        tee_right: set.glyph(UNICODE.tee_right),
+        tee_left: set.glyph(UNICODE.tee_left),
+        cross: set.glyph(UNICODE.cross),
+        solid_line: set.glyph(UNICODE.solid_line),
+        dotted_line: set.glyph(UNICODE.dotted_line),
+        self_top_right: set.glyph(UNICODE.self_top_right),
+        self_bottom: set.glyph(UNICODE.self_bottom),
+        ..UNICODE
+    }
+}
+
 pub fn is_sequence_diagram(input: &str) -> bool {
     for line in input.lines() {
         let trimmed = line.trim();
@@ -113,6 +271,12 @@ pub fn is_sequence_diagram(input: &str) -> bool {
 }
 
 pub fn parse(input: &str) -> Result<SequenceDiagram, String> {
+    parse_with_config(input, &Config::default_config())
+}
+
+/// Parses a sequence diagram, honoring parse-time options such as
+/// `sequence_lenient`.
+pub fn parse_with_config(input: &str, config: &Config) -> Result<SequenceDiagram, String> {
     let input = input.trim();
     if input.is_empty() {
         return Err("empty input".to_string());
@@ -128,16 +292,44 @@ pub fn parse(input: &str) -> Result<SequenceDiagram, String> {
         return Err(format!("expected \"{}\" keyword", SEQUENCE_DIAGRAM_KEYWORD));
     }
 
-    let participant_re =
-        Regex::new(r#"^\s*participant\s+(?:"([^"]+)"|(\S+))(?:\s+as\s+(.+))?$"#).unwrap();
+    let participant_re = Regex::new(
+        r#"^\s*(participant|actor)\s+(?:"([^"]+)"|(\S+))(?:\s+as\s+(?:"([^"]*)"|(.+)))?$"#,
+    )
+    .unwrap();
     let message_re = Regex::new(
-        r#"^\s*(?:"([^"]+)"|([^\s\->]+))\s*(-->>|->>)\s*(?:"([^"]+)"|([^\s\->]+))\s*:\s*(.*)$"#,
+        r#"^\s*(?:"([^"]+)"|([^\s\->]+))\s*(-->>|->>|-\)|-x)\s*([+-])?\s*(?:"([^"]+)"|([^\s\->:]+))\s*(?::\s*(.*))?$"#,
     )
     .unwrap();
-    let autonumber_re = Regex::new(r"^\s*autonumber\s*$").unwrap();
+    let autonumber_re = Regex::new(r"^\s*autonumber(?:\s+(\d+)(?:\s+(\d+))?)?\s*$").unwrap();
+    let rect_re = Regex::new(r"^\s*rect(?:\s+(.*))?$").unwrap();
+    let end_re = Regex::new(r"^\s*end\s*$").unwrap();
+    let note_re = Regex::new(
+        r#"(?i)^\s*note\s+over\s+(?:"([^"]+)"|([^,:]+?))\s*(?:,\s*(?:"([^"]+)"|([^,:]+?))\s*)?:\s*(.*)$"#,
+    )
+    .unwrap();
+    let activation_re =
+        Regex::new(r#"^\s*(activate|deactivate)\s+(?:"([^"]+)"|(\S+))\s*$"#).unwrap();
+    let space_re = Regex::new(r"^\s*\+space\s+(\d+)\s*$").unwrap();
+    let destroy_re = Regex::new(r#"^\s*destroy\s+(?:"([^"]+)"|(\S+))\s*$"#).unwrap();
+    let title_re = Regex::new(r"^\s*title(?:\s*:\s*|\s+)(.+)$").unwrap();
+    let spacing_re =
+        Regex::new(r"(?i)^\s*(participantSpacing|messageSpacing)\s*=\s*(\d+)\s*$").unwrap();
 
     let mut diagram = SequenceDiagram::default();
-    let mut participants = std::collections::HashMap::new();
+    let mut participants: std::collections::HashMap<String, usize> =
+        std::collections::HashMap::new();
+    let mut auto_created = std::collections::HashSet::new();
+    let mut declared: Vec<usize> = Vec::new();
+    // `autonumber [start [step]]`: the next message number and increment.
+    let mut next_number = 1;
+    let mut number_step = 1;
+    // Open `rect` blocks as (color, first message).
+    let mut open_rects: Vec<(String, usize)> = Vec::new();
+    // Open activations per participant id.
+    let mut open_activations: std::collections::HashMap<String, usize> =
+        std::collections::HashMap::new();
+    // Spacing directives are only read before the first statement.
+    let mut in_preamble = true;
 
     for (idx, line) in lines.iter().skip(1).enumerate() {
         let trimmed = line.trim();
@@ -145,20 +337,162 @@ pub fn parse(input: &str) -> Result<SequenceDiagram, String> {
             continue;
         }
 
-        if autonumber_re.is_match(trimmed) {
+        if in_preamble && let Some(caps) = spacing_re.captures(trimmed) {
+            let value: i32 = caps[2]
+                .parse()
+                .map_err(|_| format!("line {}: invalid spacing: \"{}\"", idx + 2, trimmed))?;
+            if caps[1].eq_ignore_ascii_case("participantSpacing") {
+                diagram.participant_spacing = Some(value);
+            } else {
+                diagram.message_spacing = Some(value);
+            }
+            continue;
+        }
+        in_preamble = false;
+
+        // `create participant X` declares X as usual, but X only appears
+        // at the next message.
+        let (trimmed, create) = match trimmed.strip_prefix("create ") {
+            Some(rest) if participant_re.is_match(rest) => (rest.trim(), true),
+            Some(_) => {
+                return Err(format!(
+                    "line {}: create must be followed by a participant declaration: \"{}\"",
+                    idx + 2,
+                    trimmed
+                ));
+            }
+            None => (trimmed, false),
+        };
+
+        if let Some(caps) = autonumber_re.captures(trimmed) {
             diagram.autonumber = true;
+            let arg = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<usize>().ok());
+            next_number = arg(1).unwrap_or(diagram.messages.len() + 1);
+            number_step = arg(2).unwrap_or(1);
+            continue;
+        }
+
+        if let Some(caps) = title_re.captures(trimmed) {
+            diagram.title = Some(caps[1].trim().to_string());
+            continue;
+        }
+
+        if let Some(caps) = space_re.captures(trimmed) {
+            let rows: usize = caps[1]
+                .parse()
+                .map_err(|_| format!("line {}: invalid spacing: \"{}\"", idx + 2, trimmed))?;
+            match diagram.messages.last_mut() {
+                Some(message) => message.extra_space += rows,
+                None => {
+                    return Err(format!(
+                        "line {}: \"+space\" must follow a message",
+                        idx + 2
+                    ));
+                }
+            }
+            continue;
+        }
+
+        if let Some(caps) = rect_re.captures(trimmed) {
+            let color = caps.get(1).map_or("", |m| m.as_str().trim());
+            open_rects.push((color.to_string(), diagram.messages.len()));
+            continue;
+        }
+
+        if end_re.is_match(trimmed) {
+            let Some((color, start)) = open_rects.pop() else {
+                return Err(format!("line {}: \"end\" without an open block", idx + 2));
+            };
+            diagram.rects.push(MessageRect {
+                color,
+                start,
+                end: diagram.messages.len(),
+                depth: open_rects.len(),
+            });
+            continue;
+        }
+
+        if let Some(caps) = destroy_re.captures(trimmed) {
+            let id = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            let Some(&participant) = participants.get(id) else {
+                return Err(format!("line {}: unknown participant \"{}\"", idx + 2, id));
+            };
+            diagram.participants[participant].destroyed = Some(diagram.messages.len());
+            continue;
+        }
+
+        if let Some(caps) = note_re.captures(trimmed) {
+            let id = |quoted: usize, bare: usize| {
+                caps.get(quoted)
+                    .or_else(|| caps.get(bare))
+                    .map(|m| m.as_str().trim())
+            };
+            let first_id = id(1, 2).unwrap_or("");
+            let last_id = id(3, 4).unwrap_or(first_id);
+            for id in [first_id, last_id] {
+                if !participants.contains_key(id) {
+                    auto_created.insert(id.to_string());
+                }
+            }
+            let first = get_or_insert_participant(first_id, &mut diagram, &mut participants);
+            let last = get_or_insert_participant(last_id, &mut diagram, &mut participants);
+            diagram.notes.push(Note {
+                first: first.min(last),
+                last: first.max(last),
+                text: caps[5].trim().to_string(),
+                position: diagram.messages.len(),
+            });
+            continue;
+        }
+
+        if let Some(caps) = activation_re.captures(trimmed) {
+            let id = caps.get(2).or_else(|| caps.get(3)).unwrap().as_str();
+            let change = if &caps[1] == "activate" {
+                *open_activations.entry(id.to_string()).or_default() += 1;
+                ActivationChange::Activate
+            } else {
+                deactivate(&mut open_activations, id, idx + 2)?;
+                ActivationChange::Deactivate
+            };
+            diagram.activations.push(Activation {
+                participant: id.to_string(),
+                change,
+                position: diagram.messages.len(),
+            });
             continue;
         }
 
         if let Some(caps) = participant_re.captures(trimmed) {
-            let id = caps.get(2).map(|m| m.as_str()).unwrap_or("");
-            let id = if let Some(quoted) = caps.get(1) {
+            let kind = if caps.get(1).map(|m| m.as_str()) == Some("actor") {
+                ParticipantKind::Actor
+            } else {
+                ParticipantKind::Participant
+            };
+            let id = caps.get(3).map(|m| m.as_str()).unwrap_or("");
+            let id = if let Some(quoted) = caps.get(2) {
                 quoted.as_str()
             } else {
                 id
             };
-            let label = caps.get(3).map(|m| m.as_str()).unwrap_or("");
-            let label = if label.is_empty() { id } else { label };
+            // A quoted alias is taken verbatim, commas, colons and all.
+            let label = match (caps.get(4), caps.get(5)) {
+                (Some(quoted), _) => quoted.as_str(),
+                (None, Some(bare)) => bare.as_str().trim(),
+                (None, None) => "",
+            };
+            let label = if label.is_empty() { id } else { label }.to_string();
+            if let Some(&existing) = participants.get(id)
+                && !create
+                && auto_created.remove(id)
+            {
+                // Declared after a message already introduced it: adopt the
+                // declared alias and kind; the column is fixed up below.
+                let participant = &mut diagram.participants[existing];
+                participant.label = label;
+                participant.kind = kind;
+                declared.push(existing);
+                continue;
+            }
             if participants.contains_key(id) {
                 return Err(format!(
                     "line {}: duplicate participant \"{}\"",
@@ -168,10 +502,18 @@ pub fn parse(input: &str) -> Result<SequenceDiagram, String> {
             }
             let participant = Participant {
                 id: id.to_string(),
-                label: label.trim_matches('"').to_string(),
+                label,
                 index: diagram.participants.len(),
+                kind,
+                created: create.then_some(diagram.messages.len()),
+                destroyed: None,
             };
             participants.insert(id.to_string(), participant.index);
+            // Created participants keep their place among the undeclared
+            // ones rather than moving to the front.
+            if !create {
+                declared.push(participant.index);
+            }
             diagram.participants.push(participant);
             continue;
         }
@@ -183,24 +525,54 @@ pub fn parse(input: &str) -> Result<SequenceDiagram, String> {
                 caps.get(2).map(|m| m.as_str()).unwrap_or("")
             };
             let arrow = caps.get(3).map(|m| m.as_str()).unwrap_or("");
-            let to_id = if let Some(quoted) = caps.get(4) {
+            let to_id = if let Some(quoted) = caps.get(5) {
                 quoted.as_str()
             } else {
-                caps.get(5).map(|m| m.as_str()).unwrap_or("")
+                caps.get(6).map(|m| m.as_str()).unwrap_or("")
+            };
+            let label = match caps.get(7) {
+                Some(m) => m.as_str().trim(),
+                None if config.sequence_lenient => "",
+                None => {
+                    return Err(format!(
+                        "line {}: message is missing a ':' label separator: \"{}\"",
+                        idx + 2,
+                        trimmed
+                    ));
+                }
             };
-            let label = caps.get(6).map(|m| m.as_str()).unwrap_or("").trim();
 
+            for id in [from_id, to_id] {
+                if !participants.contains_key(id) {
+                    auto_created.insert(id.to_string());
+                }
+            }
+            // `->>+B` activates the target; `->>-B` ends the sender's
+            // activation, as when replying.
+            let activation = match caps.get(4).map(|m| m.as_str()) {
+                Some("+") => {
+                    *open_activations.entry(to_id.to_string()).or_default() += 1;
+                    Some(ActivationChange::Activate)
+                }
+                Some(_) => {
+                    deactivate(&mut open_activations, from_id, idx + 2)?;
+                    Some(ActivationChange::Deactivate)
+                }
+                None => None,
+            };
             let from_idx = get_or_insert_participant(from_id, &mut diagram, &mut participants);
             let to_idx = get_or_insert_participant(to_id, &mut diagram, &mut participants);
 
-            let arrow_type = if arrow == SOLID_ARROW_SYNTAX {
-                ArrowType::Solid
-            } else {
-                ArrowType::Dotted
+            let arrow_type = match arrow {
+                SOLID_ARROW_SYNTAX => ArrowType::Solid,
+                ASYNC_ARROW_SYNTAX => ArrowType::AsyncOpen,
+                CROSS_ARROW_SYNTAX => ArrowType::Cross,
+                _ => ArrowType::Dotted,
             };
 
             let number = if diagram.autonumber {
-                diagram.messages.len() + 1
+                next_number += number_step;
+                next_number - number_step
             } else {
                 0
             };
@@ -211,6 +583,8 @@ pub fn parse(input: &str) -> Result<SequenceDiagram, String> {
                 label: label.to_string(),
                 arrow_type,
                 number,
+                extra_space: 0,
+                activation,
             });
             continue;
         }
@@ -222,9 +596,69 @@ pub fn parse(input: &str) -> Result<SequenceDiagram, String> {
         return Err("no participants found".to_string());
     }
 
+    if !open_rects.is_empty() {
+        return Err("rect block is missing its \"end\"".to_string());
+    }
+
+    let mut still_active: Vec<&String> = open_activations
+        .iter()
+        .filter(|(_, depth)| **depth > 0)
+        .map(|(id, _)| id)
+        .collect();
+    still_active.sort();
+    for id in still_active {
+        eprintln!("warning: {} is still active at the end of the diagram", id);
+    }
+
+    order_declared_first(&mut diagram, &declared);
     Ok(diagram)
 }
 
+/// Closes one activation of `id`, failing when none is open.
+fn deactivate(
+    activations: &mut std::collections::HashMap<String, usize>,
+    id: &str,
+    line: usize,
+) -> Result<(), String> {
+    match activations.get_mut(id) {
+        Some(depth) if *depth > 0 => {
+            *depth -= 1;
+            Ok(())
+        }
+        _ => Err(format!(
+            "line {}: deactivation of {} without matching activation",
+            line, id
+        )),
+    }
+}
+
+/// Reorders columns so declared participants come first, in declaration
+/// order, followed by the implicit ones in order of first use.
+fn order_declared_first(diagram: &mut SequenceDiagram, declared: &[usize]) {
+    let mut order = declared.to_vec();
+    order.extend((0..diagram.participants.len()).filter(|idx| !declared.contains(idx)));
+    let mut new_index = vec![0; order.len()];
+    for (position, &old) in order.iter().enumerate() {
+        new_index[old] = position;
+    }
+
+    let mut participants = std::mem::take(&mut diagram.participants);
+    for participant in &mut participants {
+        participant.index = new_index[participant.index];
+    }
+    participants.sort_by_key(|participant| participant.index);
+    diagram.participants = participants;
+    for message in &mut diagram.messages {
+        message.from = new_index[message.from];
+        message.to = new_index[message.to];
+    }
+    for note in &mut diagram.notes {
+        let (a, b) = (new_index[note.first], new_index[note.last]);
+        note.first = a.min(b);
+        note.last = a.max(b);
+    }
+}
+
 fn get_or_insert_participant(
     id: &str,
     diagram: &mut SequenceDiagram,
@@ -238,148 +672,772 @@ fn get_or_insert_participant(
         id: id.to_string(),
         label: id.to_string(),
         index: idx,
+        kind: ParticipantKind::Participant,
+        created: None,
+        destroyed: None,
     });
     participants.insert(id.to_string(), idx);
     idx
 }
 
+/// Serializes a parsed diagram back into normalized Mermaid source: every
+/// participant is declared up front in column order, followed by the messages.
+pub fn to_mermaid(diagram: &SequenceDiagram) -> String {
+    let mut lines = vec![SEQUENCE_DIAGRAM_KEYWORD.to_string()];
+    if let Some(spacing) = diagram.participant_spacing {
+        lines.push(format!("    participantSpacing={}", spacing));
+    }
+    if let Some(spacing) = diagram.message_spacing {
+        lines.push(format!("    messageSpacing={}", spacing));
+    }
+    if let Some(title) = &diagram.title {
+        lines.push(format!("    title {}", title));
+    }
+
+    for participant in &diagram.participants {
+        if participant.created.is_none() {
+            lines.push(format!("    {}", declaration(participant)));
+        }
+    }
+
+    let mut numbering = false;
+    let mut open = 0;
+    for (idx, message) in diagram.messages.iter().enumerate() {
+        write_notes(diagram, idx, &mut lines);
+        write_rects(diagram, idx, &mut open, &mut lines);
+        write_lifecycle(diagram, idx, &mut lines);
+        write_activations(diagram, idx, &mut lines);
+        if message.number > 0 && !numbering {
+            let step = diagram
+                .messages
+                .get(idx + 1)
+                .map_or(1, |next| next.number.saturating_sub(message.number));
+            if message.number == idx + 1 && step == 1 {
+                lines.push("    autonumber".to_string());
+            } else {
+                lines.push(format!("    autonumber {} {}", message.number, step));
+            }
+            numbering = true;
+        }
+        let arrow = match message.arrow_type {
+            ArrowType::Solid => SOLID_ARROW_SYNTAX,
+            ArrowType::Dotted => DOTTED_ARROW_SYNTAX,
+            ArrowType::AsyncOpen => ASYNC_ARROW_SYNTAX,
+            ArrowType::Cross => CROSS_ARROW_SYNTAX,
+        };
+        let suffix = match message.activation {
+            Some(ActivationChange::Activate) => "+",
+            Some(ActivationChange::Deactivate) => "-",
+            None => "",
+        };
+        lines.push(format!(
+            "    {}{}{}{}: {}",
+            quote_if_needed(&diagram.participants[message.from].id),
+            arrow,
+            suffix,
+            quote_if_needed(&diagram.participants[message.to].id),
+            message.label
+        ));
+        if message.extra_space > 0 {
+            lines.push(format!("    +space {}", message.extra_space));
+        }
+    }
+    write_notes(diagram, diagram.messages.len(), &mut lines);
+    write_rects(diagram, diagram.messages.len(), &mut open, &mut lines);
+    write_lifecycle(diagram, diagram.messages.len(), &mut lines);
+    write_activations(diagram, diagram.messages.len(), &mut lines);
+    if diagram.autonumber && !numbering {
+        lines.push("    autonumber".to_string());
+    }
+
+    format!("{}\n", lines.join("\n"))
+}
+
+fn declaration(participant: &Participant) -> String {
+    let keyword = match participant.kind {
+        ParticipantKind::Participant => "participant",
+        ParticipantKind::Actor => "actor",
+    };
+    let id = quote_if_needed(&participant.id);
+    if participant.label == participant.id {
+        format!("{} {}", keyword, id)
+    } else {
+        let alias = if participant.label.contains('"') {
+            participant.label.clone()
+        } else {
+            format!("\"{}\"", participant.label)
+        };
+        format!("{} {} as {}", keyword, id, alias)
+    }
+}
+
+/// Writes the `create` and `destroy` statements taking effect at message
+/// `idx`.
+fn write_lifecycle(diagram: &SequenceDiagram, idx: usize, lines: &mut Vec<String>) {
+    for participant in &diagram.participants {
+        if participant.created == Some(idx) {
+            lines.push(format!("    create {}", declaration(participant)));
+        }
+    }
+    for participant in &diagram.participants {
+        if participant.destroyed == Some(idx) {
+            lines.push(format!("    destroy {}", quote_if_needed(&participant.id)));
+        }
+    }
+}
+
+fn write_activations(diagram: &SequenceDiagram, idx: usize, lines: &mut Vec<String>) {
+    for activation in diagram.activations.iter().filter(|a| a.position == idx) {
+        let keyword = match activation.change {
+            ActivationChange::Activate => "activate",
+            ActivationChange::Deactivate => "deactivate",
+        };
+        lines.push(format!(
+            "    {} {}",
+            keyword,
+            quote_if_needed(&activation.participant)
+        ));
+    }
+}
+
+fn write_notes(diagram: &SequenceDiagram, idx: usize, lines: &mut Vec<String>) {
+    for note in diagram.notes.iter().filter(|note| note.position == idx) {
+        let mut over = quote_if_needed(&diagram.participants[note.first].id);
+        if note.last != note.first {
+            over = format!(
+                "{},{}",
+                over,
+                quote_if_needed(&diagram.participants[note.last].id)
+            );
+        }
+        lines.push(format!("    Note over {}: {}", over, note.text));
+    }
+}
+
+/// Writes the `end` of every block closing before message `idx`, innermost
+/// first, then the `rect` of every block opening there, outermost first.
+fn write_rects(diagram: &SequenceDiagram, idx: usize, open: &mut usize, lines: &mut Vec<String>) {
+    let mut closing: Vec<&MessageRect> = diagram
+        .rects
+        .iter()
+        .filter(|rect| rect.end == idx && rect.start < idx)
+        .collect();
+    closing.sort_by_key(|rect| std::cmp::Reverse(rect.depth));
+    for _ in closing {
+        *open -= 1;
+        lines.push(format!("{}end", "    ".repeat(*open + 1)));
+    }
+
+    let mut opening: Vec<&MessageRect> = diagram
+        .rects
+        .iter()
+        .filter(|rect| rect.start == idx)
+        .collect();
+    opening.sort_by_key(|rect| rect.depth);
+    for rect in opening {
+        let indent = "    ".repeat(*open + 1);
+        lines.push(
+            format!("{}rect {}", indent, rect.color)
+                .trim_end()
+                .to_string(),
+        );
+        if rect.end == idx {
+            lines.push(format!("{}end", indent));
+        } else {
+            *open += 1;
+        }
+    }
+}
+
+fn quote_if_needed(id: &str) -> String {
+    if id.contains(char::is_whitespace) || id.contains(['-', '>']) {
+        format!("\"{}\"", id)
+    } else {
+        id.to_string()
+    }
+}
+
 const DEFAULT_SELF_MESSAGE_WIDTH: i32 = 4;
+/// A self-message loop needs a cell between the lifeline and its right side
+/// for the arrowhead, so narrower widths draw this minimal loop.
+const MIN_SELF_LOOP_WIDTH: i32 = 3;
 const DEFAULT_MESSAGE_SPACING: i32 = 1;
 const DEFAULT_PARTICIPANT_SPACING: i32 = 5;
+const MIN_PARTICIPANT_SPACING: i32 = 1;
 const BOX_PADDING_LEFT_RIGHT: i32 = 2;
 const MIN_BOX_WIDTH: i32 = 3;
 const BOX_BORDER_WIDTH: i32 = 2;
 const LABEL_LEFT_MARGIN: i32 = 2;
 const LABEL_BUFFER_SPACE: i32 = 10;
+const ACTOR_FIGURE_WIDTH: i32 = 3;
+const NOTE_PADDING: i32 = 1;
 
 #[derive(Debug)]
 struct DiagramLayout {
+    /// Participant names as printed in the headers, after `max_label_width`.
+    participant_labels: Vec<String>,
+    /// Message labels as printed, numbered and cut to fit `max_width`.
+    message_labels: Vec<String>,
     participant_widths: Vec<i32>,
     participant_centers: Vec<i32>,
     total_width: i32,
     message_spacing: i32,
     self_message_width: i32,
+    /// Whose lifeline is drawn at the current row; see `create`/`destroy`.
+    alive: Vec<bool>,
 }
 
 fn calculate_layout(diagram: &SequenceDiagram, config: &Config) -> DiagramLayout {
-    let participant_spacing = if config.sequence_participant_spacing > 0 {
+    let mut participant_spacing = if let Some(spacing) = diagram.participant_spacing {
+        spacing
+    } else if config.sequence_participant_spacing > 0 {
         config.sequence_participant_spacing
     } else {
         DEFAULT_PARTICIPANT_SPACING
     };
 
-    let mut widths = Vec::with_capacity(diagram.participants.len());
-    for participant in &diagram.participants {
-        let label_width = UnicodeWidthStr::width(participant.label.as_str()) as i32;
-        let mut w = label_width + BOX_PADDING_LEFT_RIGHT;
-        if w < MIN_BOX_WIDTH {
-            w = MIN_BOX_WIDTH;
+    let participant_labels = |limit: Option<usize>| -> Vec<String> {
+        diagram
+            .participants
+            .iter()
+            .map(|participant| match limit {
+                Some(limit) => truncate_label(&participant.label, limit, config.ascii_output()),
+                None => participant.label.clone(),
+            })
+            .collect()
+    };
+    let mut labels = participant_labels(config.max_label_width);
+    let mut widths = participant_widths(diagram, &labels);
+
+    if let Some(max_width) = config.max_width
+        && widths.len() > 1
+    {
+        let boxes: i32 = widths.iter().map(|w| w + BOX_BORDER_WIDTH).sum();
+        let gaps = widths.len() as i32 - 1;
+        let fitted = (max_width - boxes) / gaps;
+        participant_spacing = participant_spacing.min(fitted.max(MIN_PARTICIPANT_SPACING));
+    }
+
+    // When even the narrowest spacing overflows, cut the longest names down
+    // one column at a time until the boxes fit.
+    if let Some(max_width) = config.max_width {
+        let gap = participant_spacing.max(MIN_PARTICIPANT_SPACING);
+        let span = |widths: &[i32]| -> i32 {
+            widths
+                .iter()
+                .map(|w| w + BOX_BORDER_WIDTH + gap)
+                .sum::<i32>()
+                - gap
+        };
+        let mut limit = labels
+            .iter()
+            .map(|label| UnicodeWidthStr::width(label.as_str()))
+            .max()
+            .unwrap_or(0);
+        while span(&widths) > max_width && limit > 1 {
+            limit -= 1;
+            labels = participant_labels(Some(limit));
+            widths = participant_widths(diagram, &labels);
         }
-        widths.push(w);
     }
 
+    // Boxes are placed by their edges, never closer than
+    // `MIN_PARTICIPANT_SPACING`, so a wide box can't run into its neighbour.
+    let gap = participant_spacing.max(MIN_PARTICIPANT_SPACING);
     let mut centers = Vec::with_capacity(diagram.participants.len());
-    let mut current_x = 0;
+    let mut left = 0;
     for width in &widths {
         let box_width = width + BOX_BORDER_WIDTH;
-        if centers.is_empty() {
-            centers.push(box_width / 2);
-            current_x = box_width;
-        } else {
-            current_x += participant_spacing;
-            centers.push(current_x + box_width / 2);
-            current_x += box_width;
-        }
+        centers.push(left + box_width / 2);
+        left += box_width + gap;
     }
 
     let last = diagram.participants.len() - 1;
     let total_width = centers[last] + (widths[last] + BOX_BORDER_WIDTH) / 2;
 
-    let message_spacing = if config.sequence_message_spacing > 0 {
+    let message_spacing = if let Some(spacing) = diagram.message_spacing {
+        spacing
+    } else if config.sequence_message_spacing > 0 {
         config.sequence_message_spacing
     } else {
         DEFAULT_MESSAGE_SPACING
     };
 
     let self_message_width = if config.sequence_self_message_width > 0 {
-        config.sequence_self_message_width
+        config.sequence_self_message_width.max(MIN_SELF_LOOP_WIDTH)
     } else {
         DEFAULT_SELF_MESSAGE_WIDTH
     };
 
-    DiagramLayout {
+    let mut layout = DiagramLayout {
+        participant_labels: labels,
+        message_labels: Vec::with_capacity(diagram.messages.len()),
         participant_widths: widths,
         participant_centers: centers,
         total_width,
         message_spacing,
         self_message_width,
+        alive: diagram
+            .participants
+            .iter()
+            .map(|participant| participant.created.is_none())
+            .collect(),
+    };
+    // A note too wide for its participants reaches past the last box.
+    for note in &diagram.notes {
+        let (_, right, _) = note_box(note, &layout);
+        layout.total_width = layout.total_width.max(right);
     }
+    // So does a message label longer than the span it is written over.
+    // Under `max_width` such a label is cut at the limit instead.
+    for message in &diagram.messages {
+        let mut label = message.label.clone();
+        if message.number > 0 {
+            label = format!("{}. {}", message.number, label);
+        }
+        let from = layout.participant_centers[message.from];
+        let to = layout.participant_centers[message.to];
+        let start = from.min(to) + LABEL_LEFT_MARGIN;
+        if let Some(max_width) = config.max_width {
+            let budget = (max_width - start).max(1) as usize;
+            label = truncate_label(&label, budget, config.ascii_output());
+        }
+        let label_width = UnicodeWidthStr::width(label.as_str()) as i32;
+        if label_width > 0 {
+            layout.total_width = layout.total_width.max(start + label_width - 1);
+        }
+        layout.message_labels.push(label);
+    }
+    layout
+}
+
+fn participant_widths(diagram: &SequenceDiagram, labels: &[String]) -> Vec<i32> {
+    diagram
+        .participants
+        .iter()
+        .zip(labels)
+        .map(|(participant, label)| {
+            let label_width = UnicodeWidthStr::width(label.as_str()) as i32;
+            if participant.kind == ParticipantKind::Actor {
+                // Actors have no border, so the column is just the wider of
+                // the figure and the name printed beneath it.
+                (label_width.max(ACTOR_FIGURE_WIDTH) - BOX_BORDER_WIDTH).max(1)
+            } else {
+                (label_width + BOX_PADDING_LEFT_RIGHT).max(MIN_BOX_WIDTH)
+            }
+        })
+        .collect()
 }
 
 pub fn render(diagram: &SequenceDiagram, config: &Config) -> Result<String, String> {
+    let lines = render_lines(diagram, config)?;
+    Ok(lines.join("\n"))
+}
+
+/// Writes the rendered diagram line by line; the bytes match [`render`].
+pub fn render_to(
+    diagram: &SequenceDiagram,
+    config: &Config,
+    writer: &mut dyn std::io::Write,
+) -> Result<(), String> {
+    for (idx, line) in render_lines(diagram, config)?.iter().enumerate() {
+        if idx > 0 {
+            writeln!(writer).map_err(|e| e.to_string())?;
+        }
+        write!(writer, "{}", line).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn render_lines(diagram: &SequenceDiagram, config: &Config) -> Result<Vec<String>, String> {
     if diagram.participants.is_empty() {
         return Err("no participants".to_string());
     }
 
-    let chars = if config.use_ascii { ASCII } else { UNICODE };
-    let layout = calculate_layout(diagram, config);
+    if config.output_format == OutputFormat::Svg {
+        return Err("SVG output is only supported for graph diagrams".to_string());
+    }
+    if config.output_format == OutputFormat::Json {
+        return Err("JSON output is only supported for graph diagrams".to_string());
+    }
+    let chars = box_chars(config);
+    let header = header_chars(config, chars);
+    let mut layout = calculate_layout(diagram, config);
+    if let Some(max_width) = config.max_width
+        && layout.total_width > max_width
+    {
+        eprintln!(
+            "warning: sequence diagram needs {} columns, exceeding --width {}",
+            layout.total_width, max_width
+        );
+    }
 
     let mut lines: Vec<String> = Vec::new();
+    if let Some(title) = &diagram.title {
+        let title_width = UnicodeWidthStr::width(title.as_str()) as i32;
+        let pad = ((layout.total_width - title_width) / 2).max(0) as usize;
+        lines.push(format!("{}{}", " ".repeat(pad), title));
+        lines.push(String::new());
+    }
 
-    lines.push(build_line(diagram, &layout, |i| {
-        let width = layout.participant_widths[i] as usize;
-        format!(
-            "{}{}{}",
-            chars.top_left,
-            chars.horizontal.to_string().repeat(width),
-            chars.top_right
-        )
-    }));
-
-    lines.push(build_line(diagram, &layout, |i| {
-        let width = layout.participant_widths[i] as usize;
-        let label = &diagram.participants[i].label;
-        let label_len = UnicodeWidthStr::width(label.as_str()) as i32;
-        let pad = ((width as i32 - label_len) / 2).max(0) as usize;
-        let right_pad = width.saturating_sub(pad + label.chars().count());
-        format!(
-            "{}{}{}{}",
-            chars.vertical,
-            " ".repeat(pad),
-            label,
-            format!("{}{}", " ".repeat(right_pad), chars.vertical)
-        )
-    }));
-
-    lines.push(build_line(diagram, &layout, |i| {
-        let width = layout.participant_widths[i] as usize;
-        let left = width / 2;
-        let right = width - left - 1;
-        format!(
-            "{}{}{}{}{}",
-            chars.bottom_left,
-            chars.horizontal.to_string().repeat(left),
-            chars.tee_down,
-            chars.horizontal.to_string().repeat(right),
-            chars.bottom_right
-        )
-    }));
+    let has_actors = diagram
+        .participants
+        .iter()
+        .any(|participant| participant.kind == ParticipantKind::Actor);
 
-    for message in &diagram.messages {
+    let header_rows = if has_actors { 4 } else { 3 };
+    for row in 0..header_rows {
+        let line = build_line(diagram, &layout, |i| {
+            if diagram.participants[i].created.is_some() {
+                return String::new();
+            }
+            header_cell(diagram, &layout, header, i, row)
+        });
+        lines.push(line.trim_end().to_string());
+    }
+
+    let mut message_lines = Vec::with_capacity(diagram.messages.len());
+    let mut note_boxes = Vec::new();
+    for (idx, message) in diagram.messages.iter().enumerate() {
+        render_notes(diagram, idx, &layout, chars, &mut lines, &mut note_boxes);
+        render_created(diagram, idx, header_rows, &mut layout, header, &mut lines);
+        let first = lines.len();
         for _ in 0..layout.message_spacing {
             lines.push(build_lifeline(&layout, chars));
         }
 
         if message.from == message.to {
-            lines.extend(render_self_message(message, diagram, &layout, chars));
+            let label = &layout.message_labels[idx];
+            lines.extend(render_self_message(message, label, &layout, chars));
         } else {
-            lines.extend(render_message(message, diagram, &layout, chars));
+            let label = &layout.message_labels[idx];
+            lines.extend(render_message(message, label, &layout, chars));
         }
+
+        for _ in 0..message.extra_space {
+            lines.push(build_lifeline(&layout, chars));
+        }
+        render_destroyed(diagram, idx, &mut layout, chars, &mut lines);
+        message_lines.push(first..lines.len());
     }
+    render_notes(
+        diagram,
+        diagram.messages.len(),
+        &layout,
+        chars,
+        &mut lines,
+        &mut note_boxes,
+    );
+    render_destroyed(
+        diagram,
+        diagram.messages.len(),
+        &mut layout,
+        chars,
+        &mut lines,
+    );
+    shade_rects(
+        &mut lines,
+        diagram,
+        &layout,
+        &message_lines,
+        &note_boxes,
+        config,
+        chars,
+    );
 
     lines.push(build_lifeline(&layout, chars));
 
-    Ok(format!("{}\n", lines.join("\n")))
+    apply_margins(&mut lines, config);
+    Ok(lines)
+}
+
+/// Shades the background behind each `rect` block, outermost first so nested
+/// blocks draw over their parents. Only blank cells are filled: lifelines,
+/// arrows and the spaces between words of a label are kept.
+fn shade_rects(
+    lines: &mut [String],
+    diagram: &SequenceDiagram,
+    layout: &DiagramLayout,
+    message_lines: &[std::ops::Range<usize>],
+    note_boxes: &[(std::ops::Range<usize>, usize, usize)],
+    config: &Config,
+    chars: BoxChars,
+) {
+    let shades = if chars.vertical == ASCII.vertical {
+        [':', '*', '#']
+    } else {
+        ['░', '▒', '▓']
+    };
+    let mut rects: Vec<&MessageRect> = diagram
+        .rects
+        .iter()
+        .filter(|rect| rect.start < rect.end)
+        .collect();
+    rects.sort_by_key(|rect| rect.depth);
+
+    for rect in rects {
+        let glyph = config
+            .fill_glyph
+            .unwrap_or(shades[rect.depth.min(shades.len() - 1)]);
+        let mut left = i32::MAX;
+        let mut right = 0;
+        for (idx, message) in diagram.messages[rect.start..rect.end]
+            .iter()
+            .enumerate()
+            .map(|(offset, message)| (rect.start + offset, message))
+        {
+            for i in [message.from, message.to] {
+                let box_width = layout.participant_widths[i] + BOX_BORDER_WIDTH;
+                let box_left = layout.participant_centers[i] - box_width / 2;
+                left = left.min(box_left);
+                right = right.max(box_left + box_width - 1);
+            }
+            if message.from == message.to {
+                let center = layout.participant_centers[message.from];
+                let label_width =
+                    UnicodeWidthStr::width(layout.message_labels[idx].as_str()) as i32;
+                right = right
+                    .max(center + layout.self_message_width - 1)
+                    .max(center + LABEL_LEFT_MARGIN + label_width - 1);
+            }
+        }
+        let (left, right) = (left.max(0) as usize, right.max(0) as usize);
+
+        let is_text = |ch: char| {
+            !ch.is_whitespace()
+                && ch != chars.vertical
+                && ch != chars.lifeline
+                && ch != glyph
+                && !shades.contains(&ch)
+        };
+        let span = message_lines[rect.start].start..message_lines[rect.end - 1].end;
+        for row in span {
+            let line = &mut lines[row];
+            let original = ensure_width(std::mem::take(line), right + 1);
+            let in_note = |x: usize| {
+                note_boxes
+                    .iter()
+                    .any(|(rows, left, right)| rows.contains(&row) && (*left..=*right).contains(&x))
+            };
+            let mut shaded = original.clone();
+            for x in left..=right {
+                let blank =
+                    original[x] == ' ' || original[x] == glyph || shades.contains(&original[x]);
+                let inside_label = x > 0
+                    && x + 1 < original.len()
+                    && is_text(original[x - 1])
+                    && is_text(original[x + 1]);
+                if blank && !inside_label && !in_note(x) {
+                    shaded[x] = glyph;
+                }
+            }
+            *line = rtrim(&shaded);
+        }
+    }
+}
+
+/// One participant's part of header row `row`: the box (or actor figure)
+/// rows, then, when the diagram has actors, a row of actor names.
+fn header_cell(
+    diagram: &SequenceDiagram,
+    layout: &DiagramLayout,
+    chars: BoxChars,
+    i: usize,
+    row: usize,
+) -> String {
+    let width = layout.participant_widths[i] as usize;
+    let participant = &diagram.participants[i];
+    if participant.kind == ParticipantKind::Actor {
+        if row < 3 {
+            return actor_figure_row(chars.actor[row], width);
+        }
+        let column = width + BOX_BORDER_WIDTH as usize;
+        let label = &layout.participant_labels[i];
+        let label_len = UnicodeWidthStr::width(label.as_str());
+        let pad = column.saturating_sub(label_len) / 2;
+        return format!("{}{}", " ".repeat(pad), label);
+    }
+    match row {
+        0 => format!(
+            "{}{}{}",
+            chars.top_left,
+            chars.horizontal.to_string().repeat(width),
+            chars.top_right
+        ),
+        1 => {
+            let label = &layout.participant_labels[i];
+            let label_len = UnicodeWidthStr::width(label.as_str()) as i32;
+            let pad = ((width as i32 - label_len) / 2).max(0) as usize;
+            let right_pad = width.saturating_sub(pad + label.chars().count());
+            format!(
+                "{}{}{}{}{}",
+                chars.vertical,
+                " ".repeat(pad),
+                label,
+                " ".repeat(right_pad),
+                chars.vertical
+            )
+        }
+        2 => {
+            let left = width / 2;
+            let right = width - left - 1;
+            format!(
+                "{}{}{}{}{}",
+                chars.bottom_left,
+                chars.horizontal.to_string().repeat(left),
+                chars.tee_down,
+                chars.horizontal.to_string().repeat(right),
+                chars.bottom_right
+            )
+        }
+        _ => {
+            let column = width + BOX_BORDER_WIDTH as usize;
+            format!("{}{}", " ".repeat(column / 2), chars.lifeline)
+        }
+    }
+}
+
+/// Draws the boxes of participants created at message `idx`, starting their
+/// lifelines there.
+fn render_created(
+    diagram: &SequenceDiagram,
+    idx: usize,
+    header_rows: usize,
+    layout: &mut DiagramLayout,
+    chars: BoxChars,
+    lines: &mut Vec<String>,
+) {
+    for participant in &diagram.participants {
+        if participant.created != Some(idx) {
+            continue;
+        }
+        let i = participant.index;
+        let box_width = layout.participant_widths[i] + BOX_BORDER_WIDTH;
+        let left = (layout.participant_centers[i] - box_width / 2).max(0) as usize;
+        for row in 0..header_rows {
+            let cell = header_cell(diagram, layout, chars, i, row);
+            let mut line = ensure_width(build_lifeline(layout, chars), left + cell.chars().count());
+            for (offset, ch) in cell.chars().enumerate() {
+                line[left + offset] = ch;
+            }
+            lines.push(rtrim(&line));
+        }
+        layout.alive[i] = true;
+    }
+}
+
+/// Ends the lifelines of participants destroyed at message `idx` with a
+/// cross.
+fn render_destroyed(
+    diagram: &SequenceDiagram,
+    idx: usize,
+    layout: &mut DiagramLayout,
+    chars: BoxChars,
+    lines: &mut Vec<String>,
+) {
+    let destroyed: Vec<usize> = diagram
+        .participants
+        .iter()
+        .filter(|participant| participant.destroyed == Some(idx))
+        .map(|participant| participant.index)
+        .collect();
+    if destroyed.is_empty() {
+        return;
+    }
+    let mut line = ensure_width(
+        build_lifeline(layout, chars),
+        (layout.total_width + 1) as usize,
+    );
+    for i in destroyed {
+        line[layout.participant_centers[i] as usize] = chars.destroyed;
+        layout.alive[i] = false;
+    }
+    lines.push(rtrim(&line));
+}
+
+/// Renders the notes placed before message `idx`, recording each box as
+/// (rows, left, right) so shading can leave it alone.
+fn render_notes(
+    diagram: &SequenceDiagram,
+    idx: usize,
+    layout: &DiagramLayout,
+    chars: BoxChars,
+    lines: &mut Vec<String>,
+    note_boxes: &mut Vec<(std::ops::Range<usize>, usize, usize)>,
+) {
+    for note in diagram.notes.iter().filter(|note| note.position == idx) {
+        for _ in 0..layout.message_spacing {
+            lines.push(build_lifeline(layout, chars));
+        }
+        let (left, right, text) = note_box(note, layout);
+        let (left, right) = (left as usize, right as usize);
+        let row = |fill: char, start: char, end: char, text: &str| {
+            let mut line = ensure_width(build_lifeline(layout, chars), right + 1);
+            line[left] = start;
+            for cell in &mut line[left + 1..right] {
+                *cell = fill;
+            }
+            line[right] = end;
+            for (offset, ch) in text.chars().enumerate() {
+                line[left + 1 + NOTE_PADDING as usize + offset] = ch;
+            }
+            rtrim(&line)
+        };
+        let first = lines.len();
+        lines.push(row(chars.horizontal, chars.top_left, chars.top_right, ""));
+        for text_line in &text {
+            lines.push(row(' ', chars.vertical, chars.vertical, text_line));
+        }
+        lines.push(row(
+            chars.horizontal,
+            chars.bottom_left,
+            chars.bottom_right,
+            "",
+        ));
+        note_boxes.push((first..lines.len(), left, right));
+    }
+}
+
+/// A note's box columns and its text split into rows. `over A` is centered on
+/// A's lifeline; `over A,B` runs from A's lifeline to B's, wrapping the text
+/// to fit and only growing for a word too long to wrap.
+fn note_box(note: &Note, layout: &DiagramLayout) -> (i32, i32, Vec<String>) {
+    let center = layout.participant_centers[note.first];
+    if note.first == note.last {
+        let inner = UnicodeWidthStr::width(note.text.as_str()) as i32 + 2 * NOTE_PADDING;
+        let left = (center - (inner + 2) / 2).max(0);
+        return (left, left + inner + 1, vec![note.text.clone()]);
+    }
+    let span = layout.participant_centers[note.last] - center - 1 - 2 * NOTE_PADDING;
+    let longest_word = note
+        .text
+        .split_whitespace()
+        .map(|word| UnicodeWidthStr::width(word) as i32)
+        .max()
+        .unwrap_or(0);
+    let width = span.max(longest_word);
+    let text = wrap_text(&note.text, width as usize);
+    (center, center + width + 2 * NOTE_PADDING + 1, text)
+}
+
+/// Greedy word wrap to `width` columns; a longer word gets a row to itself.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut rows: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let candidate = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if UnicodeWidthStr::width(candidate.as_str()) > width && !current.is_empty() {
+            rows.push(std::mem::replace(&mut current, word.to_string()));
+        } else {
+            current = candidate;
+        }
+    }
+    rows.push(current);
+    rows
 }
 
 fn build_line<F>(diagram: &SequenceDiagram, layout: &DiagramLayout, draw: F) -> String
@@ -400,12 +1458,18 @@ where
     out
 }
 
+fn actor_figure_row(figure: &str, width: usize) -> String {
+    let column = width + BOX_BORDER_WIDTH as usize;
+    let pad = (column / 2).saturating_sub(ACTOR_FIGURE_WIDTH as usize / 2);
+    format!("{}{}", " ".repeat(pad), figure.trim_end())
+}
+
 fn build_lifeline(layout: &DiagramLayout, chars: BoxChars) -> String {
     let mut line = vec![' '; (layout.total_width + 1) as usize];
-    for center in &layout.participant_centers {
+    for (center, alive) in layout.participant_centers.iter().zip(&layout.alive) {
         let idx = *center as usize;
-        if idx < line.len() {
-            line[idx] = chars.vertical;
+        if *alive && idx < line.len() {
+            line[idx] = chars.lifeline;
         }
     }
     rtrim(&line)
@@ -413,7 +1477,7 @@ fn build_lifeline(layout: &DiagramLayout, chars: BoxChars) -> String {
 
 fn render_message(
     message: &Message,
-    _diagram: &SequenceDiagram,
+    label: &str,
     layout: &DiagramLayout,
     chars: BoxChars,
 ) -> Vec<String> {
@@ -421,14 +1485,9 @@ fn render_message(
     let from = layout.participant_centers[message.from];
     let to = layout.participant_centers[message.to];
 
-    let mut label = message.label.clone();
-    if message.number > 0 {
-        label = format!("{}. {}", message.number, label);
-    }
-
     if !label.is_empty() {
         let start = i32::min(from, to) + LABEL_LEFT_MARGIN;
-        let label_width = UnicodeWidthStr::width(label.as_str()) as i32;
+        let label_width = UnicodeWidthStr::width(label) as i32;
         let mut line = build_lifeline(layout, chars).chars().collect::<Vec<char>>();
         let needed = (start + label_width + LABEL_BUFFER_SPACE) as usize;
         if line.len() < needed {
@@ -451,20 +1510,28 @@ fn render_message(
         chars.solid_line
     };
 
+    // Lifelines the message passes over are crossed rather than cut.
+    let cross = |current: char| {
+        if current == chars.lifeline {
+            chars.cross
+        } else {
+            merge(current, style)
+        }
+    };
     if from < to {
         line[from as usize] = chars.tee_right;
         for i in (from + 1)..to {
-            line[i as usize] = style;
+            line[i as usize] = cross(line[i as usize]);
         }
         if (to - 1) >= 0 {
-            line[(to - 1) as usize] = chars.arrow_right;
+            line[(to - 1) as usize] = arrow_head(message.arrow_type, chars, true);
         }
-        line[to as usize] = chars.vertical;
+        line[to as usize] = chars.lifeline;
     } else {
-        line[to as usize] = chars.vertical;
-        line[(to + 1) as usize] = chars.arrow_left;
+        line[to as usize] = chars.lifeline;
+        line[(to + 1) as usize] = arrow_head(message.arrow_type, chars, false);
         for i in (to + 2)..from {
-            line[i as usize] = style;
+            line[i as usize] = cross(line[i as usize]);
         }
         line[from as usize] = chars.tee_left;
     }
@@ -472,9 +1539,19 @@ fn render_message(
     lines
 }
 
+fn arrow_head(arrow_type: ArrowType, chars: BoxChars, pointing_right: bool) -> char {
+    match (arrow_type, pointing_right) {
+        (ArrowType::Cross, _) => chars.cross_end,
+        (ArrowType::AsyncOpen, true) => chars.open_arrow_right,
+        (ArrowType::AsyncOpen, false) => chars.open_arrow_left,
+        (_, true) => chars.arrow_right,
+        (_, false) => chars.arrow_left,
+    }
+}
+
 fn render_self_message(
     message: &Message,
-    _diagram: &SequenceDiagram,
+    label: &str,
     layout: &DiagramLayout,
     chars: BoxChars,
 ) -> Vec<String> {
@@ -482,18 +1559,13 @@ fn render_self_message(
     let center = layout.participant_centers[message.from] as usize;
     let width = layout.self_message_width as usize;
 
-    let mut label = message.label.clone();
-    if message.number > 0 {
-        label = format!("{}. {}", message.number, label);
-    }
-
     if !label.is_empty() {
         let mut line = ensure_width(
             build_lifeline(layout, chars),
             layout.total_width as usize + width + 1,
         );
         let start = center + LABEL_LEFT_MARGIN as usize;
-        let label_width = UnicodeWidthStr::width(label.as_str()) as usize;
+        let label_width = UnicodeWidthStr::width(label);
         let needed = start + label_width + LABEL_BUFFER_SPACE as usize;
         if line.len() < needed {
             line.resize(needed, ' ');
@@ -530,8 +1602,8 @@ fn render_self_message(
         build_lifeline(layout, chars),
         layout.total_width as usize + width + 1,
     );
-    l3[center] = chars.vertical;
-    l3[center + 1] = chars.arrow_left;
+    l3[center] = chars.lifeline;
+    l3[center + 1] = arrow_head(message.arrow_type, chars, false);
     for i in 2..(width - 1) {
         l3[center + i] = chars.horizontal;
     }
@@ -566,11 +1638,25 @@ impl SequenceDiagram {
     pub fn render(&self, config: &Config) -> Result<String, String> {
         render(self, config)
     }
+
+    /// Messages in order as `(from_label, to_label, label, arrow_type)`,
+    /// with participants resolved to their display labels.
+    pub fn messages_resolved(&self) -> impl Iterator<Item = (&str, &str, &str, ArrowType)> {
+        self.messages.iter().map(|message| {
+            (
+                self.participants[message.from].label.as_str(),
+                self.participants[message.to].label.as_str(),
+                message.label.as_str(),
+                message.arrow_type,
+            )
+        })
+    }
 }
 
 impl Diagram for SequenceDiagram {
-    fn parse(&mut self, input: &str, _config: &Config) -> Result<(), String> {
-        SequenceDiagram::parse(self, input)
+    fn parse(&mut self, input: &str, config: &Config) -> Result<(), String> {
+        *self = parse_with_config(input, config)?;
+        Ok(())
     }
 
     fn render(&self, config: &Config) -> Result<String, String> {
@@ -580,4 +1666,8 @@ impl Diagram for SequenceDiagram {
     fn diagram_type(&self) -> &'static str {
         "sequence"
     }
+
+    fn render_to(&self, config: &Config, writer: &mut dyn std::io::Write) -> Result<(), String> {
+        render_to(self, config, writer)
+    }
 }
@@ -0,0 +1,104 @@
+use crate::diagram::{Config, Diagram, remove_comments, split_lines};
+use crate::graph::{GraphDiagram, GraphProperties, NodeShape, direction_statement};
+use regex::Regex;
+use std::sync::LazyLock;
+
+const STATE_DIAGRAM_KEYWORD: &str = "stateDiagram";
+/// Pseudo-state written `[*]`: a start point as a source, an end point as a
+/// target.
+const TERMINAL_STATE: &str = "[*]";
+const START_NODE: &str = "[*]start";
+const END_NODE: &str = "[*]end";
+
+static TRANSITION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\S+)\s*-->\s*(\S+)\s*(?::\s*(.*))?$").unwrap());
+static STATE_ALIAS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^state\s+"([^"]*)"\s+as\s+(\S+)$"#).unwrap());
+static DESCRIPTION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^([^\s:]+)\s*:\s*(.+)$").unwrap());
+static STATE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[^\s:\[\]]+$").unwrap());
+
+/// A `stateDiagram-v2`, laid out and drawn by the graph renderer: states
+/// become boxes, transitions labelled edges, and `[*]` a start or end marker.
+#[derive(Debug, Clone, Default)]
+pub struct StateDiagram {
+    graph: GraphDiagram,
+}
+
+pub fn is_state_diagram(input: &str) -> bool {
+    for line in input.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("%%") {
+            continue;
+        }
+        return trimmed == STATE_DIAGRAM_KEYWORD || trimmed == "stateDiagram-v2";
+    }
+    false
+}
+
+fn parse_properties(input: &str, config: &Config) -> Result<GraphProperties, String> {
+    let lines = remove_comments(&split_lines(input.trim()));
+    let Some(header) = lines.first() else {
+        return Err("no content found".to_string());
+    };
+    if !is_state_diagram(header) {
+        return Err(format!("expected \"{}\" keyword", STATE_DIAGRAM_KEYWORD));
+    }
+
+    let mut properties = GraphProperties::new("cli", config);
+    properties.graph_direction = "TD".to_string();
+    for (idx, line) in lines.iter().enumerate().skip(1) {
+        let trimmed = line.trim();
+        if let Some(direction) = direction_statement(trimmed) {
+            properties.graph_direction = direction;
+        } else if let Some(caps) = TRANSITION_RE.captures(trimmed) {
+            let from = state_node(&mut properties, &caps[1], START_NODE, NodeShape::Start);
+            let to = state_node(&mut properties, &caps[2], END_NODE, NodeShape::End);
+            let label = caps.get(3).map_or("", |m| m.as_str().trim());
+            properties.add_edge(&from, &to, label);
+        } else if let Some(caps) = STATE_ALIAS_RE.captures(trimmed) {
+            properties.add_node(&caps[2], &caps[1]);
+        } else if let Some(caps) = DESCRIPTION_RE.captures(trimmed) {
+            properties.add_node(&caps[1], caps[2].trim());
+        } else if STATE_RE.is_match(trimmed) {
+            properties.add_node(trimmed, trimmed);
+        } else {
+            return Err(format!("line {}: invalid syntax: \"{}\"", idx + 1, trimmed));
+        }
+    }
+    Ok(properties)
+}
+
+/// Resolves a transition endpoint to its node name, turning `[*]` into the
+/// shared start or end marker.
+fn state_node(
+    properties: &mut GraphProperties,
+    name: &str,
+    marker: &str,
+    shape: NodeShape,
+) -> String {
+    if name != TERMINAL_STATE {
+        return name.to_string();
+    }
+    properties.node_shapes.insert(marker.to_string(), shape);
+    marker.to_string()
+}
+
+impl Diagram for StateDiagram {
+    fn parse(&mut self, input: &str, config: &Config) -> Result<(), String> {
+        self.graph = GraphDiagram::from_properties(parse_properties(input, config)?);
+        Ok(())
+    }
+
+    fn render(&self, config: &Config) -> Result<String, String> {
+        self.graph.render(config)
+    }
+
+    fn diagram_type(&self) -> &'static str {
+        "state"
+    }
+
+    fn render_to(&self, config: &Config, writer: &mut dyn std::io::Write) -> Result<(), String> {
+        self.graph.render_to(config, writer)
+    }
+}
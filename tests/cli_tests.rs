@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn binary() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_console-mermaid"))
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("console-mermaid-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_output_flag_writes_file() {
+    let input = temp_path("output-in.mmd");
+    let output = temp_path("output-out.txt");
+    std::fs::write(&input, "graph LR\nA --> B").unwrap();
+    std::fs::write(
+        &output,
+        "stale contents that must be truncated\n".repeat(50),
+    )
+    .unwrap();
+
+    let run = binary()
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stderr)
+    );
+    assert!(run.stdout.is_empty());
+    let stdout = binary().arg(&input).output().unwrap().stdout;
+    assert_eq!(std::fs::read(&output).unwrap(), stdout);
+    assert!(stdout.ends_with(b"\n"));
+
+    let missing_dir = temp_path("no-such-dir").join("out.txt");
+    let run = binary()
+        .arg(&input)
+        .arg("--output")
+        .arg(&missing_dir)
+        .output()
+        .unwrap();
+    assert_eq!(run.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&run.stderr).starts_with("failed to write"));
+
+    std::fs::remove_file(&input).ok();
+    std::fs::remove_file(&output).ok();
+}
+
+#[test]
+fn test_multiple_inputs_render_in_turn() {
+    let first = temp_path("multi-a.mmd");
+    let broken = temp_path("multi-b.mmd");
+    let second = temp_path("multi-c.mmd");
+    std::fs::write(&first, "graph LR\nAlpha --> Beta").unwrap();
+    std::fs::write(&broken, "graph XY\nA --> B").unwrap();
+    std::fs::write(&second, "sequenceDiagram\n    Ann->>Bo: hi").unwrap();
+
+    let run = binary()
+        .arg(&first)
+        .arg(&broken)
+        .arg(&second)
+        .output()
+        .unwrap();
+    assert_eq!(run.status.code(), Some(1));
+    let stdout = String::from_utf8(run.stdout).unwrap();
+    let headers: Vec<&str> = stdout.lines().filter(|l| l.starts_with("=== ")).collect();
+    assert_eq!(
+        headers,
+        [&first, &broken, &second].map(|p| format!("=== {} ===", p.display()))
+    );
+    let after_first = stdout.split("=== ").nth(1).unwrap();
+    assert!(after_first.contains("Alpha") && after_first.contains("Beta"));
+    assert!(stdout.rsplit("=== ").next().unwrap().contains("Ann"));
+    let stderr = String::from_utf8(run.stderr).unwrap();
+    assert!(
+        stderr.starts_with(&format!("{}: unsupported graph type", broken.display())),
+        "{}",
+        stderr
+    );
+
+    let run = binary().arg(&first).arg(&second).output().unwrap();
+    assert!(run.status.success());
+
+    for path in [first, broken, second] {
+        std::fs::remove_file(path).ok();
+    }
+}
+
+#[test]
+fn test_trailing_newline_is_exactly_one() {
+    for (name, source) in [
+        ("newline-graph.mmd", "graph LR\nA --> B"),
+        ("newline-seq.mmd", "sequenceDiagram\n    Alice->>Bob: Hi"),
+    ] {
+        let input = temp_path(name);
+        std::fs::write(&input, source).unwrap();
+
+        let stdout = binary().arg(&input).output().unwrap().stdout;
+        assert!(stdout.ends_with(b"\n"), "{:?}", stdout);
+        assert!(!stdout.ends_with(b"\n\n"), "{:?}", stdout);
+
+        let trimmed = binary()
+            .arg("--no-trailing-newline")
+            .arg(&input)
+            .output()
+            .unwrap()
+            .stdout;
+        assert_eq!(trimmed, stdout[..stdout.len() - 1]);
+        let _ = std::fs::remove_file(&input);
+    }
+}
+
+#[test]
+fn test_markdown_input_renders_each_mermaid_block() {
+    let input = temp_path("doc.md");
+    std::fs::write(
+        &input,
+        "# Doc\n\n```mermaid\ngraph LR\nAlpha --> Beta\n```\n\ntext\n\n```mermaid\nsequenceDiagram\n    Ann->>Bo: hi\n```\n",
+    )
+    .unwrap();
+
+    let run = binary().arg(&input).output().unwrap();
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stderr)
+    );
+    let stdout = String::from_utf8(run.stdout).unwrap();
+    assert!(
+        stdout.contains("Alpha") && stdout.contains("Ann"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("# Doc"), "{}", stdout);
+
+    let run = binary()
+        .arg("--markdown")
+        .arg("--check")
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(run.stdout).unwrap(), "OK\n");
+
+    std::fs::remove_file(&input).ok();
+}
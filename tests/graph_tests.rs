@@ -1,7 +1,11 @@
 mod graph_testutil;
 
-use console_mermaid::diagram::Config;
-use console_mermaid::render_diagram;
+use console_mermaid::diagram::{
+    ArrowGlyphs, AsciiCornerStyle, CharSet, Config, EdgeLabelPosition, LabelCenterBias,
+    NodeLabelAlign, OutputFormat, SelfLoopSide,
+};
+use console_mermaid::graph::junctions;
+use console_mermaid::{graph, render_diagram, render_diagram_with_node_map};
 use std::fs;
 use std::path::Path;
 
@@ -73,3 +77,1251 @@ fn test_graph_use_ascii_config() {
             || unicode_output.contains('│')
     );
 }
+
+fn label_margins(output: &str, label: &str) -> (usize, usize) {
+    let line = output
+        .lines()
+        .find(|line| line.contains(label))
+        .expect("label row");
+    let chars: Vec<char> = line.chars().collect();
+    let start = line[..line.find(label).unwrap()].chars().count();
+    let end = start + label.chars().count();
+    let left = chars[..start]
+        .iter()
+        .rev()
+        .take_while(|c| **c == ' ')
+        .count();
+    let right = chars[end..].iter().take_while(|c| **c == ' ').count();
+    (left, right)
+}
+
+#[test]
+fn test_label_centering_parity() {
+    // Each pair shares a column, so the shorter label sits in a box sized for
+    // the longer one; cover every even/odd combination of label and interior.
+    let cases = [
+        ("ab", "abcd"),
+        ("ab", "abcde"),
+        ("abc", "abcde"),
+        ("abc", "abcdef"),
+    ];
+    for bias in [LabelCenterBias::Left, LabelCenterBias::Right] {
+        let mut config = Config::default_config();
+        config.use_ascii = true;
+        config.label_center_bias = Some(bias);
+        for (short, long) in cases {
+            let input = format!("graph LR\nroot --> {}\nroot --> {}", short, long);
+            let output = render_diagram(&input, &config).expect("render diagram");
+            let (left, right) = label_margins(&output, short);
+            assert!(left.abs_diff(right) <= 1, "{} in {}", short, output);
+            match bias {
+                LabelCenterBias::Left => assert!(left <= right, "{}", output),
+                LabelCenterBias::Right => assert!(left >= right, "{}", output),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_ascii_rounded_corner_style() {
+    let input = "graph LR\nA --> B\nA --> C";
+    let mut config = Config::default_config();
+    config.use_ascii = true;
+    let plus = render_diagram(input, &config).expect("render plus");
+    assert!(!plus.contains('.') && !plus.contains('\''));
+
+    config.ascii_corner_style = AsciiCornerStyle::Rounded;
+    let rounded = render_diagram(input, &config).expect("render rounded");
+    assert!(rounded.starts_with(".---."), "{}", rounded);
+    assert!(rounded.lines().any(|line| line.starts_with("'---'")));
+    // The routed bend from A down to C turns with a bottom corner glyph.
+    assert!(rounded.contains("  '------>"), "{}", rounded);
+}
+
+#[test]
+fn test_subgraph_membership_listing_moves_existing_node() {
+    let input = "graph LR\nA --> B\nsubgraph S\nA\nend";
+    let output = render_diagram(input, &Config::default_config()).expect("render");
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines[1].contains('S'), "{}", output);
+
+    // A sits inside the subgraph border while B stays outside of it.
+    let a_row = lines.iter().find(|line| line.contains(" A ")).unwrap();
+    let a_col = a_row.chars().position(|c| c == 'A').unwrap();
+    let b_col = a_row.chars().position(|c| c == 'B').unwrap();
+    let border_end = lines[0].chars().position(|c| c == '┐').unwrap();
+    assert!(a_col < border_end && b_col > border_end, "{}", output);
+}
+
+#[test]
+fn test_svg_output() {
+    let input = "graph LR\nA[Start] -->|go| B[End]\nA --> C";
+    let mut config = Config::default_config();
+    config.output_format = OutputFormat::Svg;
+    let svg = render_diagram(input, &config).expect("render svg");
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert_eq!(svg.matches("<rect").count(), 3);
+    assert_eq!(svg.matches("<polyline").count(), 2);
+    for text in [">Start<", ">End<", ">C<", ">go<"] {
+        assert!(svg.contains(text), "missing {} in {}", text, svg);
+    }
+
+    let sequence = render_diagram("sequenceDiagram\nA->>B: hi", &config);
+    assert!(sequence.is_err());
+}
+
+#[test]
+fn test_svg_edge_markers_follow_edge_end() {
+    let mut config = Config::default_config();
+    config.output_format = OutputFormat::Svg;
+    let svg = render_diagram("graph LR\nA --> B\nB --x C\nC --o D", &config).expect("render svg");
+    for marker in ["arrow", "cross", "circle"] {
+        assert!(
+            svg.contains(&format!("<marker id=\"{}\"", marker)),
+            "{}",
+            svg
+        );
+        assert_eq!(
+            svg.matches(&format!("marker-end=\"url(#{})\"", marker))
+                .count(),
+            1,
+            "{}",
+            svg
+        );
+    }
+
+    let svg = render_diagram("graph LR\nA --> B\nB --- D", &config).expect("render svg");
+    assert_eq!(svg.matches("<polyline").count(), 2, "{}", svg);
+    assert_eq!(svg.matches("marker-end").count(), 1, "{}", svg);
+
+    // Class relations draw their head on whichever end the operator marks.
+    let svg = render_diagram(
+        "classDiagram\nAnimal <|-- Dog\nA *-- B\nC o-- D\nE --> F",
+        &config,
+    )
+    .expect("render svg");
+    for (attr, marker) in [
+        ("marker-start", "triangle"),
+        ("marker-start", "filled-diamond"),
+        ("marker-start", "diamond"),
+        ("marker-end", "arrow"),
+    ] {
+        let expected = format!("{}=\"url(#{})\"", attr, marker);
+        assert_eq!(svg.matches(&expected).count(), 1, "{}", svg);
+    }
+    assert_eq!(svg.matches("marker-end").count(), 1, "{}", svg);
+
+    // ER relationships draw a crow's foot at each end instead of an arrow.
+    let svg = render_diagram(
+        "erDiagram\nCUSTOMER ||--o{ ORDER : places\nORDER }|..o| INVOICE : bills",
+        &config,
+    )
+    .expect("render svg");
+    for expected in [
+        "marker-start=\"url(#one)\" marker-end=\"url(#zero-many)\"",
+        "marker-start=\"url(#one-many)\" marker-end=\"url(#zero-one)\"",
+    ] {
+        assert!(svg.contains(expected), "{}", svg);
+    }
+    assert!(!svg.contains("url(#arrow)"), "{}", svg);
+}
+
+#[test]
+fn test_json_layout_matches_text_grid() {
+    let input = "graph LR\nA[Start] -->|go| B[End]";
+    let mut config = Config::default_config();
+    let text = render_diagram(input, &config).expect("render text");
+    config.output_format = OutputFormat::Json;
+    let json = render_diagram(input, &config).expect("render json");
+    let layout: serde_json::Value = serde_json::from_str(&json).expect("valid json");
+
+    let rows: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+    let nodes = layout["nodes"].as_array().unwrap();
+    assert_eq!(nodes.len(), 2);
+    for node in nodes {
+        let x = node["x"].as_u64().unwrap() as usize;
+        let y = node["y"].as_u64().unwrap() as usize;
+        let width = node["width"].as_u64().unwrap() as usize;
+        assert_eq!(rows[y][x], '┌');
+        assert_eq!(rows[y][x + width - 1], '┐');
+        let label = node["label"].as_str().unwrap();
+        assert!(rows[y + 2].iter().collect::<String>().contains(label));
+    }
+
+    let edges = layout["edges"].as_array().unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0]["from"], "A");
+    assert_eq!(edges[0]["to"], "B");
+    assert_eq!(edges[0]["label"], "go");
+    assert!(edges[0]["points"].as_array().unwrap().len() >= 2);
+}
+
+#[test]
+fn test_merge_bidirectional_edges() {
+    let input = "graph LR\nA --> B\nB --> A";
+    let mut config = Config::default_config();
+    config.merge_bidirectional = true;
+    let output = render_diagram(input, &config).expect("render");
+    let row = output.lines().find(|line| line.contains(" A ")).unwrap();
+    assert!(row.contains("│ A │◄───►│ B │"), "{}", output);
+
+    config.output_format = OutputFormat::Json;
+    let json = render_diagram(input, &config).expect("render json");
+    let layout: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(layout["edges"].as_array().unwrap().len(), 1);
+    assert_eq!(layout["edges"][0]["bidirectional"], true);
+
+    config.output_format = OutputFormat::Svg;
+    let svg = render_diagram(input, &config).expect("render svg");
+    assert!(
+        svg.contains("marker-start=\"url(#arrow)\" marker-end=\"url(#arrow)\""),
+        "{}",
+        svg
+    );
+
+    // A label on each side keeps the pair apart rather than dropping one.
+    config.output_format = OutputFormat::Unicode;
+    let output = render_diagram("graph LR\nA -->|go| B\nB -->|back| A", &config).expect("render");
+    assert!(
+        output.contains("go") && output.contains("back"),
+        "{}",
+        output
+    );
+    let output = render_diagram("graph LR\nA --> B\nB -->|back| A", &config).expect("render");
+    assert!(
+        output.contains("back") && output.contains("◄"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_edge_lane_padding_controls_routing_space() {
+    let input = "graph LR\nA --> B\nB --> C\nC --> A";
+    let mut config = Config::default_config();
+    config.edge_lane_padding = Some(0);
+    let tight = render_diagram(input, &config).expect("render");
+    config.edge_lane_padding = Some(6);
+    let wide = render_diagram(input, &config).expect("render");
+    let height = |output: &str| output.lines().count();
+    let width = |output: &str| {
+        output
+            .lines()
+            .map(|line| line.trim_end().chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    assert!(
+        height(&wide) + width(&wide) > height(&tight) + width(&tight),
+        "tight:\n{}\nwide:\n{}",
+        tight,
+        wide
+    );
+
+    config.edge_lane_padding = Some(-1);
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_cycle_renders_every_edge() {
+    for input in [
+        "graph TD\nA --> B\nB --> C\nC --> A",
+        "graph LR\nB --> C\nA --> B\nC --> A",
+    ] {
+        let mut config = Config::default_config();
+        let output = render_diagram(input, &config).expect("render");
+        let heads = output.matches(['▲', '▼', '◄', '►']).count();
+        assert_eq!(heads, 3, "{}", output);
+
+        config.output_format = OutputFormat::Json;
+        let json = render_diagram(input, &config).expect("render json");
+        let layout: serde_json::Value = serde_json::from_str(&json).unwrap();
+        for edge in layout["edges"].as_array().unwrap() {
+            assert!(edge["points"].as_array().unwrap().len() >= 2, "{}", json);
+        }
+    }
+}
+
+#[test]
+fn test_grid_overlay_marks_cell_boundaries() {
+    let input = "graph LR\nA --> B\nA --> C";
+    let mut config = Config::default_config();
+    let plain = render_diagram(input, &config).expect("render");
+    config.grid_overlay = true;
+    let overlay = render_diagram(input, &config).expect("render");
+
+    let plain_rows: Vec<Vec<char>> = plain.lines().map(|l| l.chars().collect()).collect();
+    let overlay_rows: Vec<Vec<char>> = overlay.lines().map(|l| l.chars().collect()).collect();
+    assert_eq!(plain_rows.len(), overlay_rows.len());
+    let mut dots = Vec::new();
+    for (y, (p, o)) in plain_rows.iter().zip(&overlay_rows).enumerate() {
+        for (x, (pc, oc)) in p.iter().zip(o).enumerate() {
+            if pc != oc {
+                assert_eq!((*pc, *oc), (' ', '·'));
+                dots.push((x, y));
+            }
+        }
+    }
+    assert!(dots.len() > 4, "{}", overlay);
+    // The drawing's left edge is the first column boundary.
+    assert!(dots.iter().any(|d| d.0 == 0), "{}", overlay);
+    // Node interiors stay blank.
+    assert!(
+        overlay.lines().nth(1).unwrap().starts_with("│   │"),
+        "{}",
+        overlay
+    );
+
+    config.use_ascii = true;
+    let ascii = render_diagram(input, &config).expect("render");
+    assert!(ascii.is_ascii(), "{}", ascii);
+    let plain_ascii = render_diagram(input, &Config::new_test_config(true, "cli")).unwrap();
+    let marked = ascii
+        .chars()
+        .zip(plain_ascii.chars())
+        .filter(|(o, p)| o != p)
+        .collect::<Vec<_>>();
+    assert_eq!(marked.len(), dots.len(), "{}", ascii);
+    assert!(marked.iter().all(|pair| *pair == ('.', ' ')), "{}", ascii);
+}
+
+#[test]
+fn test_isolated_nodes_follow_primary_axis() {
+    let config = Config::default_config();
+    let lr = render_diagram("graph LR\nA\nB\nC", &config).expect("render LR");
+    let row = lr.lines().find(|line| line.contains(" A ")).unwrap();
+    assert!(row.contains(" B ") && row.contains(" C "), "{}", lr);
+    assert_eq!(lr.matches('┌').count(), 3);
+
+    let td = render_diagram("graph TD\nA\nB\nC", &config).expect("render TD");
+    assert!(
+        td.lines().all(|line| line.matches('┌').count() <= 1),
+        "{}",
+        td
+    );
+    assert_eq!(td.matches('┌').count(), 3);
+
+    let empty = render_diagram("graph LR\n", &config);
+    assert_eq!(empty.unwrap_err(), "graph has no nodes");
+}
+
+#[test]
+fn test_self_loop_size_and_side() {
+    let extent = |output: &str| {
+        let width = output
+            .lines()
+            .map(|line| line.trim_end().chars().count())
+            .max()
+            .unwrap_or(0);
+        (width, output.trim_end().lines().count())
+    };
+    let mut config = Config::default_config();
+    config.self_loop_side = Some(SelfLoopSide::Bottom);
+    let small = render_diagram("graph LR\nA --> A", &config).expect("render");
+    config.self_loop_size = 2;
+    let large = render_diagram("graph LR\nA --> A", &config).expect("render");
+
+    // Leaves through the bottom border and comes back into the right side.
+    let lines: Vec<&str> = large.lines().collect();
+    assert!(lines[2].starts_with("│ A │◄"), "{}", large);
+    assert!(lines[4].starts_with("└─┬─┘"), "{}", large);
+    // The box is 5x5 cells; each loop step adds a lane on both axes.
+    let (small_w, small_h) = extent(&small);
+    let (large_w, large_h) = extent(&large);
+    assert_eq!((small_w, small_h), (7, 7), "{}", small);
+    assert!(large_w > small_w && large_h > small_h, "{}", large);
+    for line in &lines[5..] {
+        assert!(
+            line.starts_with("  │") || line.starts_with("  └"),
+            "{}",
+            large
+        );
+    }
+
+    config.self_loop_size = 0;
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_inline_node_style_overrides_class() {
+    let input = "graph LR\nclassDef warm fill:#fc0,color:#00f\nA:::warm --> B\nstyle A color:#f00\nstyle B fill:#0f0";
+    let mut config = Config::default_config();
+    config.style_type = "html".to_string();
+    let html = render_diagram(input, &config).expect("render html");
+    assert!(
+        html.contains("<span style='color: #f00'>A</span>"),
+        "{}",
+        html
+    );
+    assert!(!html.contains("#00f"), "{}", html);
+
+    config.output_format = OutputFormat::Svg;
+    let svg = render_diagram(input, &config).expect("render svg");
+    assert!(svg.contains("fill=\"#fc0\""), "{}", svg);
+    assert!(svg.contains("fill=\"#0f0\""), "{}", svg);
+}
+
+#[test]
+fn test_class_statement_assigns_class_to_each_node() {
+    let input = "graph LR\nA --> B\nB --> C\nC --> D\nclassDef hot color:#f00\nclass A,B, C hot";
+    let mut config = Config::default_config();
+    config.style_type = "html".to_string();
+    let html = render_diagram(input, &config).expect("render html");
+    for name in ["A", "B", "C"] {
+        let span = format!("<span style='color: #f00'>{}</span>", name);
+        assert!(html.contains(&span), "{}", html);
+    }
+    assert!(
+        !html.contains("<span style='color: #f00'>D</span>"),
+        "{}",
+        html
+    );
+}
+
+#[test]
+fn test_link_style_targets_edge_by_index() {
+    let input = "graph LR\nA --> B\nB --> C\nlinkStyle 1 stroke:#f00,stroke-width:2px";
+    let mut config = Config::default_config();
+    let output = render_diagram(input, &config).expect("render");
+    let row = output.lines().find(|line| line.contains(" A ")).unwrap();
+    assert!(row.contains("│ A ├────►│ B ┝━━━━►│ C │"), "{}", output);
+
+    config.style_type = "html".to_string();
+    let html = render_diagram("graph LR\nA --> B\nlinkStyle default stroke:#0a0", &config)
+        .expect("render html");
+    assert!(
+        html.contains("<span style='color: #0a0'>─</span>"),
+        "{}",
+        html
+    );
+
+    let err = render_diagram("graph LR\nA --> B\nlinkStyle 3 stroke:#f00", &config).unwrap_err();
+    assert_eq!(err, "linkStyle index 3 is out of range: graph has 1 edges");
+}
+
+#[test]
+fn test_node_map_matches_drawn_boxes() {
+    let input = "graph TD\nA[Start] --> B[Finish]\nA --> C";
+    let config = Config::default_config();
+    let (output, rects) = render_diagram_with_node_map(input, &config).expect("render");
+    assert_eq!(output, render_diagram(input, &config).unwrap());
+    assert_eq!(rects.len(), 3);
+
+    let rows: Vec<Vec<char>> = output.lines().map(|l| l.chars().collect()).collect();
+    for rect in &rects {
+        let (top, left) = (rect.row, rect.col);
+        let (bottom, right) = (top + rect.height - 1, left + rect.width - 1);
+        assert_eq!(rows[top][left], '┌', "{:?}", rect);
+        assert_eq!(rows[top][right], '┐', "{:?}", rect);
+        assert_eq!(rows[bottom][left], '└', "{:?}", rect);
+        assert_eq!(rows[bottom][right], '┘', "{:?}", rect);
+    }
+    let start = rects.iter().find(|r| r.id == "A").unwrap();
+    let label_row: String = rows[start.row + 2].iter().collect();
+    assert!(label_row.contains("Start"));
+
+    assert!(render_diagram_with_node_map("sequenceDiagram\nA->>B: hi", &config).is_err());
+}
+
+#[test]
+fn test_declared_roots_keep_source_order() {
+    let input = "graph LR\nFirst\nSecond\nSecond --> Shared\nFirst --> Shared\nSecond --> Other";
+    let config = Config::default_config();
+    let (_, rects) = render_diagram_with_node_map(input, &config).expect("render");
+    let row_of = |id: &str| rects.iter().find(|r| r.id == id).unwrap().row;
+    assert!(row_of("First") < row_of("Second"));
+
+    let swapped = "graph LR\nSecond\nFirst\nSecond --> Shared\nFirst --> Shared\nSecond --> Other";
+    let (_, rects) = render_diagram_with_node_map(swapped, &config).expect("render");
+    let row_of = |id: &str| rects.iter().find(|r| r.id == id).unwrap().row;
+    assert!(row_of("Second") < row_of("First"));
+}
+
+#[test]
+fn test_front_matter_title() {
+    let config = Config::default_config();
+    let input = "---\ntitle: \"Build\"\n---\ngraph LR\nCompile --> Link";
+    let output = render_diagram(input, &config).expect("render");
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0].trim(), "Build");
+    assert!(lines[1].trim().is_empty());
+    let body = render_diagram("graph LR\nCompile --> Link", &config).unwrap();
+    assert!(output.ends_with(&body), "{}", output);
+    let width = body
+        .lines()
+        .map(|l| l.trim_end().chars().count())
+        .max()
+        .unwrap();
+    let indent = lines[0].len() - lines[0].trim_start().len();
+    assert_eq!(indent, (width - "Build".len()) / 2);
+
+    let trailing = render_diagram("graph LR\nA --> B\n---\nC --> D", &config).unwrap();
+    assert!(!trailing.contains('C'));
+    assert!(render_diagram("---\ntitle: Open\ngraph LR\nA --> B", &config).is_err());
+}
+
+#[test]
+fn test_quoted_edge_labels() {
+    let mut config = Config::default_config();
+    config.output_format = OutputFormat::Json;
+    let label_of = |input: &str| {
+        let json = render_diagram(input, &config).expect("render");
+        let layout: serde_json::Value = serde_json::from_str(&json).unwrap();
+        layout["edges"][0]["label"].as_str().unwrap().to_string()
+    };
+    assert_eq!(label_of("graph LR\nA -->|\"a|b\"| B"), "a|b");
+    assert_eq!(label_of("graph LR\nA -->|\"key: value\"| B"), "key: value");
+    assert_eq!(label_of("graph LR\nA -->|\" padded \"| B"), " padded ");
+    assert_eq!(label_of("graph LR\nA -->| trimmed | B"), "trimmed");
+
+    let text = render_diagram("graph LR\nA -->|\"a|b\"| B", &Config::default_config()).unwrap();
+    assert!(text.contains("a|b") && !text.contains('"'), "{}", text);
+}
+
+#[test]
+fn test_duplicate_edges_collapse() {
+    let mut config = Config::default_config();
+    let once = render_diagram("graph LR\nA --> B", &config).unwrap();
+    let twice = render_diagram("graph LR\nA --> B\nA --> B", &config).unwrap();
+    assert_eq!(once, twice);
+
+    config.output_format = OutputFormat::Json;
+    let json = render_diagram("graph LR\nA -->|x| B\nA -->|x| B", &config).unwrap();
+    let layout: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(layout["edges"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_parallel_labelled_edges_take_separate_routes() {
+    for direction in ["LR", "TD"] {
+        let input = format!("graph {}\nA -->|yes| B\nA -->|no| B", direction);
+        let mut config = Config::default_config();
+        let text = render_diagram(&input, &config).unwrap();
+        assert!(text.contains("yes") && text.contains("no"), "{}", text);
+
+        config.output_format = OutputFormat::Json;
+        let json = render_diagram(&input, &config).unwrap();
+        let layout: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let edges = layout["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 2);
+        let points = |edge: &serde_json::Value| {
+            edge["points"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| (p["x"].as_i64().unwrap(), p["y"].as_i64().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let (first, second) = (points(&edges[0]), points(&edges[1]));
+        assert!(
+            second.iter().all(|p| !first.contains(p)),
+            "{}: {:?} overlaps {:?}",
+            direction,
+            second,
+            first
+        );
+    }
+}
+
+#[test]
+fn test_node_label_align() {
+    let input = "graph LR\nA[Quantity<br>7]";
+    let second_line = |align| {
+        let config = Config::builder().node_label_align(align).build().unwrap();
+        let output = render_diagram(input, &config).unwrap();
+        output.lines().nth(3).unwrap().trim_end().to_string()
+    };
+    assert_eq!(second_line(NodeLabelAlign::Left), "│ 7        │");
+    assert_eq!(second_line(NodeLabelAlign::Center), "│    7     │");
+    assert_eq!(second_line(NodeLabelAlign::Right), "│        7 │");
+
+    let config = Config::builder()
+        .node_label_align(NodeLabelAlign::Right)
+        .build()
+        .unwrap();
+    let output = render_diagram(input, &config).unwrap();
+    assert_eq!(output.lines().nth(2).unwrap().trim_end(), "│ Quantity │");
+}
+
+#[test]
+fn test_diagonal_child_arrowhead_follows_last_segment() {
+    let input = "graph TD\nA --> B\nA --> C";
+    let output = render_diagram(input, &Config::default_config()).unwrap();
+    let rows: Vec<Vec<char>> = output.lines().map(|l| l.chars().collect()).collect();
+    let c_top = rows
+        .iter()
+        .position(|row| row.iter().collect::<String>().contains("│ C │"))
+        .unwrap()
+        - 2;
+    let c_col = rows[c_top + 2].iter().position(|&ch| ch == 'C').unwrap();
+    assert_eq!(rows[c_top - 1][c_col], '▼', "{}", output);
+    assert_eq!(rows[c_top - 2][c_col], '│', "{}", output);
+
+    let ascii = render_diagram(input, &Config::new_test_config(true, "cli")).unwrap();
+    let rows: Vec<Vec<char>> = ascii.lines().map(|l| l.chars().collect()).collect();
+    assert_eq!(rows[c_top - 1][c_col], 'v', "{}", ascii);
+}
+
+#[test]
+fn test_asymmetric_box_padding() {
+    let render = |config: Config| render_diagram("graph LR\nA", &config).unwrap();
+    let wide = render(
+        Config::builder()
+            .box_border_padding_x(3)
+            .box_border_padding_y(0)
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(wide, "┌───────┐\n│   A   │\n└───────┘");
+
+    let tall = render(
+        Config::builder()
+            .box_border_padding_x(0)
+            .box_border_padding_y(2)
+            .build()
+            .unwrap(),
+    );
+    let lines: Vec<&str> = tall.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines[0], "┌─┐");
+    assert_eq!(lines[3], "│A│");
+
+    let fallback = render(
+        Config::builder()
+            .box_padding(2)
+            .box_border_padding_y(0)
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(fallback.lines().nth(1).unwrap(), "│  A  │");
+    assert!(Config::builder().box_border_padding_x(-1).build().is_err());
+}
+
+#[test]
+fn test_fill_glyph_for_filled_nodes() {
+    let input = "graph LR\nclassDef hot fill:#f96\nA:::hot --> B";
+    let output = render_diagram(input, &Config::default_config()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[1], "│░░░│     │   │");
+    assert!(lines[2].starts_with("│░A░├"), "{}", output);
+
+    let config = Config::builder().fill_glyph('#').build().unwrap();
+    let output = render_diagram(input, &config).unwrap();
+    assert!(
+        output.lines().nth(2).unwrap().starts_with("│#A#├"),
+        "{}",
+        output
+    );
+
+    let plain = render_diagram("graph LR\nA --> B", &Config::default_config()).unwrap();
+    assert!(!plain.contains('░'));
+    assert_eq!(plain.lines().nth(1).unwrap(), "│   │     │   │");
+}
+
+#[test]
+fn test_margins_offset_graph_output() {
+    let input = "graph LR\nA --> B";
+    let plain = render_diagram(input, &Config::default_config()).unwrap();
+    let config = Config::builder()
+        .margin_top(2)
+        .margin_left(3)
+        .build()
+        .unwrap();
+    let output = render_diagram(input, &config).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "");
+    assert_eq!(lines[1], "");
+    for (shifted, original) in lines[2..].iter().zip(plain.lines()) {
+        assert_eq!(*shifted, format!("   {}", original));
+    }
+    assert!(Config::builder().margin_left(-1).build().is_err());
+}
+
+#[test]
+fn test_invisible_link_orders_without_drawing() {
+    let output = render_diagram("graph TD\nA ~~~ B", &Config::default_config()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines[2].contains('A'), "{}", output);
+    assert!(lines[12].contains('B'), "{}", output);
+    for line in &lines[5..10] {
+        assert!(line.trim().is_empty(), "{}", output);
+    }
+}
+
+#[test]
+fn test_longer_links_push_child_further() {
+    let mut config = Config::default_config();
+    config.output_format = OutputFormat::Json;
+    let node_x = |input: &str, name: &str| {
+        let json = render_diagram(input, &config).unwrap();
+        let layout: serde_json::Value = serde_json::from_str(&json).unwrap();
+        layout["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|node| node["id"] == name)
+            .unwrap()["x"]
+            .as_i64()
+            .unwrap()
+    };
+    let short = node_x("graph LR\nA --> B", "B");
+    assert!(node_x("graph LR\nA ---> B", "B") > short);
+    assert!(node_x("graph LR\nA ====> B", "B") > node_x("graph LR\nA ---> B", "B"));
+}
+
+#[test]
+fn test_open_links_draw_without_arrowhead() {
+    let config = Config::default_config();
+    let open = render_diagram("graph LR\nA --- B", &config).unwrap();
+    assert!(open.contains("│ A ├─────│ B │"), "{}", open);
+    let longer = render_diagram("graph LR\nA ---- B", &config).unwrap();
+    assert!(!longer.contains('►'), "{}", longer);
+    let row = longer.lines().nth(2).unwrap();
+    assert!(row.len() > open.lines().nth(2).unwrap().len(), "{}", longer);
+
+    let model = graph::parse("graph LR\nA --- B\nB ---- C").unwrap();
+    let links: Vec<&str> = model.edges.iter().map(|edge| edge.link.as_str()).collect();
+    assert_eq!(links, ["---", "----"]);
+    assert_eq!(graph::parse(&graph::to_mermaid(&model)).unwrap(), model);
+}
+
+#[test]
+fn test_wide_label_box_alignment() {
+    let output = render_diagram("graph LR\nA[你好世界] --> B", &Config::default_config()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "┌──────────┐     ┌───┐");
+    assert_eq!(lines[2], "│ 你好世界 ├────►│ B │");
+    assert_eq!(lines[4], "└──────────┘     └───┘");
+}
+
+#[test]
+fn test_node_min_width() {
+    let input = "graph LR\nA --> LongerName\nLongerName --> B";
+    let config = Config::builder().node_min_width(9).build().unwrap();
+    let output = render_diagram(input, &config).unwrap();
+    let top = output.lines().next().unwrap();
+    let widths: Vec<usize> = top
+        .split('┌')
+        .skip(1)
+        .map(|rest| rest.chars().take_while(|&ch| ch == '─').count())
+        .collect();
+    assert_eq!(widths, vec![9, 12, 9], "{}", output);
+    assert!(
+        output.lines().nth(2).unwrap().starts_with("│    A    ├"),
+        "{}",
+        output
+    );
+    assert!(Config::builder().node_min_width(-1).build().is_err());
+}
+
+#[test]
+fn test_edge_labels_do_not_overwrite_boxes() {
+    let mut config = Config::default_config();
+    config.padding_between_x = 1;
+    config.padding_between_y = 1;
+    let output = render_diagram("graph LR\nA -->|lbl| B\nB -->|back| A", &config).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[4].trim_end(), "└───┘     └──────┘", "{}", output);
+    assert!(output.contains("back"), "{}", output);
+
+    let output = render_diagram(
+        "graph LR\nA -->|longlabel| B\nA --> C\nC -->|xx| B",
+        &config,
+    )
+    .unwrap();
+    assert!(output.contains('▲'), "{}", output);
+    assert!(output.contains("xx"), "{}", output);
+}
+
+#[test]
+fn test_graph_to_mermaid_round_trip() {
+    let input = "---\ntitle: Flow\n---\ngraph LR\n  A[Start here] -->|go| B:::hot\n  B ---x C & D\n  subgraph outer\n    C\n    subgraph inner\n      D{\"Choice (y/n)\"}\n    end\n  end\n  E ~~~ A\n  C -->|\"a|b\"| E\n  classDef hot fill:#f00,color:#fff\n  style A stroke:#00f\n  linkStyle 1 stroke:#0f0";
+    let model = graph::parse(input).expect("parse");
+    let exported = graph::to_mermaid(&model);
+    let reparsed = graph::parse(&exported).expect("reparse");
+    assert_eq!(model, reparsed, "{}", exported);
+    assert_eq!(exported, graph::to_mermaid(&reparsed));
+    assert!(exported.contains("    A -->|go| B:::hot\n"), "{}", exported);
+    assert!(
+        exported.contains("    subgraph outer\n        C\n        subgraph inner\n            D\n        end\n    end"),
+        "{}",
+        exported
+    );
+}
+
+#[test]
+fn test_charset_double_and_rounded() {
+    let input = "graph TD\nA -->|go| B\nA --> C";
+    let mut config = Config::default_config();
+    let light = render_diagram(input, &config).unwrap();
+
+    config.charset = CharSet::Double;
+    let double = render_diagram(input, &config).unwrap();
+    assert!(double.starts_with("╔═"), "{}", double);
+    assert!(double.contains('╦') && double.contains("go"), "{}", double);
+    assert!(!double.contains('─') && !double.contains('│'), "{}", double);
+
+    config.charset = CharSet::Rounded;
+    let rounded = render_diagram(input, &config).unwrap();
+    assert!(rounded.starts_with("╭─"), "{}", rounded);
+    assert!(
+        !rounded.contains('┌') && !rounded.contains('┘'),
+        "{}",
+        rounded
+    );
+
+    // Only the glyphs change, never the layout.
+    for styled in [&double, &rounded] {
+        let widths = |s: &str| s.lines().map(|l| l.chars().count()).collect::<Vec<_>>();
+        assert_eq!(widths(styled), widths(&light));
+    }
+}
+
+#[test]
+fn test_quoted_node_ids_with_spaces() {
+    let model = graph::parse(
+        "graph LR\n\"My Node\" --> B\nB -->|go| \"Other & Co\"[Shown]\n\"My Node\" --> \"a --> b\"",
+    )
+    .unwrap();
+    let ids: Vec<&str> = model.nodes.iter().map(|n| n.id.as_str()).collect();
+    assert_eq!(ids, ["My Node", "B", "Other & Co", "a --> b"]);
+    assert_eq!(model.nodes[0].label, "My Node");
+    assert_eq!(model.nodes[2].label, "Shown");
+    assert_eq!(model.edges.len(), 3);
+    assert_eq!(graph::parse(&graph::to_mermaid(&model)).unwrap(), model);
+
+    let output = render_diagram("graph LR\n\"My Node\" --> B", &Config::default_config()).unwrap();
+    assert!(output.contains("│ My Node "), "{}", output);
+    assert!(!output.contains('"'), "{}", output);
+}
+
+#[test]
+fn test_labels_as_legend() {
+    let input = "graph LR\nA -->|first label| B\nB -->|second| C\nA --> C";
+    let mut config = Config::default_config();
+    config.labels_as_legend = true;
+    let output = render_diagram(input, &config).unwrap();
+    let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+    assert_eq!(
+        lines[lines.len() - 2..],
+        ["① first label", "② second"],
+        "{}",
+        output
+    );
+    let drawing = lines[..lines.len() - 2].join("\n");
+    assert!(drawing.contains('①') && drawing.contains('②'), "{}", output);
+    assert!(
+        !drawing.contains("first") && !drawing.contains("second"),
+        "{}",
+        output
+    );
+
+    config.use_ascii = true;
+    let output = render_diagram(input, &config).unwrap();
+    assert!(
+        output.contains("-[1]>") && output.trim_end().ends_with("[2] second"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_direction_keyword_survives_parse() {
+    let model = graph::parse("flowchart TB\nA --> B").unwrap();
+    assert_eq!(model.direction, "TD");
+    assert_eq!(model.direction_keyword, "TB");
+    assert!(graph::to_mermaid(&model).starts_with("graph TB\n"));
+
+    let model = graph::parse("graph TD\nA --> B").unwrap();
+    assert_eq!(model.direction_keyword, "TD");
+}
+
+#[test]
+fn test_arrow_glyphs_override() {
+    let mut config = Config::default_config();
+    config.arrow_glyphs = Some(ArrowGlyphs {
+        up: '↑',
+        down: '↓',
+        left: '←',
+        right: '→',
+    });
+    let output = render_diagram("graph LR\nA --> B\nB --> A", &config).unwrap();
+    assert!(output.contains('→') && output.contains('↑'), "{}", output);
+    assert!(!output.contains('►') && !output.contains('▲'), "{}", output);
+
+    let output = render_diagram("graph TD\nA --> B", &config).unwrap();
+    assert!(output.contains('↓'), "{}", output);
+}
+
+#[test]
+fn test_subgraph_direction_lays_out_across() {
+    let input = "graph TD\nStart --> A\nsubgraph row\n  direction LR\n  B --> C\n  C --> D\nend\nA --> B\nD --> End";
+    let output = render_diagram(input, &Config::default_config()).unwrap();
+    let row = output
+        .lines()
+        .find(|line| line.contains(" B "))
+        .expect("B is drawn");
+    assert!(row.contains(" C ") && row.contains(" D "), "{}", output);
+    let start = output.lines().position(|l| l.contains("Start")).unwrap();
+    let end = output.lines().position(|l| l.contains("End")).unwrap();
+    assert!(start < end, "{}", output);
+
+    let model = graph::parse(input).unwrap();
+    assert_eq!(model.subgraphs[0].direction.as_deref(), Some("LR"));
+    assert_eq!(graph::parse(&graph::to_mermaid(&model)).unwrap(), model);
+}
+
+#[test]
+fn test_labeled_edges_fan_out_over_ampersand_groups() {
+    let edges = |input: &str| -> Vec<(String, String, String)> {
+        graph::parse(input)
+            .expect("parse")
+            .edges
+            .into_iter()
+            .map(|edge| (edge.from, edge.to, edge.label))
+            .collect()
+    };
+    let edge = |from: &str, to: &str, label: &str| (from.into(), to.into(), label.into());
+
+    assert_eq!(
+        edges("graph LR\nA -->|go| B & C"),
+        [edge("A", "B", "go"), edge("A", "C", "go")]
+    );
+    assert_eq!(
+        edges("graph LR\nA & B -->|x| C & D"),
+        [
+            edge("A", "C", "x"),
+            edge("A", "D", "x"),
+            edge("B", "C", "x"),
+            edge("B", "D", "x"),
+        ]
+    );
+    assert_eq!(
+        edges("graph LR\nA -->|\"this & that\"| B & C --> D"),
+        [
+            edge("A", "B", "this & that"),
+            edge("A", "C", "this & that"),
+            edge("B", "D", ""),
+            edge("C", "D", ""),
+        ]
+    );
+}
+
+#[test]
+fn test_compact_collapses_blank_rows() {
+    let input = "graph LR\nA --> B\nC --> D";
+    let mut config = Config::new_test_config(false, "cli");
+    config.padding_between_y = 8;
+    let tall = render_diagram(input, &config).expect("render");
+    config.compact = true;
+    let compact = render_diagram(input, &config).expect("render");
+
+    assert!(
+        compact.lines().count() < tall.lines().count(),
+        "{}\n{}",
+        tall,
+        compact
+    );
+    let glyphs = |output: &str| {
+        let mut chars: Vec<char> = output.chars().filter(|c| !c.is_whitespace()).collect();
+        chars.sort();
+        chars
+    };
+    assert_eq!(glyphs(&compact), glyphs(&tall));
+    let lines: Vec<&str> = compact.lines().collect();
+    assert!(
+        lines
+            .windows(2)
+            .all(|pair| !pair[0].trim().is_empty() || !pair[1].trim().is_empty()),
+        "{}",
+        compact
+    );
+}
+
+#[test]
+fn test_disconnected_components_render_in_separate_bands() {
+    let input = "graph LR\nA --> B --> C\nD --> E --> F\nA --> X\nA --> Y";
+    let mut config = Config::new_test_config(false, "cli");
+    let output = render_diagram(input, &config).expect("render");
+    let rows = |output: &str, names: &[&str]| -> Vec<usize> {
+        output
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                names
+                    .iter()
+                    .any(|name| line.contains(&format!("│ {} ", name)))
+            })
+            .map(|(row, _)| row)
+            .collect()
+    };
+    let first = rows(&output, &["A", "B", "C", "X", "Y"]);
+    let second = rows(&output, &["D", "E", "F"]);
+    assert!(first.iter().max() < second.iter().min(), "{}", output);
+
+    config.component_spacing = Some(12);
+    let spaced = render_diagram(input, &config).expect("render");
+    let gap = |output: &str| rows(output, &["D"])[0] - rows(output, &["Y"])[0];
+    assert!(gap(&spaced) > gap(&output), "{}", spaced);
+
+    config.component_spacing = Some(-1);
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_click_directives_are_kept_as_metadata() {
+    let input = "graph LR\nA --> B --> C\nclick A \"https://example.com\" \"Docs\" _blank\nclick B callback\nclick C call notify(1)";
+    let config = Config::new_test_config(false, "cli");
+    let output = render_diagram(input, &config).expect("render");
+    assert!(!output.contains("example"), "{}", output);
+
+    let model = graph::parse(input).unwrap();
+    let click = |id: &str| {
+        model
+            .nodes
+            .iter()
+            .find(|node| node.id == id)
+            .and_then(|node| node.click.clone())
+            .unwrap()
+    };
+    assert_eq!(click("A").href.as_deref(), Some("https://example.com"));
+    assert_eq!(click("A").tooltip.as_deref(), Some("Docs"));
+    assert_eq!(click("A").target.as_deref(), Some("_blank"));
+    assert_eq!(click("B").callback.as_deref(), Some("callback"));
+    assert_eq!(click("C").callback.as_deref(), Some("notify(1)"));
+    assert_eq!(graph::parse(&graph::to_mermaid(&model)).unwrap(), model);
+}
+
+#[test]
+fn test_click_call_accepts_argument_lists() {
+    let input = "graph LR\nA --> B\nclick A call cb(1, 2)\nclick B call cb(\"x\") \"Tip\"";
+    let model = graph::parse(input).unwrap();
+    let click = |id: &str| {
+        model
+            .nodes
+            .iter()
+            .find(|node| node.id == id)
+            .and_then(|node| node.click.clone())
+            .unwrap()
+    };
+    assert_eq!(click("A").callback.as_deref(), Some("cb(1, 2)"));
+    assert_eq!(click("B").callback.as_deref(), Some("cb(\"x\")"));
+    assert_eq!(click("B").tooltip.as_deref(), Some("Tip"));
+
+    let err = graph::parse("graph LR\nA --> B\nclick A call cb(1").unwrap_err();
+    assert_eq!(err, "invalid click directive: click A call cb(1");
+}
+
+#[test]
+fn test_edge_label_position_start_sits_by_source() {
+    let label_row = |position: EdgeLabelPosition| {
+        let mut config = Config::new_test_config(false, "cli");
+        config.padding_between_y = 9;
+        config.edge_label_position = position;
+        let output = render_diagram("graph TD\nA -->|go| B", &config).expect("render");
+        let lines: Vec<&str> = output.lines().collect();
+        let source_bottom = lines.iter().position(|line| line.contains('┬')).unwrap();
+        let target_top = lines.iter().rposition(|line| line.contains('┌')).unwrap();
+        let label = lines.iter().position(|line| line.contains("go")).unwrap();
+        (label - source_bottom, target_top - label, output)
+    };
+
+    let (after_source, _, output) = label_row(EdgeLabelPosition::Start);
+    assert_eq!(after_source, 2, "{}", output);
+    let (middle_after_source, _, output) = label_row(EdgeLabelPosition::Middle);
+    assert!(middle_after_source > after_source, "{}", output);
+    let (_, before_target, output) = label_row(EdgeLabelPosition::End);
+    assert_eq!(before_target, 3, "{}", output);
+
+    let mut config = Config::new_test_config(false, "cli");
+    config.padding_between_x = 14;
+    config.edge_label_position = EdgeLabelPosition::Start;
+    let output = render_diagram("graph LR\nAlpha -->|go| Beta", &config).expect("render");
+    assert!(output.contains("│ Alpha ├─go─"), "{}", output);
+}
+
+#[test]
+fn test_path_search_limit_gives_up_on_routes() {
+    // With the search capped at its start cell no target is ever reached, as
+    // if every one were walled in; layout must still finish with all boxes.
+    let input = "graph LR\nA --> B\nB --> C\nA --> C\nC --> D";
+    let mut config = Config::new_test_config(false, "cli");
+    config.path_search_limit = Some(1);
+    let output = render_diagram(input, &config).expect("render");
+    assert_eq!(output.matches('┌').count(), 4, "{}", output);
+    assert!(!output.contains('►'), "{}", output);
+
+    config.path_search_limit = None;
+    let output = render_diagram(input, &config).expect("render");
+    assert!(output.contains('►'), "{}", output);
+}
+
+#[test]
+fn test_path_search_limit_falls_back_to_alternative_route() {
+    // C's preferred route into D outruns this limit but its alternative
+    // still fits, so the edge is drawn rather than dropped.
+    let input = "graph LR\nA --> B\nA --> C\nB --> D\nC --> D";
+    let mut config = Config::new_test_config(false, "cli");
+    config.path_search_limit = Some(9);
+    let output = render_diagram(input, &config).expect("render");
+    assert_eq!(output.matches('►').count(), 3, "{}", output);
+    assert!(output.contains('▲'), "{}", output);
+}
+
+// Only built by `cargo test --no-default-features`, proving the crate still
+// parses and renders with the `debug!` calls compiled out.
+#[cfg(not(feature = "logging"))]
+#[test]
+fn test_renders_without_logging_feature() {
+    let config = Config::new_test_config(false, "cli");
+    let output = render_diagram("graph LR\nA -->|go| B", &config).expect("render");
+    assert!(output.contains("go"), "{}", output);
+}
+
+#[test]
+fn test_semicolons_end_and_separate_statements() {
+    let model = graph::parse("graph LR;\nA --> B;\nB -->|x;y| C; C --> D[d;e] ;\n").expect("parse");
+    let ids: Vec<&str> = model.nodes.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(ids, ["A", "B", "C", "D"]);
+    let edges: Vec<(&str, &str, &str)> = model
+        .edges
+        .iter()
+        .map(|edge| (edge.from.as_str(), edge.to.as_str(), edge.label.as_str()))
+        .collect();
+    assert_eq!(edges, [("A", "B", ""), ("B", "C", "x;y"), ("C", "D", "")]);
+    assert_eq!(model.nodes[3].label, "d;e");
+}
+
+#[test]
+fn test_first_node_label_sticks() {
+    let model = graph::parse("graph LR\nA[First] --> B\nB --> A\nA[Second] --> C").expect("parse");
+    assert_eq!(model.nodes[0].label, "First");
+
+    let config = Config::new_test_config(false, "cli");
+    let output = render_diagram(
+        "graph LR\nA --> B\nA[First] --> C\nB --> A[Second]",
+        &config,
+    )
+    .expect("render");
+    assert!(output.contains("First"), "{}", output);
+    assert!(!output.contains("Second"), "{}", output);
+}
+
+#[test]
+fn test_junction_merge_table() {
+    let cases = [
+        ('─', '│', '┼'),
+        ('│', '─', '┼'),
+        ('─', '┌', '┬'),
+        ('│', '┘', '┤'),
+        ('┌', '┘', '┼'),
+        ('└', '┘', '┴'),
+        ('├', '┤', '┼'),
+        ('┬', '┴', '┼'),
+        ('─', '─', '─'),
+        ('│', '►', '►'),
+        (' ', '─', '─'),
+        ('─', 'x', 'x'),
+    ];
+    for (a, b, merged) in cases {
+        assert_eq!(junctions::merge(a, b), merged, "{} + {}", a, b);
+    }
+    assert_eq!(junctions::junction_dirs('├'), (true, true, false, true));
+    assert_eq!(junctions::junction_from_dirs(true, false, true, true), '┴');
+    assert!(!junctions::is_junction('+'));
+}
+
+// Only built with `--features debug`.
+#[cfg(feature = "debug")]
+#[test]
+fn test_layout_report_lists_every_node() {
+    let input = "graph LR\nA --> B\nB --> C\nA --> D";
+    let config = Config::new_test_config(false, "cli");
+    let report = console_mermaid::layout_report(input, &config).expect("report");
+    let ids: Vec<&str> = report.nodes.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(ids, ["A", "B", "C", "D"]);
+    for node in &report.nodes {
+        assert!(report.column_width.contains_key(&node.grid.0), "{:?}", node);
+        assert!(report.row_height.contains_key(&node.grid.1), "{:?}", node);
+    }
+    assert!(console_mermaid::layout_report("sequenceDiagram\n    A->>B: hi", &config).is_err());
+}
+
+#[test]
+fn test_roots_ignore_edge_declaration_order() {
+    let config = Config::default_config();
+    let expected = render_diagram("graph LR\nA --> B\nB --> C\nC --> D", &config).expect("render");
+    for input in [
+        "graph LR\nC --> D\nB --> C\nA --> B",
+        "graph LR\nB --> C\nC --> D\nA --> B",
+    ] {
+        let output = render_diagram(input, &config).expect("render");
+        assert_eq!(
+            output.lines().count(),
+            expected.lines().count(),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("│ A ├────►│ B ├────►│ C ├────►│ D │"),
+            "{}",
+            output
+        );
+    }
+}
+
+#[test]
+fn test_deterministic_edges_ignore_declaration_order() {
+    let nodes = "graph TD\nA\nB\nC\nD\n";
+    let declared = format!("{}A --> B\nA --> C\nB --> D\nC --> D\nA --> D", nodes);
+    let reordered = format!("{}A --> D\nC --> D\nB --> D\nA --> C\nA --> B", nodes);
+    let mut config = Config::default_config();
+    assert_ne!(
+        render_diagram(&declared, &config).unwrap(),
+        render_diagram(&reordered, &config).unwrap()
+    );
+
+    config.deterministic_edges = true;
+    assert_eq!(
+        render_diagram(&declared, &config).unwrap(),
+        render_diagram(&reordered, &config).unwrap()
+    );
+}
+
+#[test]
+fn test_links_parse_without_surrounding_spaces() {
+    let edges = |input: &str| {
+        graph::parse(input)
+            .unwrap()
+            .edges
+            .into_iter()
+            .map(|edge| (edge.from, edge.to, edge.label))
+            .collect::<Vec<_>>()
+    };
+    let plain = vec![("A".to_string(), "B".to_string(), String::new())];
+    for input in ["graph LR\nA-->B", "graph LR\nA --> B", "graph LR\nA-->  B"] {
+        assert_eq!(edges(input), plain, "{}", input);
+    }
+    let labelled = vec![("A".to_string(), "B".to_string(), "x".to_string())];
+    for input in [
+        "graph LR\nA-->|x|B",
+        "graph LR\nA -->|x| B",
+        "graph LR\nA --> |x| B",
+        "graph LR\nA-->| x |B",
+    ] {
+        assert_eq!(edges(input), labelled, "{}", input);
+    }
+
+    // Link-like text inside a label or a node's brackets stays text.
+    let model = graph::parse("graph LR\nA[x-->y]-->|a-->b|B").unwrap();
+    assert_eq!(model.edges.len(), 1);
+    assert_eq!(model.edges[0].label, "a-->b");
+    assert_eq!(model.nodes[0].label, "x-->y");
+
+    // An unmatched closer, as in the `>flag]` shape, doesn't unmask the rest.
+    let model = graph::parse("graph LR\nA>flag] --> B[x-->y]").unwrap();
+    assert_eq!(model.edges.len(), 1);
+    let b = model.nodes.iter().find(|node| node.id == "B").unwrap();
+    assert_eq!(b.label, "x-->y");
+}
@@ -1,5 +1,11 @@
-use console_mermaid::diagram::Config;
-use console_mermaid::render_diagram;
+use console_mermaid::diagram::{
+    CharSet, Config, OutputFormat, detect_diagram_type, diagram_factory, extract_mermaid_blocks,
+    remove_comments, split_diagrams,
+};
+use console_mermaid::{
+    check_diagram, render_all, render_diagram, render_diagram_to, render_graph, render_markdown,
+    render_sequence, render_to_cells,
+};
 
 #[test]
 fn test_sequence_diagram_integration() {
@@ -28,3 +34,464 @@ fn test_invalid_input_errors() {
     assert!(render_diagram("", &config).is_err());
     assert!(render_diagram("not a diagram", &config).is_err());
 }
+
+fn max_line_width(output: &str) -> usize {
+    output
+        .lines()
+        .map(|line| line.trim_end().chars().count())
+        .max()
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_max_width_stacks_wide_graph() {
+    let input = "graph LR\nAlpha --> Beta\nBeta --> Gamma\nGamma --> Delta";
+    let mut config = Config::new_test_config(false, "cli");
+    let unconstrained = render_diagram(input, &config).expect("render");
+    assert!(max_line_width(&unconstrained) > 40);
+
+    config.max_width = Some(40);
+    let constrained = render_diagram(input, &config).expect("render");
+    assert!(max_line_width(&constrained) <= 40);
+}
+
+#[test]
+fn test_max_width_tightens_sequence_spacing() {
+    let input = "sequenceDiagram\n    Alice->>Bob: Hi\n    Bob->>Carol: Hey";
+    let mut config = Config::new_test_config(false, "cli");
+    let unconstrained = render_diagram(input, &config).expect("render");
+
+    config.max_width = Some(30);
+    let constrained = render_diagram(input, &config).expect("render");
+    assert!(max_line_width(&constrained) < max_line_width(&unconstrained));
+    assert!(max_line_width(&constrained) <= 30);
+}
+
+#[test]
+fn test_max_width_truncates_sequence_labels_after_spacing() {
+    let input = "sequenceDiagram\n    participant Authentication\n    participant Database\n    participant Cache\n    Authentication->>Database: look up the account record\n    Database->>Cache: ok";
+    let mut config = Config::new_test_config(false, "cli");
+    config.max_width = Some(30);
+    let output = render_diagram(input, &config).expect("render");
+    assert!(max_line_width(&output) <= 30, "{}", output);
+    assert!(output.contains("│ Auth… │"), "{}", output);
+    assert!(output.contains("│ Cache │"), "{}", output);
+    assert!(output.contains("look up the account rec…"), "{}", output);
+
+    config.use_ascii = true;
+    let ascii = render_diagram(input, &config).expect("render");
+    assert!(max_line_width(&ascii) <= 30, "{}", ascii);
+    assert!(ascii.contains("| Au... |"), "{}", ascii);
+
+    config.use_ascii = false;
+    config.charset = CharSet::Ascii;
+    assert_eq!(render_diagram(input, &config).expect("render"), ascii);
+    config.charset = CharSet::Light;
+    config.output_format = OutputFormat::Ascii;
+    assert_eq!(render_diagram(input, &config).expect("render"), ascii);
+}
+
+#[test]
+fn test_max_width_rejects_non_positive() {
+    let mut config = Config::new_test_config(false, "cli");
+    config.max_width = Some(0);
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_caption_is_centered_last_line() {
+    for input in ["graph LR\nA --> B", "sequenceDiagram\n    Alice->>Bob: Hi"] {
+        let mut config = Config::new_test_config(false, "cli");
+        let plain = render_diagram(input, &config).expect("render");
+        config.caption = Some("Figure 1".to_string());
+        let output = render_diagram(input, &config).expect("render");
+
+        let last = output
+            .trim_end_matches('\n')
+            .lines()
+            .last()
+            .expect("caption line");
+        assert_eq!(last.trim(), "Figure 1");
+        let left = last.len() - last.trim_start().len();
+        assert_eq!(left, (max_line_width(&plain) - "Figure 1".len()) / 2);
+    }
+}
+
+#[test]
+fn test_detect_diagram_type() {
+    assert_eq!(
+        detect_diagram_type("%% intro\nsequenceDiagram\nA->>B: hi"),
+        Some("sequence")
+    );
+    assert_eq!(detect_diagram_type("flowchart TD\nA --> B"), Some("graph"));
+    assert_eq!(detect_diagram_type("\n  graph LR\nA --> B"), Some("graph"));
+    assert_eq!(
+        detect_diagram_type("erDiagram\nA ||--o{ B : has"),
+        Some("er")
+    );
+    assert_eq!(detect_diagram_type("classDiagram\nA <|-- B"), Some("class"));
+    assert_eq!(detect_diagram_type("pie title Pets"), None);
+    assert_eq!(detect_diagram_type("A --> B"), None);
+    assert_eq!(detect_diagram_type(""), None);
+    assert_eq!(detect_diagram_type("%% only a comment"), None);
+}
+
+#[test]
+fn test_config_builder() {
+    let config = Config::builder()
+        .ascii(true)
+        .direction("TD")
+        .padding_x(3)
+        .max_width(80)
+        .build()
+        .expect("valid config");
+    assert!(config.use_ascii);
+    assert_eq!(config.graph_direction, "TD");
+    assert_eq!(config.padding_between_x, 3);
+    assert_eq!(config.max_width, Some(80));
+    assert_eq!(
+        config.padding_between_y,
+        Config::default_config().padding_between_y
+    );
+
+    let invalid = [
+        Config::builder().direction("BT").build(),
+        Config::builder().padding_x(-1).build(),
+        Config::builder().style_type("svg").build(),
+        Config::builder().max_width(0).build(),
+        Config::builder().sequence_self_message_width(1).build(),
+    ];
+    for result in invalid {
+        assert!(result.unwrap_err().starts_with("invalid config"));
+    }
+
+    let mut mutated = Config::default_config();
+    mutated.padding_between_y = -2;
+    assert_eq!(
+        Config::builder().padding_y(-2).build().unwrap_err(),
+        mutated.validate().unwrap_err()
+    );
+}
+
+#[test]
+fn test_render_diagram_to_writer_matches_string() {
+    let inputs = [
+        "graph TD\nA --> B\nA --> C",
+        "---\ntitle: Flow\n---\ngraph LR\nA --> B",
+        "sequenceDiagram\n    Alice->>Bob: Hello\n    Bob-->>Alice: Hi",
+    ];
+    let config = Config::default_config();
+    for input in inputs {
+        let mut diagram = diagram_factory(input).unwrap();
+        diagram.parse(input, &config).unwrap();
+        let expected = diagram.render(&config).unwrap();
+
+        let mut written = Vec::new();
+        render_diagram_to(input, &config, &mut written).expect("render to writer");
+        assert_eq!(String::from_utf8(written).unwrap(), expected);
+        assert_eq!(render_diagram(input, &config).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_check_diagram_parses_without_rendering() {
+    let config = Config::default_config();
+    assert!(check_diagram("graph LR\nA --> B", &config).is_ok());
+    assert!(check_diagram("sequenceDiagram\n    A->>B: hi", &config).is_ok());
+
+    let err = check_diagram("sequenceDiagram\n    A->>B hi", &config).unwrap_err();
+    assert!(err.contains("line 2"), "{}", err);
+    assert!(check_diagram("graph LR\nlinkStyle 3 stroke:red\nA --> B", &config).is_err());
+}
+
+#[test]
+fn test_render_all_splits_diagrams() {
+    let graph = "graph LR\nA --> B";
+    let sequence = "sequenceDiagram\n    Alice->>Bob: Hello";
+    let titled = "---\ntitle: Third\n---\ngraph TD\nC --> D";
+    let document = format!("{}\n\n{}\n---\n{}", graph, sequence, titled);
+    assert_eq!(split_diagrams(&document), [graph, sequence, titled]);
+
+    let config = Config::default_config();
+    let expected = [graph, sequence, titled]
+        .map(|block| {
+            render_diagram(block, &config)
+                .unwrap()
+                .trim_end()
+                .to_string()
+        })
+        .join("\n\n");
+    assert_eq!(render_all(&document, &config).unwrap(), expected);
+
+    let broken = format!("{}\nsequenceDiagram\n    A->>B oops", graph);
+    let err = render_all(&broken, &config).unwrap_err();
+    assert!(err.starts_with("diagram 2: line 2"), "{}", err);
+}
+
+#[test]
+fn test_percent_percent_inside_labels_is_not_a_comment() {
+    let config = Config::new_test_config(false, "cli");
+    let graph = render_diagram("graph LR\nA -->|50%% done| B %% trailing note", &config).unwrap();
+    assert!(graph.contains("50%%"), "{}", graph);
+    assert!(!graph.contains("trailing"), "{}", graph);
+
+    let sequence = render_diagram(
+        "sequenceDiagram\n    A->>B: \"100%% sure\" %% aside\n    B->>A: ok",
+        &config,
+    )
+    .unwrap();
+    assert!(sequence.contains("100%% sure"), "{}", sequence);
+    assert!(!sequence.contains("aside"), "{}", sequence);
+
+    let lines = vec![
+        "A -->|x| B %% note".to_string(),
+        "%% whole line".to_string(),
+    ];
+    assert_eq!(remove_comments(&lines), vec!["A -->|x| B".to_string()]);
+}
+
+#[test]
+fn test_auto_direction_picks_direction_that_fits() {
+    // Only auto_direction turns a wide TD fan-out sideways: the max_width
+    // fallback after layout only ever restacks LR graphs top-down.
+    let fan = "graph TD\nR --> A\nR --> B\nR --> C\nR --> D\nR --> E\nR --> F";
+    let mut config = Config::new_test_config(false, "cli");
+    config.max_width = Some(30);
+    let output = render_diagram(fan, &config).expect("render");
+    assert!(max_line_width(&output) > 30, "{}", output);
+
+    config.auto_direction = true;
+    let output = render_diagram(fan, &config).expect("render");
+    assert!(max_line_width(&output) <= 30, "{}", output);
+    let top = output.lines().nth(2).unwrap();
+    assert!(top.contains("R") && top.contains("A"), "{}", output);
+
+    // A long LR chain fits either way, flipped before layout or after.
+    let chain = "graph LR\nA --> B\nB --> C\nC --> D\nD --> E\nE --> F";
+    config.max_width = Some(20);
+    let output = render_diagram(chain, &config).expect("render");
+    assert!(max_line_width(&output) <= 20, "{}", output);
+    assert!(output.lines().count() > 20, "{}", output);
+    config.auto_direction = false;
+    assert_eq!(render_diagram(chain, &config).expect("render"), output);
+}
+
+#[test]
+fn test_unsupported_diagram_types_are_named() {
+    let config = Config::new_test_config(false, "cli");
+    for (input, keyword) in [
+        ("timeline\n    title History", "timeline"),
+        ("gantt\n    title Plan", "gantt"),
+        ("%% history\ngitGraph\n    commit", "gitGraph"),
+        ("pie title Pets\n    \"Dogs\" : 3", "pie"),
+    ] {
+        let expected = format!("diagram type '{}' is not yet supported", keyword);
+        assert_eq!(diagram_factory(input).err(), Some(expected.clone()));
+        assert_eq!(render_diagram(input, &config).unwrap_err(), expected);
+    }
+}
+
+#[test]
+fn test_max_label_width_truncates_with_ellipsis() {
+    let mut config = Config::new_test_config(false, "cli");
+    config.max_label_width = Some(8);
+    let graph = render_diagram("graph LR\nA[Supercalifragilistic] --> B[Short]", &config).unwrap();
+    assert!(graph.contains("│ Superca… ├"), "{}", graph);
+    assert!(graph.contains("│ Short │"), "{}", graph);
+    assert!(!graph.contains("Supercal"), "{}", graph);
+
+    let sequence = render_diagram(
+        "sequenceDiagram\n    participant Supercalifragilistic\n    participant Bob\n    Supercalifragilistic->>Bob: hi",
+        &config,
+    )
+    .unwrap();
+    assert!(sequence.contains("Superca…"), "{}", sequence);
+    assert!(sequence.contains("Bob"), "{}", sequence);
+
+    config.use_ascii = true;
+    let ascii = render_diagram("graph LR\nA[Supercalifragilistic] --> B[Short]", &config).unwrap();
+    assert!(ascii.contains("| Super... |"), "{}", ascii);
+
+    config.max_label_width = Some(0);
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_extract_mermaid_blocks_from_markdown() {
+    let md = "# Notes\n\n```mermaid\ngraph LR\nA --> B\n```\n\n```rust\nfn main() {}\n```\n\n  ~~~~ mermaid\n  sequenceDiagram\n      Alice->>Bob: Hi\n  ~~~~\n";
+    let blocks = extract_mermaid_blocks(md);
+    assert_eq!(
+        blocks,
+        ["graph LR\nA --> B", "sequenceDiagram\n    Alice->>Bob: Hi"]
+    );
+
+    let config = Config::new_test_config(false, "cli");
+    let expected = blocks
+        .iter()
+        .map(|block| {
+            render_diagram(block, &config)
+                .unwrap()
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    assert_eq!(render_markdown(md, &config).unwrap(), expected);
+    assert!(render_markdown("no diagrams here", &config).is_err());
+}
+
+#[test]
+fn test_render_to_cells_matches_string_render() {
+    let config = Config::new_test_config(false, "cli");
+    for input in [
+        "graph TD\nA --> B\nA --> C",
+        "sequenceDiagram\n    Alice->>Bob: Hello\n    Bob-->>Alice: Hi",
+    ] {
+        let output = render_diagram(input, &config).unwrap();
+        let cells = render_to_cells(input, &config).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(cells.len(), lines.len());
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap();
+        for (row, line) in cells.iter().zip(&lines) {
+            assert_eq!(row.len(), width);
+            let text: String = row.iter().collect();
+            assert_eq!(text.trim_end(), line.trim_end());
+        }
+    }
+
+    // Wide characters take two cells, so columns line up with the terminal.
+    let input = "graph LR\nA[你好] --> B";
+    let output = render_diagram(input, &config).unwrap();
+    let cells = render_to_cells(input, &config).unwrap();
+    let row = output
+        .lines()
+        .position(|line| line.contains("你好"))
+        .unwrap();
+    let col = cells[row].iter().position(|&ch| ch == '你').unwrap();
+    assert_eq!(&cells[row][col..col + 4], &['你', '\0', '好', '\0']);
+    let border = &cells[row - 2];
+    assert_eq!(border[col + 4], '─', "{}", output);
+    for (cells, line) in cells.iter().zip(output.lines()) {
+        let text: String = cells.iter().filter(|&&ch| ch != '\0').collect();
+        assert_eq!(text.trim_end(), line.trim_end());
+        assert_eq!(cells.len(), border.len());
+    }
+}
+
+#[test]
+fn test_journey_honors_every_ascii_switch() {
+    let input = std::fs::read_to_string("tests/testdata/ascii/journey_single_section.txt").unwrap();
+    let (input, expected) = input.split_once("\n---\n").unwrap();
+    let mut config = Config::new_test_config(false, "cli");
+    config.charset = CharSet::Ascii;
+    let output = render_diagram(input, &config).unwrap();
+    assert_eq!(output.trim_end(), expected.trim_end());
+
+    config.charset = CharSet::Light;
+    config.output_format = OutputFormat::Ascii;
+    assert_eq!(render_diagram(input, &config).unwrap(), output);
+}
+
+#[test]
+fn test_journey_rejects_svg_and_json_and_applies_margins() {
+    let input = "journey\n    section Morning\n      Wake up: 3: Me";
+    let mut config = Config::new_test_config(false, "cli");
+    let plain = render_diagram(input, &config).unwrap();
+    config.margin_top = 1;
+    config.margin_left = 2;
+    let expected: Vec<String> = std::iter::once(String::new())
+        .chain(plain.lines().map(|line| format!("  {}", line)))
+        .collect();
+    assert_eq!(render_diagram(input, &config).unwrap(), expected.join("\n"));
+
+    config.output_format = OutputFormat::Svg;
+    assert_eq!(
+        render_diagram(input, &config).unwrap_err(),
+        "SVG output is only supported for graph diagrams"
+    );
+    config.output_format = OutputFormat::Json;
+    assert_eq!(
+        render_diagram(input, &config).unwrap_err(),
+        "JSON output is only supported for graph diagrams"
+    );
+}
+
+#[test]
+fn test_mindmap_honors_every_ascii_switch() {
+    let input = "mindmap\n    root\n        A\n            A1\n        B";
+    let mut config = Config::new_test_config(true, "cli");
+    let expected = render_diagram(input, &config).unwrap();
+    assert_eq!(expected, "root\n+- A\n|  \\- A1\n\\- B");
+
+    config.use_ascii = false;
+    config.charset = CharSet::Ascii;
+    assert_eq!(render_diagram(input, &config).unwrap(), expected);
+    config.charset = CharSet::Light;
+    config.output_format = OutputFormat::Ascii;
+    assert_eq!(render_diagram(input, &config).unwrap(), expected);
+}
+
+#[test]
+fn test_mindmap_rejects_svg_and_json_and_applies_margins() {
+    let input = "mindmap\n    root\n        A";
+    let mut config = Config::new_test_config(false, "cli");
+    config.margin_top = 1;
+    config.margin_left = 2;
+    assert_eq!(render_diagram(input, &config).unwrap(), "\n  root\n  └─ A");
+
+    config.output_format = OutputFormat::Svg;
+    assert_eq!(
+        render_diagram(input, &config).unwrap_err(),
+        "SVG output is only supported for graph diagrams"
+    );
+    config.output_format = OutputFormat::Json;
+    assert_eq!(
+        render_diagram(input, &config).unwrap_err(),
+        "JSON output is only supported for graph diagrams"
+    );
+}
+
+#[test]
+fn test_tab_indented_mindmap_matches_spaces() {
+    let mut config = Config::new_test_config(false, "cli");
+    let spaces = "mindmap\n    root\n        A\n            A1\n        B";
+    let expected = render_diagram(spaces, &config).unwrap();
+    assert_eq!(expected, "root\n├─ A\n│  └─ A1\n└─ B");
+
+    let tabs = "mindmap\n\troot\n\t\tA\n\t\t\tA1\n\t\tB";
+    assert_eq!(render_diagram(tabs, &config).unwrap(), expected);
+    // A tab runs to the next stop, so leading spaces before it don't count.
+    let mixed = "mindmap\n  \troot\n\t  \tA\n        \tA1\n    \tB";
+    assert_eq!(render_diagram(mixed, &config).unwrap(), expected);
+
+    config.tab_width = 2;
+    let narrow = "mindmap\n\t\troot\n\t\t\t\tA\n\t\t\t\t\t\tA1\n\t\t\t\tB";
+    assert_eq!(render_diagram(narrow, &config).unwrap(), expected);
+
+    config.tab_width = 0;
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_typed_render_entry_points() {
+    let config = Config::new_test_config(false, "cli");
+    let graph = "graph TD\nA --> B";
+    let sequence = "sequenceDiagram\n    Alice->>Bob: Hello";
+    assert_eq!(
+        render_graph(graph, &config).unwrap(),
+        render_diagram(graph, &config).unwrap()
+    );
+    assert_eq!(
+        render_sequence(sequence, &config).unwrap(),
+        render_diagram(sequence, &config).unwrap()
+    );
+
+    let err = render_graph(sequence, &config).unwrap_err();
+    assert!(
+        err.starts_with("unsupported graph type 'sequenceDiagram'"),
+        "{}",
+        err
+    );
+    let err = render_sequence(graph, &config).unwrap_err();
+    assert_eq!(err, "expected \"sequenceDiagram\" keyword");
+}
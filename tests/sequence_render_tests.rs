@@ -1,14 +1,17 @@
 mod sequence_testutil;
 
-use console_mermaid::diagram::Config;
-use console_mermaid::sequence::{parse, render};
+use console_mermaid::diagram::{CharSet, Config};
+use console_mermaid::sequence::{ArrowType, parse, parse_with_config, render, to_mermaid};
 use std::path::Path;
 
 fn verify_sequence<P: AsRef<Path>>(path: P, use_ascii: bool) {
+    verify_sequence_with(path, &Config::new_test_config(use_ascii, "cli"));
+}
+
+fn verify_sequence_with<P: AsRef<Path>>(path: P, config: &Config) {
     let tc = sequence_testutil::read_sequence_test_case(path).expect("read sequence test");
     let diagram = parse(&tc.mermaid).expect("parse sequence");
-    let config = Config::new_test_config(use_ascii, "cli");
-    let output = render(&diagram, &config).expect("render sequence");
+    let output = render(&diagram, config).expect("render sequence");
 
     let expected = sequence_testutil::normalize_whitespace(&tc.expected);
     let actual = sequence_testutil::normalize_whitespace(&output);
@@ -26,14 +29,23 @@ fn verify_sequence<P: AsRef<Path>>(path: P, use_ascii: bool) {
 fn test_sequence_unicode_golden() {
     let base = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testdata/sequence");
     let files = [
+        "actors.txt",
         "adjacent_participants_communication.txt",
+        "async_arrows_only.txt",
         "autonumber.txt",
         "bidirectional_messages.txt",
+        "cross_arrows_only.txt",
+        "crossing_lifelines.txt",
         "dotted_arrows_only.txt",
         "four_participants.txt",
+        "long_message_label.txt",
         "long_participant_names.txt",
         "messages_without_labels.txt",
         "multiword_labels.txt",
+        "note_over_span.txt",
+        "participant_created.txt",
+        "participant_destroyed.txt",
+        "rect_shading.txt",
         "self_message.txt",
         "simple_two_participants.txt",
         "single_message.txt",
@@ -48,7 +60,11 @@ fn test_sequence_unicode_golden() {
 fn test_sequence_ascii_golden() {
     let base = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testdata/sequence-ascii");
     let files = [
+        "actors.txt",
+        "async_arrows_only.txt",
         "autonumber.txt",
+        "cross_arrows_only.txt",
+        "crossing_lifelines.txt",
         "dotted_arrows_only.txt",
         "self_message.txt",
         "simple_two_participants.txt",
@@ -59,6 +75,85 @@ fn test_sequence_ascii_golden() {
     }
 }
 
+#[test]
+fn test_sequence_dotted_lifelines_golden() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testdata");
+    for (dir, use_ascii, dotted, solid) in [
+        ("sequence", false, '┆', '│'),
+        ("sequence-ascii", true, ':', '|'),
+    ] {
+        let mut config = Config::new_test_config(use_ascii, "cli");
+        config.sequence_dotted_lifelines = true;
+        let dotted_path = root.join(dir).join("three_participants_dotted.txt");
+        verify_sequence_with(&dotted_path, &config);
+
+        // Only the lifeline glyph differs from the solid rendering.
+        let dotted_case = sequence_testutil::read_sequence_test_case(&dotted_path).unwrap();
+        let solid_case = sequence_testutil::read_sequence_test_case(
+            root.join(dir).join("three_participants.txt"),
+        )
+        .unwrap();
+        assert_eq!(
+            dotted_case.expected.replace(dotted, &solid.to_string()),
+            solid_case.expected
+        );
+    }
+}
+
+#[test]
+fn test_sequence_self_message_width_golden() {
+    let base = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testdata/sequence");
+    for width in [2, 3, 4] {
+        let mut config = Config::new_test_config(false, "cli");
+        config.sequence_self_message_width = width;
+        verify_sequence_with(
+            base.join(format!("self_message_width_{}.txt", width)),
+            &config,
+        );
+    }
+}
+
+#[test]
+fn test_sequence_rounded_participant_boxes_golden() {
+    let base = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testdata/sequence");
+    let mut config = Config::new_test_config(false, "cli");
+    config.participant_box_style = Some(CharSet::Rounded);
+    verify_sequence_with(base.join("participant_box_rounded.txt"), &config);
+
+    // ASCII output keeps its plain boxes.
+    let input = "sequenceDiagram\n    Alice->>Bob: Hello";
+    let mut ascii = Config::new_test_config(true, "cli");
+    let plain = render(&parse(input).unwrap(), &ascii).unwrap();
+    ascii.participant_box_style = Some(CharSet::Rounded);
+    assert_eq!(render(&parse(input).unwrap(), &ascii).unwrap(), plain);
+}
+
+#[test]
+fn test_light_participant_boxes_over_heavy_and_double_lifelines() {
+    let input = "sequenceDiagram\n    Alice->>Bob: Hello";
+    for (charset, tee, lifeline) in [(CharSet::Heavy, '┰', '┃'), (CharSet::Double, '╥', '║')]
+    {
+        let mut config = Config::new_test_config(false, "cli");
+        config.charset = charset;
+        config.participant_box_style = Some(CharSet::Light);
+        let output = render(&parse(input).unwrap(), &config).unwrap();
+        let bottom = output.lines().nth(2).unwrap();
+        assert_eq!(
+            bottom.trim(),
+            format!("└───{}───┘     └──{}──┘", tee, tee),
+            "{}",
+            output
+        );
+        let below = output.lines().nth(3).unwrap();
+        assert_eq!(
+            below.trim(),
+            format!("{}            {}", lifeline, lifeline),
+            "{}",
+            output
+        );
+    }
+}
+
 #[test]
 fn test_sequence_ascii_smoke() {
     let base = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testdata/sequence");
@@ -68,6 +163,7 @@ fn test_sequence_ascii_smoke() {
         "bidirectional_messages.txt",
         "dotted_arrows_only.txt",
         "four_participants.txt",
+        "long_message_label.txt",
         "long_participant_names.txt",
         "messages_without_labels.txt",
         "multiword_labels.txt",
@@ -94,3 +190,283 @@ fn test_sequence_ascii_smoke() {
         );
     }
 }
+
+#[test]
+fn test_sequence_to_mermaid_round_trip() {
+    let input = "sequenceDiagram\n    actor U as User\n    participant \"Web Server\" as Web\n    U->>\"Web Server\": GET /\n    autonumber\n    rect rgb(0, 0, 0)\n    \"Web Server\"-->>U: 200 OK\n    rect red\n    Db->>Db: vacuum\n    Note over Db,U: all done\n    end\n    end";
+    let diagram = parse(input).expect("parse");
+    let exported = to_mermaid(&diagram);
+    let reparsed = parse(&exported).expect("reparse");
+    assert_eq!(diagram, reparsed);
+    assert_eq!(exported, to_mermaid(&reparsed));
+}
+
+#[test]
+fn test_sequence_to_mermaid_keeps_activations() {
+    let input =
+        "sequenceDiagram\n    A->>+B: ask\n    activate A\n    B-->>-A: answer\n    deactivate A";
+    let diagram = parse(input).expect("parse");
+    let exported = to_mermaid(&diagram);
+    assert!(exported.contains("A->>+B: ask"), "{}", exported);
+    assert!(exported.contains("B-->>-A: answer"), "{}", exported);
+    assert!(exported.contains("    activate A\n"), "{}", exported);
+    assert!(exported.ends_with("    deactivate A\n"), "{}", exported);
+    assert_eq!(parse(&exported).expect("reparse"), diagram);
+}
+
+#[test]
+fn test_sequence_lenient_message_without_colon() {
+    let input = "sequenceDiagram\n    A->>B\n    B-->>A: done";
+    assert!(parse(input).is_err());
+
+    let mut config = Config::default_config();
+    config.sequence_lenient = true;
+    let diagram = parse_with_config(input, &config).expect("lenient parse");
+    assert_eq!(diagram.messages.len(), 2);
+    assert_eq!(diagram.messages[0].label, "");
+    assert_eq!(diagram.messages[1].label, "done");
+}
+
+#[test]
+fn test_sequence_extra_space_after_message() {
+    let plain = "sequenceDiagram\n    A->>B: first\n    B->>A: second";
+    let spaced = "sequenceDiagram\n    A->>B: first\n    +space 2\n    B->>A: second";
+    let config = Config::new_test_config(false, "cli");
+    let plain_out = render(&parse(plain).unwrap(), &config).unwrap();
+    let spaced_out = render(&parse(spaced).unwrap(), &config).unwrap();
+    assert_eq!(spaced_out.lines().count(), plain_out.lines().count() + 2);
+
+    let lines: Vec<&str> = spaced_out.lines().collect();
+    let first_arrow = lines.iter().position(|l| l.contains('►')).unwrap();
+    let second_label = lines.iter().position(|l| l.contains("second")).unwrap();
+    let gap = &lines[first_arrow + 1..second_label];
+    assert_eq!(gap.len(), 3, "{}", spaced_out);
+    assert!(
+        gap.iter()
+            .all(|l| l.trim().chars().all(|c| c == '│' || c == ' '))
+    );
+
+    assert!(parse("sequenceDiagram\n    +space 1\n    A->>B: hi").is_err());
+    let diagram = parse(spaced).unwrap();
+    assert_eq!(parse(&to_mermaid(&diagram)).unwrap(), diagram);
+}
+
+#[test]
+fn test_sequence_alias_applies_to_auto_created_participant() {
+    let input = "sequenceDiagram\n    A->>B: hi\n    participant B as Bob\n    B-->>A: hey";
+    let diagram = parse(input).expect("parse");
+    assert_eq!(diagram.participants.len(), 2);
+    assert_eq!(diagram.participants[0].id, "B");
+    assert_eq!(diagram.participants[0].label, "Bob");
+
+    let twice =
+        "sequenceDiagram\n    A->>B: hi\n    participant B as Bob\n    participant B as Robert";
+    let err = parse(twice).unwrap_err();
+    assert!(err.contains("duplicate participant"), "{}", err);
+}
+
+#[test]
+fn test_sequence_title_centered_above_participants() {
+    let plain = "sequenceDiagram\n    Alice->>Bob: hi";
+    let titled = "sequenceDiagram\n    title: Greeting\n    Alice->>Bob: hi";
+    let config = Config::new_test_config(false, "cli");
+    let plain_out = render(&parse(plain).unwrap(), &config).unwrap();
+    let diagram = parse(titled).unwrap();
+    assert_eq!(diagram.title.as_deref(), Some("Greeting"));
+    let titled_out = render(&diagram, &config).unwrap();
+
+    let lines: Vec<&str> = titled_out.lines().collect();
+    assert_eq!(lines[0].trim(), "Greeting");
+    assert_eq!(lines[1], "");
+    assert_eq!(lines[2..].join("\n"), plain_out);
+    let width = plain_out.lines().map(|l| l.chars().count()).max().unwrap();
+    let indent = lines[0].len() - lines[0].trim_start().len();
+    assert!(indent > 0 && indent <= width / 2, "{}", titled_out);
+
+    assert_eq!(parse(&to_mermaid(&diagram)).unwrap(), diagram);
+    let spaced = parse("sequenceDiagram\n    title Greeting\n    Alice->>Bob: hi").unwrap();
+    assert_eq!(spaced.title, diagram.title);
+}
+
+#[test]
+fn test_sequence_declared_participants_keep_declaration_order() {
+    let input =
+        "sequenceDiagram\n    participant B\n    participant A\n    C->>A: ping\n    A->>B: hi";
+    let diagram = parse(input).expect("parse");
+    let ids: Vec<&str> = diagram.participants.iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(ids, ["B", "A", "C"]);
+    for (idx, participant) in diagram.participants.iter().enumerate() {
+        assert_eq!(participant.index, idx);
+    }
+    assert_eq!((diagram.messages[1].from, diagram.messages[1].to), (1, 0));
+
+    let late =
+        parse("sequenceDiagram\n    A->>B: hi\n    participant B\n    participant A").unwrap();
+    let ids: Vec<&str> = late.participants.iter().map(|p| p.id.as_str()).collect();
+    assert_eq!(ids, ["B", "A"]);
+    assert_eq!((late.messages[0].from, late.messages[0].to), (1, 0));
+
+    let config = Config::new_test_config(false, "cli");
+    let output = render(&diagram, &config).unwrap();
+    let header = output.lines().nth(1).unwrap();
+    assert!(header.find('B').unwrap() < header.find('A').unwrap());
+}
+
+#[test]
+fn test_sequence_quoted_aliases_with_punctuation() {
+    let labels = [
+        "A, B",
+        "Alice: admin",
+        "Auth (primary)",
+        "x, y: (z)",
+        " padded ",
+    ];
+    for label in labels {
+        let input = format!(
+            "sequenceDiagram\n    participant A as \"{}\"\n    actor \"Data Store\" as \"{}\"\n    A->>\"Data Store\": hi",
+            label, label
+        );
+        let diagram = parse(&input).expect("parse");
+        assert_eq!(diagram.participants[0].label, label);
+        assert_eq!(diagram.participants[1].id, "Data Store");
+        assert_eq!(diagram.participants[1].label, label);
+        assert_eq!(
+            parse(&to_mermaid(&diagram)).unwrap(),
+            diagram,
+            "{}",
+            to_mermaid(&diagram)
+        );
+    }
+}
+
+#[test]
+fn test_sequence_margins() {
+    let diagram = parse("sequenceDiagram\nAlice->>Bob: Hi").unwrap();
+    let plain = render(&diagram, &Config::default_config()).unwrap();
+    let config = Config::builder()
+        .margin_top(1)
+        .margin_left(2)
+        .build()
+        .unwrap();
+    let output = render(&diagram, &config).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "");
+    for (shifted, original) in lines[1..].iter().zip(plain.lines()) {
+        assert_eq!(*shifted, format!("  {}", original));
+    }
+}
+
+#[test]
+fn test_sequence_spacing_directives() {
+    let plain = parse("sequenceDiagram\nA->>B: hi").unwrap();
+    assert_eq!(plain.participant_spacing, None);
+    assert_eq!(plain.message_spacing, None);
+    let default_output = render(&plain, &Config::default_config()).unwrap();
+    assert_eq!(default_output.lines().next().unwrap(), "┌───┐     ┌───┐");
+
+    let input = "sequenceDiagram\nparticipantSpacing=1\nmessageSpacing=3\nA->>B: hi";
+    let diagram = parse(input).unwrap();
+    assert_eq!(diagram.participant_spacing, Some(1));
+    assert_eq!(diagram.message_spacing, Some(3));
+    let mut config = Config::default_config();
+    config.sequence_participant_spacing = 8;
+    let output = render(&diagram, &config).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "┌───┐ ┌───┐");
+    assert_eq!(lines[6], "  │ hi  │");
+    assert!(to_mermaid(&diagram).contains("participantSpacing=1"));
+
+    assert!(parse("sequenceDiagram\nA->>B: hi\nmessageSpacing=3").is_err());
+}
+
+#[test]
+fn test_sequence_messages_resolved() {
+    let input = "sequenceDiagram\n    participant A as Alice\n    A->>B: hello\n    B-->>A: hi\n    A-)B: bye";
+    let diagram = parse(input).unwrap();
+    let resolved: Vec<_> = diagram.messages_resolved().collect();
+    assert_eq!(
+        resolved,
+        vec![
+            ("Alice", "B", "hello", ArrowType::Solid),
+            ("B", "Alice", "hi", ArrowType::Dotted),
+            ("Alice", "B", "bye", ArrowType::AsyncOpen),
+        ]
+    );
+}
+
+#[test]
+fn test_sequence_activation_balance() {
+    let balanced = "sequenceDiagram\n    Alice->>+Bob: hi\n    activate Alice\n    Bob-->>-Alice: hello\n    deactivate Alice";
+    let diagram = parse(balanced).expect("balanced activations parse");
+    assert_eq!(diagram.messages.len(), 2);
+    assert_eq!(diagram.participants[1].id, "Bob");
+
+    let underflow = "sequenceDiagram\n    Alice->>Bob: hi\n    Bob-->>-Alice: hello";
+    assert_eq!(
+        parse(underflow).unwrap_err(),
+        "line 3: deactivation of Bob without matching activation"
+    );
+    let err = parse("sequenceDiagram\n    activate A\n    deactivate A\n    deactivate A");
+    assert_eq!(
+        err.unwrap_err(),
+        "line 4: deactivation of A without matching activation"
+    );
+}
+
+#[test]
+fn test_sequence_autonumber_start_and_step() {
+    let numbers = |input: &str| -> Vec<usize> {
+        let diagram = parse(input).unwrap();
+        assert_eq!(parse(&to_mermaid(&diagram)).unwrap(), diagram);
+        diagram.messages.iter().map(|m| m.number).collect()
+    };
+    let body = "\n    A->>B: one\n    B->>A: two\n    A->>B: three";
+
+    assert_eq!(
+        numbers(&format!("sequenceDiagram\n    autonumber{}", body)),
+        [1, 2, 3]
+    );
+    assert_eq!(
+        numbers(&format!("sequenceDiagram\n    autonumber 10{}", body)),
+        [10, 11, 12]
+    );
+    assert_eq!(
+        numbers(&format!("sequenceDiagram\n    autonumber 10 5{}", body)),
+        [10, 15, 20]
+    );
+
+    let diagram = parse(&format!("sequenceDiagram\n    autonumber 10 5{}", body)).unwrap();
+    let output = render(&diagram, &Config::default_config()).unwrap();
+    assert!(output.contains("15. two"), "{}", output);
+}
+
+#[test]
+fn test_sequence_wide_participant_keeps_gap() {
+    let input = "sequenceDiagram\nparticipantSpacing=0\n    participant A\n    participant L as A very long participant label indeed\n    participant B\n    A->>L: hi\n    L->>B: there";
+    let diagram = parse(input).unwrap();
+    let output = render(&diagram, &Config::default_config()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines[0],
+        format!("┌───┐ ┌{}┐ ┌───┐", "─".repeat(38)),
+        "{}",
+        output
+    );
+    assert!(lines[1].contains("│ A │ │ A very long"), "{}", output);
+    for line in &lines[3..] {
+        assert_eq!(line.matches(['│', '├', '┤']).count(), 3, "{}", output);
+    }
+}
+
+#[test]
+fn test_sequence_create_and_destroy_round_trip() {
+    let input = "sequenceDiagram\n    Alice->>Bob: hi\n    create actor Carl as C\n    Alice->>Carl: hello\n    destroy Bob\n    Bob-->>Alice: bye";
+    let diagram = parse(input).unwrap();
+    assert_eq!(diagram.participants[2].created, Some(1));
+    assert_eq!(diagram.participants[1].destroyed, Some(2));
+    assert_eq!(parse(&to_mermaid(&diagram)).unwrap(), diagram);
+
+    assert!(parse("sequenceDiagram\n    A->>B: hi\n    create participant B").is_err());
+    assert!(parse("sequenceDiagram\n    create A->>B: hi").is_err());
+    assert!(parse("sequenceDiagram\n    A->>B: hi\n    destroy C").is_err());
+}
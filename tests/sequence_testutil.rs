@@ -15,7 +15,7 @@ pub fn read_sequence_test_case<P: AsRef<Path>>(path: P) -> Result<TestCase, Stri
     }
     Ok(TestCase {
         mermaid: parts[0].trim().to_string(),
-        expected: parts[1].trim().to_string(),
+        expected: parts[1].trim_matches('\n').to_string(),
     })
 }
 
@@ -0,0 +1,70 @@
+use console_mermaid::diagram::{Config, detect_diagram_type};
+use console_mermaid::render_diagram;
+
+const MACHINE: &str = "stateDiagram-v2
+[*] --> Idle
+Idle --> Running : start
+Running --> Done
+Done --> [*]";
+
+#[test]
+fn test_state_diagram_detection() {
+    assert_eq!(detect_diagram_type(MACHINE), Some("state"));
+    assert_eq!(detect_diagram_type("stateDiagram\nA --> B"), Some("state"));
+}
+
+#[test]
+fn test_three_state_machine_with_markers() {
+    let output = render_diagram(MACHINE, &Config::default_config()).unwrap();
+    let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+    assert_eq!(lines[0].trim(), "●");
+    assert_eq!(lines[1].trim(), "│");
+    for state in ["Idle", "Running", "Done"] {
+        assert!(output.contains(&format!(" {} ", state)), "{}", output);
+    }
+    assert!(output.contains("start"), "{}", output);
+    let end = lines.iter().rposition(|line| line.contains('◉')).unwrap();
+    assert_eq!(lines[end - 1].trim(), "▼", "{}", output);
+    assert_eq!(output.matches('┌').count(), 3, "{}", output);
+}
+
+#[test]
+fn test_state_markers_ascii() {
+    let mut config = Config::default_config();
+    config.use_ascii = true;
+    let output = render_diagram(MACHINE, &config).unwrap();
+    assert_eq!(output.lines().next().unwrap().trim(), "*");
+    assert!(output.contains('@'), "{}", output);
+}
+
+#[test]
+fn test_state_descriptions_and_errors() {
+    let input = "stateDiagram-v2\nstate \"Waiting for input\" as Wait\n[*] --> Wait";
+    let output = render_diagram(input, &Config::default_config()).unwrap();
+    assert!(output.contains("Waiting for input"), "{}", output);
+
+    let err = render_diagram("stateDiagram-v2\nA -> B", &Config::default_config()).unwrap_err();
+    assert_eq!(err, "line 2: invalid syntax: \"A -> B\"");
+}
+
+#[test]
+fn test_state_output_ends_on_drawn_row() {
+    let output = render_diagram(MACHINE, &Config::default_config()).unwrap();
+    assert!(output.lines().last().unwrap().contains('◉'), "{}", output);
+
+    let input = "stateDiagram-v2\ndirection LR\n[*] --> A\nA --> B\nB --> [*]";
+    let output = render_diagram(input, &Config::default_config()).unwrap();
+    assert!(
+        !output.lines().last().unwrap().trim().is_empty(),
+        "{}",
+        output
+    );
+    assert_eq!(
+        output.lines().filter(|l| l.contains('◉')).count(),
+        1,
+        "{}",
+        output
+    );
+    let row = output.lines().find(|l| l.contains('●')).unwrap();
+    assert!(row.contains("►◉"), "{}", output);
+}